@@ -1,5 +1,29 @@
+use ratatui::layout::Rect;
 use ratatui::widgets::{ListState, ScrollbarState};
 use crate::tui_list_state_tracker::ListStateTracker;
+use crate::highlight::HighlightRule;
+use crate::search::SearchState;
+use crate::log_filter::LogFilterState;
+use crate::transfer_log::TransferLog;
+use crate::transfer_log_view::TransferLogView;
+use crate::transfer_log_line_cache::TransferLogLineCache;
+use crate::line_editor::LineEditor;
+use crate::snippets::Snippet;
+use crate::tx_queue::TxQueue;
+use crate::send_template::TemplateContext;
+use crate::tx_scheduler::TxScheduler;
+use crate::file_sender::{FileSendMode, FileSender, SendFilePromptState};
+use crate::file_transfer::{FileTransfer, ReceiveFilePromptState};
+use crate::zmodem::ZmodemDetector;
+use crate::modem_lines::ModemLines;
+use crate::flow_control::FlowControl;
+use crate::line_errors::{LineError, LineErrorDecoder};
+use crate::reset_recipes::{get_builtin_reset_recipes, ResetRecipe, ResetRunner};
+use crate::rs485::{BusEchoFilter, Rs485Config};
+use crate::protocol_decoder::{DecodedView, DecoderKind};
+use crate::modbus::{ModbusPromptState, RtuFramer};
+use crate::send_history::{SendHistory, SendHistorySearchState, DEFAULT_SEND_HISTORY_MAX_ENTRIES};
+use crate::log_selection::{LogSelection, RenderedLogLayout};
 
 
 // TODO: rename to AppScreen
@@ -20,15 +44,52 @@ pub struct App {
     pub general_error_message: Option<String>,
 
     pub pick_baud_rate_input_field: String,
+    pub pick_baud_rate_list_state: ListState, // selection in COMMON_BAUD_RATES
 
-    // TODO: pick_baud_rate_active_list options
+    // where the pick lists were drawn in the last frame, for mouse clicks
+    pub pick_serial_port_list_rect: Rect,
+    pub pick_baud_rate_list_rect: Rect,
 
-    pub main_input: String, // TODO: maybe make this a Vec<u8> instead, probably
-    pub main_input_send_history: Vec<String>, // for up-arrow history
+    pub main_input: LineEditor, // TODO: maybe make this a Vec<u8> instead, probably
+    pub main_input_send_history: SendHistory, // for up-arrow history
     pub main_input_send_history_index: Option<usize>,
+    pub main_input_send_history_search: Option<SendHistorySearchState>, // Some while the Ctrl+R prompt is open
     pub main_input_typing_in_progress_but_not_sent: Option<String>, // so that if you look through the send history, you can still send the current in-progress message
-    pub main_input_cursor_position: Option<usize>,
-    pub main_screen_transfer_log: Vec<TransferLogEntry>,
+    pub tx_queue: TxQueue, // everything sent goes through here (Send box, snippets)
+    pub send_template_context: TemplateContext, // {counter}, {random}
+    pub main_input_schedule_prompt: Option<String>, // Some while typing how to repeat the Send box's text (Alt+R)
+
+    /// Periodic/repeated sends, shown in the scheduler panel (only while there are any).
+    pub tx_scheduler: TxScheduler,
+    pub scheduler_panel_list_state: ListState,
+    pub scheduler_panel_rect: Rect, // where it was drawn in the last frame, for mouse clicks
+
+    /// The file being sent (Ctrl+O, or `--send-file`), if any.
+    pub file_sender: Option<FileSender>,
+    pub send_file_prompt: Option<SendFilePromptState>, // Some while typing the path of the file to send
+    pub send_file_on_start: Option<std::path::PathBuf>, // from `--send-file`, sent once the port is open
+
+    /// The XMODEM/YMODEM transfer, if any. While it runs, received data goes to it (not the transfer log).
+    /// It's kept once it's finished, so that its popup shows how it went, until it's closed.
+    pub file_transfer: Option<Box<dyn FileTransfer>>,
+    pub receive_file_prompt: Option<ReceiveFilePromptState>, // Some while typing where to save a received file (Alt+O)
+    pub zmodem_detector: ZmodemDetector, // offers to receive/send when the other side runs `sz`/`rz`
+
+    /// CTS/DSR/RI/CD (polled) and DTR/RTS (toggled with Alt+1/Alt+2), shown in the status strip.
+    pub modem_lines: ModemLines,
+    pub is_tx_paused_by_xoff: bool, // with XON/XOFF flow control, until the next XON
+    pub break_end_time: Option<std::time::Instant>, // Some while a BREAK is being sent (Alt+3)
+
+    /// Takes the marks for received breaks and line errors out of the received data.
+    /// None if the port can't mark them (e.g., on Windows).
+    pub line_error_decoder: Option<LineErrorDecoder>,
+    pub reset_runner: Option<ResetRunner>, // Some while a reset recipe is running
+    pub bus_echo_filter: BusEchoFilter, // in RS-485 mode, for our own bytes coming back from the bus
+    pub rtu_framer: RtuFramer, // with the Modbus RTU decoder, splits received data into frames (rather than lines)
+    pub modbus_prompt: Option<ModbusPromptState>, // Some while the Modbus master console is open (Alt+M)
+    pub main_screen_transfer_log: TransferLog,
+    pub main_screen_log_view: TransferLogView,
+    pub main_screen_line_cache: TransferLogLineCache,
 
     pub bound_serial_port: Option<Box<serialport5::SerialPort>>,
 
@@ -38,6 +99,34 @@ pub struct App {
     pub main_screen_vert_scroll_val: usize,
     pub main_screen_horiz_scroll_val: usize,
     pub main_screen_vert_scroll_pos: ScrollPosition,
+    pub main_screen_search: SearchState,
+    pub main_screen_filter: LogFilterState,
+
+    /// Index of the log entry at the top of the view (as of the last render).
+    pub main_screen_top_entry_idx: usize,
+    pub main_screen_vert_scroll_val_at_filter_restore: Option<usize>,
+
+    // where the Main screen's regions were drawn in the last frame, for mouse clicks
+    pub main_screen_input_rect: Rect,
+    pub main_screen_log_layout: RenderedLogLayout,
+
+    /// The text being selected with the mouse in the transfer log (while dragging).
+    pub main_screen_log_selection: Option<LogSelection>,
+
+    /// Snippets from the workspace's snippets file, sent from the picker (Ctrl+P) or with F1-F12.
+    pub snippets: Vec<Snippet>,
+    pub snippet_picker_list_state: Option<ListState>, // Some while the picker is open
+    pub snippet_picker_list_rect: Rect,
+
+    /// Start of the current session (when the serial port was bound), for relative timestamps.
+    pub session_start_time: chrono::DateTime<chrono::Local>,
+
+    /// Short-lived message shown in the footer (e.g., "Exported log to ...").
+    pub status_message: Option<String>,
+
+    pub config1_rule_list_state: ListState,
+    pub config1_rule_input: Option<String>, // Some while typing a new highlight rule
+    pub config1_error_message: Option<String>,
 }
 
 impl App {
@@ -51,13 +140,41 @@ impl App {
             general_error_message: None,
             
             pick_baud_rate_input_field: String::new(),
+            pick_baud_rate_list_state: ListState::default(),
+
+            pick_serial_port_list_rect: Rect::default(),
+            pick_baud_rate_list_rect: Rect::default(),
 
-            main_input: String::new(),
-            main_input_send_history: Vec::new(),
+            main_input: LineEditor::new(),
+            main_input_send_history: SendHistory::new(DEFAULT_SEND_HISTORY_MAX_ENTRIES),
             main_input_send_history_index: None,
+            main_input_send_history_search: None,
             main_input_typing_in_progress_but_not_sent: None,
-            main_input_cursor_position: None,
-            main_screen_transfer_log: Vec::new(),
+            tx_queue: TxQueue::new(),
+            send_template_context: TemplateContext::new(),
+            main_input_schedule_prompt: None,
+
+            tx_scheduler: TxScheduler::new(),
+            scheduler_panel_list_state: ListState::default(),
+            scheduler_panel_rect: Rect::default(),
+
+            file_sender: None,
+            send_file_prompt: None,
+            send_file_on_start: None,
+            file_transfer: None,
+            receive_file_prompt: None,
+            zmodem_detector: ZmodemDetector::new(),
+            modem_lines: ModemLines::new(),
+            is_tx_paused_by_xoff: false,
+            break_end_time: None,
+            line_error_decoder: None,
+            reset_runner: None,
+            bus_echo_filter: BusEchoFilter::new(),
+            rtu_framer: RtuFramer::new(),
+            modbus_prompt: None,
+            main_screen_transfer_log: TransferLog::new(DEFAULT_SCROLLBACK_MAX_LINES_IN_MEMORY, None),
+            main_screen_log_view: TransferLogView::new(),
+            main_screen_line_cache: TransferLogLineCache::new(),
 
             bound_serial_port: None,
 
@@ -67,75 +184,148 @@ impl App {
             main_screen_vert_scroll_val: 0,
             main_screen_horiz_scroll_val: 0,
             main_screen_vert_scroll_pos: ScrollPosition::PinnedAtEnd,
+            main_screen_search: SearchState::new(),
+            main_screen_filter: LogFilterState::new(),
+            main_screen_top_entry_idx: 0,
+            main_screen_vert_scroll_val_at_filter_restore: None,
+
+            main_screen_input_rect: Rect::default(),
+            main_screen_log_layout: RenderedLogLayout::new(),
+            main_screen_log_selection: None,
+
+            snippets: Vec::new(),
+            snippet_picker_list_state: None,
+            snippet_picker_list_rect: Rect::default(),
+
+            session_start_time: chrono::Local::now(),
+
+            status_message: None,
+
+            config1_rule_list_state: ListState::default(),
+            config1_rule_input: None,
+            config1_error_message: None,
         }
     }
     
-    pub fn add_rxd_serial_data_to_transfer_log(&mut self, new_data: Vec<u8>) {
-        // If the last element in the transfer log is a SerialData. 
-        // Otherwise, add a new SerialData element.
-
+    /// Adds received bytes to the transfer log, splitting them into lines.
+    /// `timestamp` should be the time the bytes were read from the port, and is used as the
+    /// timestamp of any line that starts within `new_data`.
+    pub fn add_rxd_serial_data_to_transfer_log(&mut self, new_data: Vec<u8>, timestamp: chrono::DateTime<chrono::Local>) {
         // split by newline, loop through, add each to the transfer log (either append to last element or add new element)
-
-        // Loop through each chunk and process it
         let delimiter: u8 = 0x0A; // newline // TODO: read from config, or something
+        let chunk_count = new_data.split(|&x| x == delimiter).count();
         for (chunk_num, chunk) in new_data.split(|&x| x == delimiter).enumerate() {
-            let is_chunk_final = chunk.last() == Some(&delimiter);
-            
-            // This chunk_num check is an optimization to prevent looking into the last
-            // element of the transfer log if it's not necessary.
+            // every chunk except the last one was terminated by a delimiter
+            let is_chunk_final = chunk_num < (chunk_count - 1);
+
+            // The first chunk continues the last line in the log, if that line is still open.
             if chunk_num == 0 {
                 if let Some(last_elem) = self.main_screen_transfer_log.last_mut() {
-                    match last_elem.log_type {
-                        TransferLogType::SerialData => {
-                            match last_elem.is_final {
-                                true => {
-                                    self.main_screen_transfer_log.push(
-                                        TransferLogEntry::new(
-                                            chunk.to_vec(),
-                                            TransferLogType::SerialData,
-                                            is_chunk_final
-                                        )
-                                    );
-                                }
-                                false => {
-                                    last_elem.data_as_bytes.extend(chunk);
-                                    last_elem.is_final = is_chunk_final;
-                                }
-                            }
-                        }
-                        _ => {
-
-                            self.main_screen_transfer_log.push(
-                                TransferLogEntry::new(
-                                    chunk.to_vec(),
-                                    TransferLogType::SerialData,
-                                    is_chunk_final
-                                )
-                            );
-                        }
+                    if let (TransferLogType::SerialData, false) = (&last_elem.log_type, last_elem.is_final) {
+                        last_elem.data_as_bytes.extend(chunk);
+                        last_elem.is_final = is_chunk_final;
+                        continue;
                     }
                 }
-            } else {
-                self.main_screen_transfer_log.push(
-                    TransferLogEntry::new(
-                        chunk.to_vec(),
-                        TransferLogType::SerialData,
-                        is_chunk_final
-                    )
-                );
             }
+
+            // Don't start a new (empty) line until its first byte actually arrives, so that
+            // the line's timestamp is the time its first byte was read.
+            if chunk.is_empty() && !is_chunk_final {
+                continue;
+            }
+
+            self.push_to_transfer_log(
+                TransferLogEntry::new(
+                    chunk.to_vec(),
+                    TransferLogType::SerialData,
+                    is_chunk_final,
+                    timestamp,
+                )
+            );
+        }
+    }
+
+    /// Adds a received frame (e.g., Modbus RTU, split by silence) as an entry of its own.
+    pub fn add_rxd_frame_to_transfer_log(&mut self, frame: Vec<u8>, timestamp: chrono::DateTime<chrono::Local>) {
+        if let Some(last_elem) = self.main_screen_transfer_log.last_mut() {
+            last_elem.is_final = true;
         }
+        self.push_to_transfer_log(
+            TransferLogEntry::new(
+                frame,
+                TransferLogType::SerialData,
+                true,
+                timestamp,
+            )
+        );
     }
 
     pub fn add_echo_to_transfer_log(&mut self, new_data: Vec<u8>) {
         if let Some(last_elem) = self.main_screen_transfer_log.last_mut() {
             last_elem.is_final = true;
         }
-        self.main_screen_transfer_log.push(
+        self.push_to_transfer_log(
             TransferLogEntry::new(
                 new_data,
                 TransferLogType::EchoData,
-                true
+                true,
+                chrono::Local::now(),
+            )
+        );
+    }
+
+    /// Why sending is on hold because of flow control, a BREAK, or a reset (None if it isn't).
+    pub fn get_tx_pause_reason(&self) -> Option<&'static str> {
+        if self.break_end_time.is_some() {
+            return Some("Sending BREAK");
+        }
+        if self.reset_runner.is_some() {
+            return Some("Resetting board");
+        }
+        match self.app_config.flow_control {
+            FlowControl::None => None,
+            FlowControl::Hardware => match self.modem_lines.inputs {
+                Some(inputs) if !inputs.cts => Some("TX paused (CTS off)"),
+                _ => None,
+            },
+            FlowControl::Software => match self.is_tx_paused_by_xoff {
+                true => Some("TX paused by XOFF"),
+                false => None,
+            },
+        }
+    }
+
+    /// Adds something that happened on the port (not data), like a modem control line change.
+    pub fn add_event_to_transfer_log(&mut self, event: String) {
+        if let Some(last_elem) = self.main_screen_transfer_log.last_mut() {
+            last_elem.is_final = true;
+        }
+        self.push_to_transfer_log(
+            TransferLogEntry::new(
+                event.into_bytes(),
+                TransferLogType::EventData,
+                true,
+                chrono::Local::now(),
+            )
+        );
+    }
+
+    /// Adds a received break or line error, where it was in the received data.
+    pub fn add_line_error_to_transfer_log(&mut self, line_error: LineError, timestamp: chrono::DateTime<chrono::Local>) {
+        if let Some(last_elem) = self.main_screen_transfer_log.last_mut() {
+            last_elem.is_final = true;
+        }
+        self.push_to_transfer_log(
+            TransferLogEntry::new(
+                line_error.get_description().into_bytes(),
+                match line_error {
+                    LineError::Break => TransferLogType::BreakData,
+                    LineError::Framing(_) => TransferLogType::FramingErrorData,
+                    LineError::Parity(_) => TransferLogType::ParityErrorData,
+                },
+                true,
+                timestamp,
             )
         );
     }
@@ -144,19 +334,35 @@ impl App {
         if let Some(last_elem) = self.main_screen_transfer_log.last_mut() {
             last_elem.is_final = true;
         }
-        self.main_screen_transfer_log.push(
+        self.push_to_transfer_log(
             TransferLogEntry::new(
                 new_data.into_bytes(),
                 TransferLogType::ErrorData,
-                true
+                true,
+                chrono::Local::now(),
             )
         );
     }
+
+    fn push_to_transfer_log(&mut self, entry: TransferLogEntry) {
+        self.main_screen_transfer_log.push(entry);
+        self.show_spool_error_message();
+    }
+
+    /// Shows the error from the last failed spool write or read (e.g., while drawing the scrollback), if any.
+    pub fn show_spool_error_message(&mut self) {
+        if let Some(spool_error_message) = self.main_screen_transfer_log.take_spool_error_message() {
+            self.status_message = Some(spool_error_message);
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct TransferLogEntry {
     pub data_as_bytes: Vec<u8>,
     pub log_type: TransferLogType,
+
+    /// When the first byte of this entry was read (or written, for echoes).
     pub timestamp: chrono::DateTime<chrono::Local>,
     
     /// Whether this is the final entry in the log, or if it can still be written to.
@@ -164,36 +370,55 @@ pub struct TransferLogEntry {
 }
 
 impl TransferLogEntry {
-    pub fn new(data: Vec<u8>, log_type: TransferLogType, is_final: bool) -> Self {
+    pub fn new(data: Vec<u8>, log_type: TransferLogType, is_final: bool, timestamp: chrono::DateTime<chrono::Local>) -> Self {
         Self {
             data_as_bytes: data,
             log_type,
-            timestamp: chrono::Local::now(),
+            timestamp,
             is_final,
         }
     }
 
+    /// The entry's data as text. Bytes that aren't valid UTF-8 (e.g., in a binary protocol's frames) are shown as `\xHH`.
     pub fn get_data_as_string(&self) -> String {
-        let string_val = match self.log_type {
-            TransferLogType::SerialData => {
-                String::from_utf8(self.data_as_bytes.clone()).expect("Data should be UTF-8, for now")
-            }
-            TransferLogType::EchoData => {
-                String::from_utf8(self.data_as_bytes.clone()).expect("Data should be UTF-8, for now")
+        decode_bytes_lossy(&self.data_as_bytes).trim_end().to_string()
+    }
+}
+
+/// Decodes UTF-8, replacing each invalid byte with its `\xHH` escape (instead of the U+FFFD character,
+/// so that the actual byte values can still be seen).
+pub fn decode_bytes_lossy(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    let mut remaining = bytes;
+    loop {
+        match std::str::from_utf8(remaining) {
+            Ok(valid_str) => {
+                out.push_str(valid_str);
+                return out;
             }
-            TransferLogType::ErrorData => {
-                String::from_utf8(self.data_as_bytes.clone()).expect("Error messages should be UTF-8")
+            Err(e) => {
+                let (valid_bytes, after_valid) = remaining.split_at(e.valid_up_to());
+                out.push_str(std::str::from_utf8(valid_bytes).expect("Bytes were checked to be valid"));
+                // an incomplete sequence at the end has no error_len(); show each of its bytes
+                let invalid_len = e.error_len().unwrap_or(after_valid.len());
+                for byte in &after_valid[..invalid_len] {
+                    out.push_str(&format!("\\x{:02X}", byte));
+                }
+                remaining = &after_valid[invalid_len..];
             }
-        }.trim_end().to_string();
-        
-        string_val
+        }
     }
 }
 
+#[derive(Clone)]
 pub enum TransferLogType {
     SerialData,
     EchoData,
     ErrorData,
+    EventData, // e.g., modem control line changes
+    BreakData, // a received break
+    FramingErrorData,
+    ParityErrorData,
     // TODO: maybe other
 }
 
@@ -203,6 +428,13 @@ pub enum ScrollPosition {
     PinnedAtEnd,
 }
 
+/// Baud rates offered in the list on the PickBaudRate screen.
+pub const COMMON_BAUD_RATES: [u32; 12] = [
+    300, 1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600,
+];
+
+pub const DEFAULT_SCROLLBACK_MAX_LINES_IN_MEMORY: usize = 100_000;
+
 pub struct AppConfig {
     // NOTE: serial_port is not here because it's not cross-environment; it will however be a CLI argument
     
@@ -215,13 +447,58 @@ pub struct AppConfig {
     pub parity: serialport5::Parity,
     pub stop_bits: serialport5::StopBits,
 
+    pub flow_control: FlowControl,
+    pub break_duration: std::time::Duration, // how long the line is held low when sending a BREAK
+
+    /// Built-in and config file reset recipes (DTR/RTS sequences), and the one to run when the port is opened.
+    pub reset_recipes: Vec<ResetRecipe>,
+    pub reset_on_connect: Option<String>,
 
+    pub rs485: Option<Rs485Config>, // Some in RS-485 (half-duplex) mode
 
-    // pub line_wrap: bool, // TODO: implement line wrap
+    /// Protocol decoder for the session's frames, and how they're shown (cycled with D in the transfer log).
+    pub decoder: Option<DecoderKind>,
+    pub decoded_view: DecodedView,
+
+
+
+    /// Soft-wrap long lines in the transfer log, instead of scrolling horizontally.
+    pub line_wrap: bool,
+
+    /// Capture the mouse (scrolling, clicking, selecting). When off, the terminal's own selection works instead.
+    pub mouse_capture: bool,
     // pub show_borders: bool, // TODO: implement show/hide borders
     // pub show_help: bool, // TODO: implement show/hide help at bottom
     pub echo_mode: EchoMode,
-
+    pub multi_line_send_policy: MultiLineSendPolicy,
+    pub multi_line_send_delay: std::time::Duration,
+
+    /// Turn backslash escapes (`\r`, `\x1b`, ...) in sent text into the bytes they stand for.
+    pub send_parse_escapes: bool,
+    /// Expand placeholders like `{counter}` and `{crc16_modbus}` in sent text (see `expand_send_text`).
+    pub send_expand_templates: bool,
+    pub timestamp_mode: TimestampMode,
+
+    /// Regex highlight rules for the transfer log, applied in order (later rules win).
+    pub highlight_rules: Vec<HighlightRule>,
+
+    /// Max number of transfer log lines kept in memory. Older lines are moved to the spool file, or dropped.
+    pub scrollback_max_lines_in_memory: usize,
+    pub scrollback_spill_to_disk: bool,
+    pub scrollback_spool_dir: Option<std::path::PathBuf>, // None = system temp directory
+
+    /// How files are sent (see `FileSendOptions`).
+    pub file_send_mode: FileSendMode,
+    pub file_send_chunk_size: usize,
+    pub file_send_chunk_delay: std::time::Duration,
+    pub file_send_line_delay: std::time::Duration,
+    pub file_send_prompt_regex: Option<regex::Regex>, // in line mode, wait for this before sending the next line
+    pub file_send_prompt_timeout: std::time::Duration,
+
+    /// Save the send history to a file, so that it's kept across runs.
+    pub send_history_persist: bool,
+    pub send_history_per_device: bool, // within a workspace; outside of one, it's always per device
+    pub send_history_max_entries: usize,
 }
 
 impl AppConfig {
@@ -232,8 +509,39 @@ impl AppConfig {
             data_bits: 8,
             parity: serialport5::Parity::None,
             stop_bits: serialport5::StopBits::One,
+            flow_control: FlowControl::None,
+            break_duration: std::time::Duration::from_millis(250),
+            reset_recipes: get_builtin_reset_recipes(),
+            reset_on_connect: None,
+            rs485: None,
+            decoder: None,
+            decoded_view: DecodedView::Inline,
 
             echo_mode: EchoMode::On,
+            multi_line_send_policy: MultiLineSendPolicy::Whole,
+            multi_line_send_delay: std::time::Duration::ZERO,
+            send_parse_escapes: false,
+            send_expand_templates: false,
+            timestamp_mode: TimestampMode::Off,
+            line_wrap: false,
+            mouse_capture: true,
+
+            highlight_rules: Vec::new(),
+
+            scrollback_max_lines_in_memory: DEFAULT_SCROLLBACK_MAX_LINES_IN_MEMORY,
+            scrollback_spill_to_disk: false,
+            scrollback_spool_dir: None,
+
+            file_send_mode: FileSendMode::Raw,
+            file_send_chunk_size: 64,
+            file_send_chunk_delay: std::time::Duration::ZERO,
+            file_send_line_delay: std::time::Duration::ZERO,
+            file_send_prompt_regex: None,
+            file_send_prompt_timeout: std::time::Duration::from_secs(5),
+
+            send_history_persist: true, // can be turned off, so that secrets typed into device shells aren't saved
+            send_history_per_device: false,
+            send_history_max_entries: DEFAULT_SEND_HISTORY_MAX_ENTRIES,
         }
     }
 }
@@ -241,23 +549,28 @@ impl AppConfig {
 pub enum MainScreenActiveRegion {
     Input,
     InputEolChoice, // PERMANENTLY DISABLED, for now
+    SchedulerPanel, // skipped while there are no scheduled sends
     OutputScrollBars,
 }
 
 impl MainScreenActiveRegion {
-    pub fn next(&self) -> MainScreenActiveRegion {
-        match self {
-            MainScreenActiveRegion::Input => MainScreenActiveRegion::OutputScrollBars,
-            MainScreenActiveRegion::OutputScrollBars => MainScreenActiveRegion::Input,
-            MainScreenActiveRegion::InputEolChoice => unimplemented!(),
+    pub fn next(&self, has_scheduler_panel: bool) -> MainScreenActiveRegion {
+        match (self, has_scheduler_panel) {
+            (MainScreenActiveRegion::Input, true) => MainScreenActiveRegion::SchedulerPanel,
+            (MainScreenActiveRegion::Input, false) => MainScreenActiveRegion::OutputScrollBars,
+            (MainScreenActiveRegion::SchedulerPanel, _) => MainScreenActiveRegion::OutputScrollBars,
+            (MainScreenActiveRegion::OutputScrollBars, _) => MainScreenActiveRegion::Input,
+            (MainScreenActiveRegion::InputEolChoice, _) => unimplemented!(),
         }
     }
 
-    pub fn prev(&self) -> MainScreenActiveRegion {
-        match self {
-            MainScreenActiveRegion::Input => MainScreenActiveRegion::OutputScrollBars,
-            MainScreenActiveRegion::OutputScrollBars => MainScreenActiveRegion::Input,
-            MainScreenActiveRegion::InputEolChoice => unimplemented!(),
+    pub fn prev(&self, has_scheduler_panel: bool) -> MainScreenActiveRegion {
+        match (self, has_scheduler_panel) {
+            (MainScreenActiveRegion::Input, _) => MainScreenActiveRegion::OutputScrollBars,
+            (MainScreenActiveRegion::SchedulerPanel, _) => MainScreenActiveRegion::Input,
+            (MainScreenActiveRegion::OutputScrollBars, true) => MainScreenActiveRegion::SchedulerPanel,
+            (MainScreenActiveRegion::OutputScrollBars, false) => MainScreenActiveRegion::Input,
+            (MainScreenActiveRegion::InputEolChoice, _) => unimplemented!(),
         }
     }
 }
@@ -267,3 +580,60 @@ pub enum EchoMode {
     Off,
 }
 
+/// How the timestamp gutter in the transfer log is shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimestampMode {
+    Off,
+    /// Wall-clock time, with millisecond precision.
+    Absolute,
+    /// Time since the session started.
+    Relative,
+    /// Time since the previous line in the log.
+    Delta,
+}
+
+impl TimestampMode {
+    pub fn next(&self) -> TimestampMode {
+        match self {
+            TimestampMode::Off => TimestampMode::Absolute,
+            TimestampMode::Absolute => TimestampMode::Relative,
+            TimestampMode::Relative => TimestampMode::Delta,
+            TimestampMode::Delta => TimestampMode::Off,
+        }
+    }
+
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            TimestampMode::Off => "Off",
+            TimestampMode::Absolute => "Absolute",
+            TimestampMode::Relative => "Relative",
+            TimestampMode::Delta => "Delta",
+        }
+    }
+}
+
+/// How a multi-line block in the Send box is sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MultiLineSendPolicy {
+    /// One write, with the EOL after each line.
+    Whole,
+    /// One write per line (each with the EOL), with `multi_line_send_delay` between lines.
+    LineByLine,
+}
+
+impl MultiLineSendPolicy {
+    pub fn from_config_str(value: &str) -> Result<Self, String> {
+        match value {
+            "whole" => Ok(MultiLineSendPolicy::Whole),
+            "line_by_line" => Ok(MultiLineSendPolicy::LineByLine),
+            _ => Err(format!("Invalid multi_line_send value '{}' (expected 'whole' or 'line_by_line')", value)),
+        }
+    }
+
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            MultiLineSendPolicy::Whole => "whole",
+            MultiLineSendPolicy::LineByLine => "line by line",
+        }
+    }
+}
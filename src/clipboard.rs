@@ -0,0 +1,64 @@
+use std::io::Write;
+
+/// Most text (in bytes) that's copied at once. Terminals truncate or reject longer OSC 52 sequences.
+pub const CLIPBOARD_MAX_BYTES: usize = 100 * 1024;
+
+/// Copies `text` to the system clipboard using the OSC 52 terminal escape sequence.
+/// This works over SSH, as long as the terminal supports OSC 52 (most modern ones do).
+/// Inside tmux, the sequence is passed through to the outer terminal (needs `set -g allow-passthrough on` in tmux 3.3+).
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    let sequence = get_osc52_sequence(text, std::env::var_os("TMUX").is_some());
+
+    // the terminal is drawn on stderr, so the escape sequence has to go there too
+    let mut stderr = std::io::stderr();
+    stderr.write_all(sequence.as_bytes())?;
+    stderr.flush()
+}
+
+/// The escape sequence that sets the clipboard to `text`, wrapped for tmux if `is_in_tmux`.
+fn get_osc52_sequence(text: &str, is_in_tmux: bool) -> String {
+    let osc52_sequence = format!("\x1b]52;c;{}\x07", encode_base64(text.as_bytes()));
+    match is_in_tmux {
+        // tmux's DCS passthrough: every ESC inside has to be doubled
+        true => format!("\x1bPtmux;{}\x1b\\", osc52_sequence.replace('\x1b', "\x1b\x1b")),
+        false => osc52_sequence,
+    }
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | (bytes[2] as u32);
+        for char_num in 0..4 {
+            match char_num <= chunk.len() {
+                true => out.push(ALPHABET[((group >> (18 - 6 * char_num)) & 0x3F) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_sequence() {
+        assert_eq!(get_osc52_sequence("hello", false), "\x1b]52;c;aGVsbG8=\x07");
+        assert_eq!(get_osc52_sequence("hello", true), "\x1bPtmux;\x1b\x1b]52;c;aGVsbG8=\x07\x1b\\");
+        assert_eq!(get_osc52_sequence("", false), "\x1b]52;c;\x07");
+    }
+
+    #[test]
+    fn base64_padding() {
+        // the test vectors from RFC 4648
+        let encoded: Vec<String> = ["", "f", "fo", "foo", "foob", "fooba", "foobar"].iter()
+            .map(|text| encode_base64(text.as_bytes()))
+            .collect();
+        assert_eq!(encoded, vec!["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy"]);
+        assert_eq!(encode_base64(&[0xFB, 0xFF]), "+/8=");
+    }
+}
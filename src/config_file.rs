@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::app::{AppConfig, MultiLineSendPolicy};
+use crate::highlight::HighlightRule;
+use crate::file_sender::FileSendMode;
+use crate::flow_control::FlowControl;
+use crate::reset_recipes::{ResetRecipe, ResetStep};
+use crate::rs485::{BusEchoMode, Rs485Config};
+use crate::protocol_decoder::DecoderKind;
+use crate::modem_lines::ModemOutputLine;
+
+/// The per-workspace directory (similar to `.vscode/`), relative to the current directory.
+pub const WORKSPACE_DIR_NAME: &str = ".baud-boss";
+
+/// Name of the config file within the workspace directory.
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// The contents of a config file. Every field is optional; anything missing keeps its default.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub line_wrap: Option<bool>,
+
+    /// "none", "rts_cts", or "xon_xoff".
+    pub flow_control: Option<String>,
+    /// How long a BREAK (Alt+3) holds the line low.
+    pub break_duration_ms: Option<u64>,
+    /// Protocol decoder for the transfer log: "nmea".
+    pub decoder: Option<String>,
+
+    /// Name of a reset recipe to run each time the port is opened.
+    pub reset_on_connect: Option<String>,
+
+    /// How multi-line blocks in the Send box are sent: "whole" or "line_by_line".
+    pub multi_line_send: Option<String>,
+    pub multi_line_delay_ms: Option<u64>,
+
+    /// Parse backslash escapes (`\r`, `\x1b`, ...) in sent text.
+    pub send_escapes: Option<bool>,
+    /// Expand placeholders (`{counter}`, `{crc16_modbus}`, ...) in sent text.
+    pub send_templates: Option<bool>,
+
+    #[serde(default)]
+    pub highlight_rules: Vec<HighlightRuleConfig>,
+
+    pub scrollback: Option<ScrollbackConfig>,
+
+    pub send_history: Option<SendHistoryConfig>,
+
+    pub send_file: Option<SendFileConfig>,
+
+    #[serde(default)]
+    pub reset_recipes: Vec<ResetRecipeConfig>,
+
+    pub rs485: Option<Rs485FileConfig>,
+}
+
+/// A highlight rule, as written in the config file.
+///
+/// ```toml
+/// [[highlight_rules]]
+/// pattern = "ERROR"
+/// style = "red,bold"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightRuleConfig {
+    pub pattern: String,
+    pub style: String,
+}
+
+/// A reset recipe, as written in the config file (with the same name as a built-in one, it replaces it).
+///
+/// ```toml
+/// [[reset_recipes]]
+/// name = "stm32_bootloader"
+/// steps = ["dtr on", "rts on", "wait 100", "rts off", "wait 50", "dtr off"]
+/// key = "alt+7" # optional, "alt+4" to "alt+9"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResetRecipeConfig {
+    pub name: String,
+    pub steps: Vec<String>,
+    pub key: Option<String>,
+}
+
+/// RS-485 (half-duplex) mode. The section turns it on.
+///
+/// ```toml
+/// [rs485]
+/// direction_line = "rts" # "rts" (default) or "dtr": the line that enables the transceiver's driver
+/// active_low = false # the driver is enabled with the line off
+/// pre_delay_ms = 1 # between enabling the driver and writing
+/// post_delay_ms = 1 # between the last byte going out and disabling the driver
+/// echo = "none" # "none" (default), "suppress", or "mark": our own bytes, if they come back from the bus
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rs485FileConfig {
+    pub direction_line: Option<String>,
+    pub active_low: Option<bool>,
+    pub pre_delay_ms: Option<u64>,
+    pub post_delay_ms: Option<u64>,
+    pub echo: Option<String>,
+}
+
+/// Limits for the transfer log's scrollback.
+///
+/// ```toml
+/// [scrollback]
+/// max_lines_in_memory = 100000
+/// spill_to_disk = true # keep older lines in a spool file, instead of dropping them
+/// spool_dir = "/var/tmp" # defaults to the system temp directory
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScrollbackConfig {
+    pub max_lines_in_memory: Option<usize>,
+    pub spill_to_disk: Option<bool>,
+    pub spool_dir: Option<PathBuf>,
+}
+
+/// Where (and whether) the send history is saved.
+///
+/// ```toml
+/// [send_history]
+/// persist = true # save it in the workspace directory (or in ~/.baud-boss/, outside of a workspace); on by default
+/// per_device = true # keep a separate history for each serial port
+/// max_entries = 1000
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendHistoryConfig {
+    pub persist: Option<bool>,
+    pub per_device: Option<bool>,
+    pub max_entries: Option<usize>,
+}
+
+/// Pacing for sending files (Ctrl+O, or `--send-file`).
+///
+/// ```toml
+/// [send_file]
+/// mode = "lines" # "raw" (default), "lines", or "hex"
+/// chunk_size = 16 # bytes written at once
+/// chunk_delay_ms = 5
+/// line_delay_ms = 20
+/// wait_for_prompt = '>>> $' # line mode: wait for this regex in the received data before the next line
+/// prompt_timeout_ms = 5000
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SendFileConfig {
+    pub mode: Option<String>,
+    pub chunk_size: Option<usize>,
+    pub chunk_delay_ms: Option<u64>,
+    pub line_delay_ms: Option<u64>,
+    pub wait_for_prompt: Option<String>,
+    pub prompt_timeout_ms: Option<u64>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading config file '{}': {}", path.display(), e))?;
+        let config_file: ConfigFile = toml::from_str(&contents)
+            .map_err(|e| format!("Error parsing config file '{}': {}", path.display(), e))?;
+        Ok(config_file)
+    }
+
+    /// Applies the values from the config file on top of `app_config`.
+    pub fn apply_to(&self, app_config: &mut AppConfig) -> Result<(), Box<dyn Error>> {
+        if let Some(line_wrap) = self.line_wrap {
+            app_config.line_wrap = line_wrap;
+        }
+        if let Some(flow_control) = &self.flow_control {
+            app_config.flow_control = FlowControl::from_config_str(flow_control)?;
+        }
+        if let Some(break_duration_ms) = self.break_duration_ms {
+            app_config.break_duration = std::time::Duration::from_millis(break_duration_ms);
+        }
+        for recipe in self.reset_recipes.iter() {
+            let recipe = ResetRecipe {
+                name: recipe.name.clone(),
+                steps: recipe.steps.iter().map(|step| ResetStep::from_config_str(step)).collect::<Result<_, _>>()?,
+                alt_key: recipe.key.as_deref().map(ResetRecipe::parse_alt_key).transpose()?,
+            };
+            app_config.reset_recipes.retain(|existing_recipe| existing_recipe.name != recipe.name);
+            app_config.reset_recipes.push(recipe);
+        }
+        if let Some(reset_on_connect) = &self.reset_on_connect {
+            app_config.reset_on_connect = Some(reset_on_connect.clone());
+        }
+        if let Some(rs485) = &self.rs485 {
+            let mut rs485_config = Rs485Config::new();
+            if let Some(direction_line) = &rs485.direction_line {
+                rs485_config.direction_line = match direction_line.as_str() {
+                    "rts" => ModemOutputLine::Rts,
+                    "dtr" => ModemOutputLine::Dtr,
+                    _ => return Err(format!("Invalid rs485.direction_line '{}' (expected \"rts\" or \"dtr\")", direction_line).into()),
+                };
+            }
+            if let Some(active_low) = rs485.active_low {
+                rs485_config.is_active_low = active_low;
+            }
+            if let Some(pre_delay_ms) = rs485.pre_delay_ms {
+                rs485_config.pre_delay = std::time::Duration::from_millis(pre_delay_ms);
+            }
+            if let Some(post_delay_ms) = rs485.post_delay_ms {
+                rs485_config.post_delay = std::time::Duration::from_millis(post_delay_ms);
+            }
+            if let Some(echo) = &rs485.echo {
+                rs485_config.echo_mode = BusEchoMode::from_config_str(echo)?;
+            }
+            app_config.rs485 = Some(rs485_config);
+        }
+        if let Some(decoder) = &self.decoder {
+            app_config.decoder = Some(DecoderKind::from_config_str(decoder)?);
+        }
+        if let Some(multi_line_send) = &self.multi_line_send {
+            app_config.multi_line_send_policy = MultiLineSendPolicy::from_config_str(multi_line_send)?;
+        }
+        if let Some(multi_line_delay_ms) = self.multi_line_delay_ms {
+            app_config.multi_line_send_delay = std::time::Duration::from_millis(multi_line_delay_ms);
+        }
+        if let Some(send_escapes) = self.send_escapes {
+            app_config.send_parse_escapes = send_escapes;
+        }
+        if let Some(send_templates) = self.send_templates {
+            app_config.send_expand_templates = send_templates;
+        }
+        for rule in self.highlight_rules.iter() {
+            app_config.highlight_rules.push(HighlightRule::new(&rule.pattern, &rule.style)?);
+        }
+        if let Some(scrollback) = &self.scrollback {
+            if let Some(max_lines_in_memory) = scrollback.max_lines_in_memory {
+                app_config.scrollback_max_lines_in_memory = max_lines_in_memory;
+            }
+            if let Some(spill_to_disk) = scrollback.spill_to_disk {
+                app_config.scrollback_spill_to_disk = spill_to_disk;
+            }
+            if let Some(spool_dir) = &scrollback.spool_dir {
+                app_config.scrollback_spool_dir = Some(spool_dir.clone());
+            }
+        }
+        if let Some(send_file) = &self.send_file {
+            if let Some(mode) = &send_file.mode {
+                app_config.file_send_mode = FileSendMode::from_config_str(mode)?;
+            }
+            if let Some(chunk_size) = send_file.chunk_size {
+                if chunk_size == 0 {
+                    return Err("send_file.chunk_size must be at least 1".into());
+                }
+                app_config.file_send_chunk_size = chunk_size;
+            }
+            if let Some(chunk_delay_ms) = send_file.chunk_delay_ms {
+                app_config.file_send_chunk_delay = std::time::Duration::from_millis(chunk_delay_ms);
+            }
+            if let Some(line_delay_ms) = send_file.line_delay_ms {
+                app_config.file_send_line_delay = std::time::Duration::from_millis(line_delay_ms);
+            }
+            if let Some(wait_for_prompt) = &send_file.wait_for_prompt {
+                app_config.file_send_prompt_regex = Some(regex::Regex::new(wait_for_prompt)
+                    .map_err(|e| format!("Invalid send_file.wait_for_prompt regex: {}", e))?);
+            }
+            if let Some(prompt_timeout_ms) = send_file.prompt_timeout_ms {
+                app_config.file_send_prompt_timeout = std::time::Duration::from_millis(prompt_timeout_ms);
+            }
+        }
+        if let Some(send_history) = &self.send_history {
+            if let Some(persist) = send_history.persist {
+                app_config.send_history_persist = persist;
+            }
+            if let Some(per_device) = send_history.per_device {
+                app_config.send_history_per_device = per_device;
+            }
+            if let Some(max_entries) = send_history.max_entries {
+                app_config.send_history_max_entries = max_entries;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Path to the workspace directory (whether it exists or not).
+pub fn get_workspace_dir() -> PathBuf {
+    PathBuf::from(WORKSPACE_DIR_NAME)
+}
+
+/// Path to the config file in the workspace directory, if it exists.
+pub fn find_workspace_config_file() -> Option<PathBuf> {
+    let path = get_workspace_dir().join(CONFIG_FILE_NAME);
+    match path.is_file() {
+        true => Some(path),
+        false => None,
+    }
+}
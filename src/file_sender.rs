@@ -0,0 +1,407 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::app::decode_bytes_lossy;
+use crate::file_transfer::FileTransferProtocol;
+use crate::send_template::get_echo_string;
+use crate::hex::parse_hex_bytes;
+
+/// Max amount of received text kept while waiting for the prompt (the prompt is at the end of it anyway).
+const MAX_PROMPT_BUFFER_LEN: usize = 4096;
+
+/// How a file is sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileSendMode {
+    /// The file's bytes, as-is.
+    Raw,
+    /// One line at a time, each with the session's EOL (instead of the file's own line endings).
+    Lines,
+    /// The file is hex bytes as text (e.g., "01 03 0A"); the bytes they stand for are sent.
+    HexText,
+}
+
+impl FileSendMode {
+    pub fn from_config_str(s: &str) -> Result<Self, String> {
+        match s {
+            "raw" => Ok(FileSendMode::Raw),
+            "lines" => Ok(FileSendMode::Lines),
+            "hex" => Ok(FileSendMode::HexText),
+            _ => Err(format!("Invalid file send mode '{}' (expected \"raw\", \"lines\", or \"hex\")", s)),
+        }
+    }
+
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            FileSendMode::Raw => "raw",
+            FileSendMode::Lines => "lines",
+            FileSendMode::HexText => "hex text",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            FileSendMode::Raw => FileSendMode::Lines,
+            FileSendMode::Lines => FileSendMode::HexText,
+            FileSendMode::HexText => FileSendMode::Raw,
+        }
+    }
+}
+
+/// How a file is sent from the "send a file" prompt: paced, or with a file transfer protocol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SendFileMethod {
+    Paced(FileSendMode),
+    Protocol(FileTransferProtocol),
+}
+
+impl SendFileMethod {
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            SendFileMethod::Paced(mode) => mode.get_display_name(),
+            SendFileMethod::Protocol(protocol) => protocol.get_display_name(),
+        }
+    }
+
+    /// Cycles through the paced modes, then the protocols.
+    pub fn next(&self) -> Self {
+        match self {
+            SendFileMethod::Paced(FileSendMode::HexText) => SendFileMethod::Protocol(FileTransferProtocol::Xmodem),
+            SendFileMethod::Paced(mode) => SendFileMethod::Paced(mode.next()),
+            SendFileMethod::Protocol(FileTransferProtocol::Zmodem) => SendFileMethod::Paced(FileSendMode::Raw),
+            SendFileMethod::Protocol(protocol) => SendFileMethod::Protocol(protocol.next()),
+        }
+    }
+}
+
+/// Pacing for sending a file.
+#[derive(Clone)]
+pub struct FileSendOptions {
+    pub mode: FileSendMode,
+
+    /// Max number of bytes written at once.
+    pub chunk_size: usize,
+    /// Delay after each chunk (within a line, in line mode).
+    pub chunk_delay: Duration,
+    /// Delay after each line (line mode only).
+    pub line_delay: Duration,
+
+    /// In line mode, wait until the received data matches this before sending the next line.
+    pub prompt_regex: Option<Regex>,
+    pub prompt_timeout: Duration,
+}
+
+/// A chunk of the file that's due to be written.
+pub struct FileSendChunk {
+    pub data: Vec<u8>,
+
+    /// The line that starts with this chunk (line mode), to echo to the transfer log.
+    pub echo_line: Option<String>,
+}
+
+/// A file being sent to the serial port, a chunk at a time.
+pub struct FileSender {
+    pub file_path: PathBuf,
+    pub options: FileSendOptions,
+
+    /// What's sent, in order: the whole file (raw, hex text), or each line with its EOL (line mode).
+    units: Vec<Vec<u8>>,
+    end_of_line_len: usize,
+    unit_idx: usize,
+    unit_offset: usize,
+
+    pub sent_byte_count: usize,
+    pub total_byte_count: usize,
+
+    next_send_time: Instant,
+
+    /// Set while waiting for the prompt, after sending a line: when the wait started, and what's been received since.
+    prompt_wait: Option<(Instant, String)>,
+}
+
+impl FileSender {
+    /// Reads the file, and gets it ready to be sent. `end_of_line` is sent after each line, in line mode.
+    pub fn new(file_path: &Path, options: FileSendOptions, end_of_line: &str) -> Result<Self, String> {
+        let contents = std::fs::read(file_path)
+            .map_err(|e| format!("Error reading '{}': {}", file_path.display(), e))?;
+        Self::from_contents(file_path, contents, options, end_of_line)
+    }
+
+    fn from_contents(file_path: &Path, contents: Vec<u8>, options: FileSendOptions, end_of_line: &str) -> Result<Self, String> {
+        let units = split_into_units(file_path, contents, options.mode, end_of_line)?;
+        let total_byte_count = units.iter().map(|unit| unit.len()).sum();
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            options,
+            units,
+            end_of_line_len: end_of_line.len(),
+            unit_idx: 0,
+            unit_offset: 0,
+            sent_byte_count: 0,
+            total_byte_count,
+            next_send_time: Instant::now(),
+            prompt_wait: None,
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.unit_idx >= self.units.len()
+    }
+
+    pub fn is_waiting_for_prompt(&self) -> bool {
+        self.prompt_wait.is_some()
+    }
+
+    /// Fraction of the file sent so far, from 0 to 1.
+    pub fn get_progress(&self) -> f64 {
+        match self.total_byte_count {
+            0 => 1.0,
+            total_byte_count => self.sent_byte_count as f64 / total_byte_count as f64,
+        }
+    }
+
+    /// Takes the next chunk to write, if it's time for it (and the prompt has been received).
+    /// Fails if the prompt didn't come in time.
+    pub fn take_next_chunk(&mut self) -> Result<Option<FileSendChunk>, String> {
+        if let Some((wait_start_time, _)) = &self.prompt_wait {
+            if wait_start_time.elapsed() > self.options.prompt_timeout {
+                return Err(format!("Timed out waiting for the prompt after line {}", self.unit_idx));
+            }
+            return Ok(None);
+        }
+        if self.is_done() || Instant::now() < self.next_send_time {
+            return Ok(None);
+        }
+
+        let unit = &self.units[self.unit_idx];
+        let chunk_end = (self.unit_offset + self.options.chunk_size.max(1)).min(unit.len());
+        let echo_line = match (self.options.mode, self.unit_offset) {
+            (FileSendMode::Lines, 0) => Some(get_echo_string(&unit[..unit.len() - self.end_of_line_len])),
+            _ => None,
+        };
+        let data = unit[self.unit_offset..chunk_end].to_vec();
+        self.sent_byte_count += data.len();
+        self.unit_offset = chunk_end;
+
+        let is_unit_done = self.unit_offset >= unit.len();
+        self.next_send_time = Instant::now() + match (is_unit_done, self.options.mode) {
+            (true, FileSendMode::Lines) => self.options.line_delay,
+            _ => self.options.chunk_delay,
+        };
+        if is_unit_done {
+            self.unit_idx += 1;
+            self.unit_offset = 0;
+            if self.options.mode == FileSendMode::Lines && self.options.prompt_regex.is_some() && !self.is_done() {
+                self.prompt_wait = Some((Instant::now(), String::new()));
+            }
+        }
+        Ok(Some(FileSendChunk { data, echo_line }))
+    }
+
+    /// Checks received data for the prompt (while waiting for it).
+    pub fn handle_rxd_data(&mut self, data: &[u8]) {
+        let (Some((_, received_text)), Some(prompt_regex)) = (&mut self.prompt_wait, &self.options.prompt_regex) else {
+            return;
+        };
+        received_text.push_str(&decode_bytes_lossy(data));
+        if prompt_regex.is_match(received_text) {
+            self.prompt_wait = None;
+        }
+        else if received_text.len() > MAX_PROMPT_BUFFER_LEN {
+            let mut cut_idx = received_text.len() - MAX_PROMPT_BUFFER_LEN;
+            while !received_text.is_char_boundary(cut_idx) {
+                cut_idx += 1;
+            }
+            received_text.drain(..cut_idx);
+        }
+    }
+}
+
+/// Splits a file's contents into what's sent, in order: the whole file (raw, hex text), or each line with
+/// `end_of_line` instead of its own line ending (line mode). `file_path` is only for the error messages.
+fn split_into_units(file_path: &Path, contents: Vec<u8>, mode: FileSendMode, end_of_line: &str) -> Result<Vec<Vec<u8>>, String> {
+    match mode {
+        FileSendMode::Raw => Ok(vec![contents]),
+        FileSendMode::Lines => {
+            let mut lines: Vec<&[u8]> = contents.split(|byte| *byte == b'\n').collect();
+            // a final line break doesn't start another (empty) line
+            if lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+            Ok(lines.iter()
+                .map(|line| [line.strip_suffix(b"\r").unwrap_or(line), end_of_line.as_bytes()].concat())
+                .collect())
+        }
+        FileSendMode::HexText => {
+            let text = String::from_utf8(contents)
+                .map_err(|_| format!("'{}' isn't a text file of hex bytes", file_path.display()))?;
+            Ok(vec![parse_hex_bytes(&text).map_err(|e| format!("Invalid hex in '{}': {}", file_path.display(), e))?])
+        }
+    }
+}
+
+/// State of the "send a file" prompt (Ctrl+O).
+pub struct SendFilePromptState {
+    pub path: String,
+    pub method: SendFileMethod,
+
+    /// Opened because the other side is waiting for a file (e.g., ran `rz`), rather than with Ctrl+O.
+    pub is_auto_detected: bool,
+}
+
+/// Completes `path` as far as it's unambiguous (like a shell's Tab completion). Directories get a trailing '/'.
+pub fn complete_path(path: &str) -> String {
+    let (dir, file_name_prefix) = match path.rfind('/') {
+        Some(slash_idx) => (&path[..=slash_idx], &path[slash_idx + 1..]),
+        None => ("", path),
+    };
+    let Ok(dir_entries) = std::fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return path.to_string();
+    };
+    let mut matching_names: Vec<String> = dir_entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() {
+                name.push('/');
+            }
+            name
+        })
+        .filter(|name| name.starts_with(file_name_prefix))
+        .collect();
+    matching_names.sort();
+    let (Some(first_name), Some(last_name)) = (matching_names.first(), matching_names.last()) else {
+        return path.to_string();
+    };
+    // the longest common prefix of all the matches is the common prefix of the first and last (sorted)
+    let common_prefix_len: usize = first_name.chars().zip(last_name.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    format!("{}{}", dir, &first_name[..common_prefix_len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_options(mode: FileSendMode, chunk_size: usize, prompt: Option<&str>, prompt_timeout: Duration) -> FileSendOptions {
+        FileSendOptions {
+            mode,
+            chunk_size,
+            chunk_delay: Duration::ZERO,
+            line_delay: Duration::ZERO,
+            prompt_regex: prompt.map(|prompt| Regex::new(prompt).unwrap()),
+            prompt_timeout,
+        }
+    }
+
+    fn split(contents: &[u8], mode: FileSendMode) -> Result<Vec<Vec<u8>>, String> {
+        split_into_units(Path::new("test.txt"), contents.to_vec(), mode, "\r\n")
+    }
+
+    fn take_all_chunks(file_sender: &mut FileSender) -> Vec<(Vec<u8>, Option<String>)> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = file_sender.take_next_chunk().unwrap() {
+            chunks.push((chunk.data, chunk.echo_line));
+        }
+        chunks
+    }
+
+    #[test]
+    fn lines_get_the_session_eol() {
+        assert_eq!(split(b"a\r\nb\n\nc\n", FileSendMode::Lines).unwrap(),
+            vec![b"a\r\n".to_vec(), b"b\r\n".to_vec(), b"\r\n".to_vec(), b"c\r\n".to_vec()]);
+        // without a final line break, the last line is still sent
+        assert_eq!(split(b"a\nb", FileSendMode::Lines).unwrap(), vec![b"a\r\n".to_vec(), b"b\r\n".to_vec()]);
+        // only the final empty line is dropped
+        assert_eq!(split(b"\n", FileSendMode::Lines).unwrap(), vec![b"\r\n".to_vec()]);
+        assert_eq!(split(b"", FileSendMode::Lines).unwrap(), Vec::<Vec<u8>>::new());
+        // a lone '\r' isn't a line ending
+        assert_eq!(split(b"a\rb\r\r\n", FileSendMode::Lines).unwrap(), vec![b"a\rb\r\r\n".to_vec()]);
+    }
+
+    #[test]
+    fn raw_and_hex_text_are_sent_whole() {
+        assert_eq!(split(b"a\r\nb\n", FileSendMode::Raw).unwrap(), vec![b"a\r\nb\n".to_vec()]);
+        assert_eq!(split(b"01 02\r\n0x0A,FF\n", FileSendMode::HexText).unwrap(), vec![vec![0x01, 0x02, 0x0A, 0xFF]]);
+        assert_eq!(split(b"01 0G", FileSendMode::HexText).unwrap_err(), "Invalid hex in 'test.txt': 'G' isn't a hex digit");
+        assert_eq!(split(&[0xFF, 0xFE], FileSendMode::HexText).unwrap_err(), "'test.txt' isn't a text file of hex bytes");
+    }
+
+    #[test]
+    fn chunks_are_at_most_chunk_size() {
+        let options = make_options(FileSendMode::Raw, 3, None, Duration::ZERO);
+        let mut file_sender = FileSender::from_contents(Path::new("test.bin"), b"abcdefg".to_vec(), options, "\r\n").unwrap();
+        let chunks: Vec<Vec<u8>> = take_all_chunks(&mut file_sender).into_iter().map(|(data, _)| data).collect();
+        assert_eq!(chunks, vec![b"abc".to_vec(), b"def".to_vec(), b"g".to_vec()]);
+        assert!(file_sender.is_done());
+        assert_eq!(file_sender.get_progress(), 1.0);
+
+        // a chunk never spans two lines, and only a line's first chunk echoes it
+        let options = make_options(FileSendMode::Lines, 2, None, Duration::ZERO);
+        let mut file_sender = FileSender::from_contents(Path::new("test.txt"), b"abc\nd\n".to_vec(), options, "\n").unwrap();
+        assert_eq!(take_all_chunks(&mut file_sender), vec![
+            (b"ab".to_vec(), Some(String::from("abc"))),
+            (b"c\n".to_vec(), None),
+            (b"d\n".to_vec(), Some(String::from("d"))),
+        ]);
+
+        // a chunk size of 0 still makes progress
+        let options = make_options(FileSendMode::Raw, 0, None, Duration::ZERO);
+        let mut file_sender = FileSender::from_contents(Path::new("test.bin"), b"ab".to_vec(), options, "\r\n").unwrap();
+        assert_eq!(take_all_chunks(&mut file_sender).len(), 2);
+    }
+
+    #[test]
+    fn next_line_waits_for_the_prompt() {
+        let options = make_options(FileSendMode::Lines, 64, Some(r"\$ $"), Duration::from_secs(3600));
+        let mut file_sender = FileSender::from_contents(Path::new("test.txt"), b"ls\npwd\n".to_vec(), options, "\n").unwrap();
+        assert_eq!(take_all_chunks(&mut file_sender).len(), 1);
+        assert!(file_sender.is_waiting_for_prompt());
+
+        // the prompt can be split over several reads
+        file_sender.handle_rxd_data(b"file.txt\nuser@host:~");
+        assert!(file_sender.is_waiting_for_prompt());
+        file_sender.handle_rxd_data(b"$ ");
+        assert!(!file_sender.is_waiting_for_prompt());
+
+        // no wait after the last line
+        assert_eq!(take_all_chunks(&mut file_sender).len(), 1);
+        assert!(!file_sender.is_waiting_for_prompt());
+        assert!(file_sender.is_done());
+    }
+
+    #[test]
+    fn prompt_wait_times_out() {
+        let options = make_options(FileSendMode::Lines, 64, Some(">"), Duration::from_millis(1));
+        let mut file_sender = FileSender::from_contents(Path::new("test.txt"), b"a\nb\n".to_vec(), options, "\n").unwrap();
+        assert!(file_sender.take_next_chunk().unwrap().is_some());
+        std::thread::sleep(Duration::from_millis(5));
+        let result = file_sender.take_next_chunk();
+        assert_eq!(result.err(), Some(String::from("Timed out waiting for the prompt after line 1")));
+    }
+
+    #[test]
+    fn prompt_buffer_is_trimmed_on_a_char_boundary() {
+        let options = make_options(FileSendMode::Lines, 64, Some(">"), Duration::from_secs(3600));
+        let mut file_sender = FileSender::from_contents(Path::new("test.txt"), b"a\nb\n".to_vec(), options, "\n").unwrap();
+        assert!(file_sender.take_next_chunk().unwrap().is_some());
+
+        file_sender.handle_rxd_data("x".repeat(MAX_PROMPT_BUFFER_LEN + 100).as_bytes());
+        let received_len = |file_sender: &FileSender| file_sender.prompt_wait.as_ref().map(|(_, received_text)| received_text.len());
+        assert_eq!(received_len(&file_sender), Some(MAX_PROMPT_BUFFER_LEN));
+
+        // 3-byte characters, so the cut (2 bytes in) falls inside one, and moves on to the next one
+        file_sender.prompt_wait = Some((Instant::now(), String::new()));
+        file_sender.handle_rxd_data("\u{20AC}".repeat(1366).as_bytes());
+        let (_, received_text) = file_sender.prompt_wait.as_ref().unwrap();
+        assert_eq!(received_text.len(), MAX_PROMPT_BUFFER_LEN - 1);
+        assert!(received_text.starts_with('\u{20AC}'));
+
+        // the prompt is still found after trimming
+        file_sender.handle_rxd_data(b">");
+        assert!(!file_sender.is_waiting_for_prompt());
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use crate::xmodem::{XmodemReceiver, XmodemSender};
+use crate::zmodem::{ZmodemReceiver, ZmodemSender};
+
+/// Max number of events kept in a transfer's status (for the transfer popup).
+const MAX_STATUS_EVENTS: usize = 100;
+
+/// A file transfer protocol, run over the bound serial port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileTransferProtocol {
+    /// 128-byte blocks, with a CRC (or a checksum, if the receiver asks for it).
+    Xmodem,
+    /// 1024-byte blocks, with a CRC.
+    Xmodem1k,
+    /// XMODEM-1K, with the file name and size sent first.
+    Ymodem,
+    /// Streaming, with resends from where the errors were (rather than per block). Used by `sz`/`rz`.
+    Zmodem,
+}
+
+impl FileTransferProtocol {
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            FileTransferProtocol::Xmodem => "XMODEM",
+            FileTransferProtocol::Xmodem1k => "XMODEM-1K",
+            FileTransferProtocol::Ymodem => "YMODEM",
+            FileTransferProtocol::Zmodem => "ZMODEM",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            FileTransferProtocol::Xmodem => FileTransferProtocol::Xmodem1k,
+            FileTransferProtocol::Xmodem1k => FileTransferProtocol::Ymodem,
+            FileTransferProtocol::Ymodem => FileTransferProtocol::Zmodem,
+            FileTransferProtocol::Zmodem => FileTransferProtocol::Xmodem,
+        }
+    }
+
+    /// Whether the protocol sends the file name, so that the receiver saves into a directory (rather than to a file).
+    pub fn has_file_names(&self) -> bool {
+        matches!(self, FileTransferProtocol::Ymodem | FileTransferProtocol::Zmodem)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileTransferState {
+    InProgress,
+    Done,
+    Failed(String),
+}
+
+/// What's shown in the transfer popup: progress, retries, and recent events.
+pub struct FileTransferStatus {
+    /// E.g., "YMODEM send 'firmware.bin'".
+    pub title: String,
+    pub state: FileTransferState,
+
+    pub transferred_byte_count: u64,
+    pub total_byte_count: Option<u64>,
+    pub retry_count: u32,
+
+    /// Things that happened (retries, errors, files saved), newest last.
+    pub events: Vec<String>,
+}
+
+impl FileTransferStatus {
+    pub fn new(title: String) -> Self {
+        Self {
+            title,
+            state: FileTransferState::InProgress,
+            transferred_byte_count: 0,
+            total_byte_count: None,
+            retry_count: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn add_event(&mut self, event: String) {
+        self.events.push(event);
+        if self.events.len() > MAX_STATUS_EVENTS {
+            self.events.remove(0);
+        }
+    }
+
+    pub fn fail(&mut self, error_message: String) {
+        self.add_event(format!("Failed: {}", error_message));
+        self.state = FileTransferState::Failed(error_message);
+    }
+
+    /// Fraction transferred so far, from 0 to 1 (0 while the size isn't known).
+    pub fn get_progress(&self) -> f64 {
+        match (&self.state, self.total_byte_count) {
+            (FileTransferState::Done, _) => 1.0,
+            (_, Some(total_byte_count)) if total_byte_count > 0 => {
+                (self.transferred_byte_count as f64 / total_byte_count as f64).min(1.0)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// A file transfer in progress. It doesn't do any I/O itself: received bytes are passed in, and the
+/// bytes to write back are returned, so that it can run over the serial port (or any other transport).
+pub trait FileTransfer {
+    /// Handles bytes received from the other side. Returns the bytes to write back.
+    fn handle_rxd_data(&mut self, data: &[u8]) -> Vec<u8>;
+
+    /// Called regularly (even when nothing was received), for timeouts and retries. Returns the bytes to write.
+    fn tick(&mut self) -> Vec<u8>;
+
+    /// Stops the transfer. Returns the bytes to write to tell the other side.
+    fn cancel(&mut self) -> Vec<u8>;
+
+    fn get_status(&self) -> &FileTransferStatus;
+}
+
+/// Starts sending the file at `path`. Nothing is written until the receiver asks for the first block.
+pub fn start_file_transfer_send(protocol: FileTransferProtocol, path: &Path) -> Result<Box<dyn FileTransfer>, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+    let file_name = path.file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("'{}' isn't a file", path.display()))?;
+    match protocol {
+        FileTransferProtocol::Xmodem | FileTransferProtocol::Xmodem1k | FileTransferProtocol::Ymodem => {
+            Ok(Box::new(XmodemSender::new(protocol, file_name, data)))
+        }
+        FileTransferProtocol::Zmodem => Ok(Box::new(ZmodemSender::new(file_name, data))),
+    }
+}
+
+/// State of the "receive a file" prompt (Alt+O).
+pub struct ReceiveFilePromptState {
+    pub path: String,
+    pub protocol: FileTransferProtocol,
+
+    /// Opened because the other side started a transfer (e.g., ran `sz`), rather than with Alt+O.
+    pub is_auto_detected: bool,
+}
+
+/// Starts receiving into `path`: the file to save (XMODEM), or the directory to save into (YMODEM/ZMODEM, the
+/// current directory when empty).
+pub fn start_file_transfer_receive(protocol: FileTransferProtocol, path: &Path) -> Result<Box<dyn FileTransfer>, String> {
+    let path = match path.as_os_str().is_empty() && protocol.has_file_names() {
+        true => Path::new("."),
+        false => path,
+    };
+    if protocol.has_file_names() && !path.is_dir() {
+        return Err(format!("'{}' isn't a directory", path.display()));
+    }
+    if !protocol.has_file_names() && (path.as_os_str().is_empty() || path.is_dir()) {
+        return Err(String::from("Enter the path of the file to save to"));
+    }
+    match protocol {
+        FileTransferProtocol::Xmodem | FileTransferProtocol::Xmodem1k | FileTransferProtocol::Ymodem => {
+            Ok(Box::new(XmodemReceiver::new(protocol, path.to_path_buf())))
+        }
+        FileTransferProtocol::Zmodem => Ok(Box::new(ZmodemReceiver::new(path.to_path_buf()))),
+    }
+}
+
+/// Saves a received file, without overwriting an existing one (adding ".1", ".2", ... to the name instead).
+/// Returns the path it was saved to.
+pub fn save_received_file(path: &Path, data: &[u8]) -> Result<PathBuf, String> {
+    let mut save_path = path.to_path_buf();
+    let mut suffix_num = 1;
+    while save_path.exists() {
+        save_path = PathBuf::from(format!("{}.{}", path.display(), suffix_num));
+        suffix_num += 1;
+    }
+    std::fs::write(&save_path, data)
+        .map_err(|e| format!("Error saving '{}': {}", save_path.display(), e))?;
+    Ok(save_path)
+}
+
+/// Keeps just the file name from a name sent by the other side (no directories, so it can't write elsewhere).
+pub fn get_safe_file_name(sent_file_name: &str) -> String {
+    let file_name = sent_file_name.rsplit(['/', '\\']).next().unwrap_or("");
+    match file_name {
+        "" | "." | ".." => String::from("received_file"),
+        _ => file_name.to_string(),
+    }
+}
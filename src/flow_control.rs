@@ -0,0 +1,69 @@
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+/// Flow control for the bound port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowControl {
+    None,
+    /// RTS/CTS, done by the OS driver (sending is also held while CTS is off, so that a write doesn't block the UI).
+    Hardware,
+    /// XON/XOFF, done here rather than by the OS driver, so that a pause is visible (and doesn't block the UI in a
+    /// write). Only the other side's XOFF/XON are handled: received data is read fast enough not to need them.
+    Software,
+}
+
+impl FlowControl {
+    pub fn from_config_str(s: &str) -> Result<Self, String> {
+        match s {
+            "none" => Ok(FlowControl::None),
+            "rts_cts" => Ok(FlowControl::Hardware),
+            "xon_xoff" => Ok(FlowControl::Software),
+            _ => Err(format!("Invalid flow control '{}' (expected \"none\", \"rts_cts\", or \"xon_xoff\")", s)),
+        }
+    }
+
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            FlowControl::None => "None",
+            FlowControl::Hardware => "RTS/CTS",
+            FlowControl::Software => "XON/XOFF",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            FlowControl::None => FlowControl::Hardware,
+            FlowControl::Hardware => FlowControl::Software,
+            FlowControl::Software => FlowControl::None,
+        }
+    }
+
+    /// What the OS driver is set to (XON/XOFF is handled here, so the driver doesn't do any).
+    pub fn get_driver_flow_control(&self) -> serialport5::FlowControl {
+        match self {
+            FlowControl::None | FlowControl::Software => serialport5::FlowControl::None,
+            FlowControl::Hardware => serialport5::FlowControl::Hardware,
+        }
+    }
+}
+
+/// Removes XON/XOFF from received data (they're not shown in the transfer log, like the driver would do).
+/// Returns the rest of the data, and whether sending is paused after the last of them (None if there were none).
+pub fn take_xon_xoff(data: &[u8]) -> (Vec<u8>, Option<bool>) {
+    let mut is_paused: Option<bool> = None;
+    let rest: Vec<u8> = data.iter()
+        .filter(|byte| match **byte {
+            XOFF => {
+                is_paused = Some(true);
+                false
+            }
+            XON => {
+                is_paused = Some(false);
+                false
+            }
+            _ => true,
+        })
+        .copied()
+        .collect();
+    (rest, is_paused)
+}
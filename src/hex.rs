@@ -0,0 +1,38 @@
+/// Parses hex bytes like "01 03 0A", "01030A", or "0x01, 0x03, 0x0A".
+pub fn parse_hex_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|word| word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")).unwrap_or(word))
+        .collect();
+    if let Some(bad_char) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' isn't a hex digit", bad_char));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("odd number of hex digits"));
+    }
+    Ok((0..digits.len()).step_by(2)
+        .map(|idx| u8::from_str_radix(&digits[idx..idx + 2], 16).expect("checked that they're hex digits"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_bytes_in_any_common_format() {
+        let expected = vec![0x01, 0x03, 0x0A];
+        assert_eq!(parse_hex_bytes("01 03 0A").unwrap(), expected);
+        assert_eq!(parse_hex_bytes("01030a").unwrap(), expected);
+        assert_eq!(parse_hex_bytes("0x01, 0x03, 0X0A").unwrap(), expected);
+        assert_eq!(parse_hex_bytes("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn bad_hex_bytes() {
+        assert_eq!(parse_hex_bytes("01 0G"), Err(String::from("'G' isn't a hex digit")));
+        assert_eq!(parse_hex_bytes("01 3"), Err(String::from("odd number of hex digits")));
+        assert!(parse_hex_bytes("0x").is_ok());
+        assert!(parse_hex_bytes("01;03").is_err());
+    }
+}
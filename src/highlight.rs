@@ -0,0 +1,134 @@
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use regex::Regex;
+
+/// A user-defined rule that styles every regex match in a transfer log line.
+pub struct HighlightRule {
+    pub pattern: String,
+    pub regex: Regex,
+
+    /// The style, as written by the user (e.g., "black,on_yellow,bold").
+    pub style_spec: String,
+    pub style: Style,
+
+    pub enabled: bool,
+}
+
+impl HighlightRule {
+    pub fn new(pattern: &str, style_spec: &str) -> Result<Self, String> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| format!("Invalid regex '{}': {}", pattern, e))?;
+        let style = parse_style_spec(style_spec)?;
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+            style_spec: style_spec.to_string(),
+            style,
+            enabled: true,
+        })
+    }
+
+    /// Parses a rule written as "<style_spec> <pattern>" (e.g., "red,bold ERROR").
+    pub fn from_rule_string(rule_string: &str) -> Result<Self, String> {
+        match rule_string.trim_start().split_once(' ') {
+            Some((style_spec, pattern)) if !pattern.is_empty() => {
+                HighlightRule::new(pattern, style_spec)
+            }
+            _ => Err(String::from("Rule must be written as '<style> <pattern>' (e.g., 'red,bold ERROR')")),
+        }
+    }
+}
+
+/// Parses a comma-separated style spec, like "red", "black,on_yellow", or "dim".
+/// Each part is either a foreground color, a background color prefixed with "on_",
+/// or a modifier (bold, dim, italic, underlined, reversed).
+pub fn parse_style_spec(style_spec: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    for part in style_spec.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()) {
+        style = match part.to_lowercase().as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underlined" | "underline" => style.add_modifier(Modifier::UNDERLINED),
+            "reversed" | "reverse" => style.add_modifier(Modifier::REVERSED),
+            part_lower => {
+                match part_lower.strip_prefix("on_") {
+                    Some(bg_color) => style.bg(parse_color(bg_color)?),
+                    None => style.fg(parse_color(part)?),
+                }
+            }
+        };
+    }
+    Ok(style)
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    Color::from_str(color).map_err(|_| format!("Unknown color '{}'", color))
+}
+
+/// Splits `text` into runs of (text, style) according to the enabled rules.
+/// When rules overlap, the later rule in the list wins for the overlapping characters.
+/// `overlays` are extra (start, end, style) byte ranges (e.g., search matches), applied on top of the rules.
+pub fn get_highlighted_runs<'a>(text: &'a str, rules: &[HighlightRule], overlays: &[(usize, usize, Style)]) -> Vec<(&'a str, Style)> {
+    // all the styles that can apply to this line (0 = unstyled)
+    let mut styles: Vec<Style> = vec![Style::default()];
+    // style index for every byte of the text
+    let mut byte_style_idx: Vec<usize> = vec![0; text.len()];
+
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        let mut is_rule_matched = false;
+        for m in rule.regex.find_iter(text) {
+            byte_style_idx[m.start()..m.end()].fill(styles.len());
+            is_rule_matched = true;
+        }
+        if is_rule_matched {
+            styles.push(rule.style);
+        }
+    }
+    for (start, end, style) in overlays.iter() {
+        byte_style_idx[*start..*end].fill(styles.len());
+        styles.push(*style);
+    }
+    if styles.len() == 1 {
+        return vec![(text, Style::default())];
+    }
+
+    let mut runs: Vec<(&str, Style)> = Vec::new();
+    let mut run_start = 0;
+    for idx in 1..=text.len() {
+        if idx == text.len() || byte_style_idx[idx] != byte_style_idx[run_start] {
+            runs.push((&text[run_start..idx], styles[byte_style_idx[run_start]]));
+            run_start = idx;
+        }
+    }
+    runs
+}
+
+/// Builds the spans for one line of the transfer log, with the highlight rules (and overlays) applied,
+/// split into rows at the `row_starts` byte offsets (e.g., for line wrapping). Returns the spans for each row.
+pub fn get_highlighted_rows(text: &str, rules: &[HighlightRule], overlays: &[(usize, usize, Style)], row_starts: &[usize]) -> Vec<Vec<Span<'static>>> {
+    let mut rows: Vec<Vec<Span<'static>>> = vec![Vec::new(); row_starts.len().max(1)];
+    let mut row_idx = 0;
+    let mut run_start = 0;
+    for (run_text, style) in get_highlighted_runs(text, rules, overlays) {
+        let run_end = run_start + run_text.len();
+        let mut piece_start = run_start;
+        while piece_start < run_end {
+            // move on to the row that this piece of the run starts in
+            while row_idx + 1 < row_starts.len() && row_starts[row_idx + 1] <= piece_start {
+                row_idx += 1;
+            }
+            let piece_end = match row_starts.get(row_idx + 1) {
+                Some(&next_row_start) => next_row_start.min(run_end),
+                None => run_end,
+            };
+            rows[row_idx].push(Span::styled(text[piece_start..piece_end].to_string(), style));
+            piece_start = piece_end;
+        }
+        run_start = run_end;
+    }
+    rows
+}
@@ -0,0 +1,519 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Max number of entries in the kill ring.
+const KILL_RING_SIZE: usize = 16;
+
+/// Max number of undo steps kept.
+const UNDO_STACK_SIZE: usize = 200;
+
+/// A readline-style editor (for the Send box). The text can have several lines, separated by '\n'.
+///
+/// The cursor is a byte offset into the text, and always sits on a grapheme cluster boundary,
+/// so moving and deleting never splits a character (or an emoji, or a letter with combining accents).
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+
+    undo_stack: Vec<(String, usize)>,
+    redo_stack: Vec<(String, usize)>,
+    last_edit_kind: Option<EditKind>,
+
+    /// Killed (cut) text, newest last. Consecutive kills are merged into one entry, like in readline.
+    kill_ring: Vec<String>,
+
+    /// The text range inserted by the last yank, and which kill ring entry it was (for Alt+Y).
+    last_yank: Option<(usize, usize, usize)>,
+}
+
+/// Kinds of edits, to group consecutive edits of the same kind into one undo step.
+#[derive(Clone, Copy, PartialEq)]
+enum EditKind {
+    InsertChar,
+    DeleteChar,
+    Kill,
+    Other,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            cursor: 0,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_edit_kind: None,
+            kill_ring: Vec::new(),
+            last_yank: None,
+        }
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Byte offset of the cursor in the text.
+    pub fn get_cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replaces the whole text (e.g., from the send history), with the cursor at the end. Can be undone.
+    pub fn set_text(&mut self, text: &str) {
+        self.save_undo_state(EditKind::Other);
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    /// Clears the text, and the undo history (e.g., after sending). The kill ring is kept.
+    pub fn reset(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_edit_kind = None;
+        self.last_yank = None;
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        // a new word starts a new undo step, so that undo doesn't remove everything typed at once
+        let is_word_start = c.is_whitespace() != self.text[..self.cursor].ends_with(char::is_whitespace);
+        if is_word_start {
+            self.last_edit_kind = None;
+        }
+        self.save_undo_state(EditKind::InsertChar);
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.save_undo_state(EditKind::Other);
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    /// Deletes the grapheme before the cursor (Backspace).
+    pub fn delete_grapheme_before(&mut self) {
+        let start = self.get_prev_grapheme_boundary(self.cursor);
+        if start < self.cursor {
+            self.save_undo_state(EditKind::DeleteChar);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    /// Deletes the grapheme after the cursor (Delete).
+    pub fn delete_grapheme_after(&mut self) {
+        let end = self.get_next_grapheme_boundary(self.cursor);
+        if end > self.cursor {
+            self.save_undo_state(EditKind::DeleteChar);
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.get_prev_grapheme_boundary(self.cursor);
+        self.last_edit_kind = None;
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.get_next_grapheme_boundary(self.cursor);
+        self.last_edit_kind = None;
+    }
+
+    /// Moves to the start of the current line.
+    pub fn move_home(&mut self) {
+        self.cursor = self.get_line_start(self.cursor);
+        self.last_edit_kind = None;
+    }
+
+    /// Moves to the end of the current line.
+    pub fn move_end(&mut self) {
+        self.cursor = self.get_line_end(self.cursor);
+        self.last_edit_kind = None;
+    }
+
+    pub fn move_text_start(&mut self) {
+        self.cursor = 0;
+        self.last_edit_kind = None;
+    }
+
+    pub fn move_text_end(&mut self) {
+        self.cursor = self.text.len();
+        self.last_edit_kind = None;
+    }
+
+    /// Moves to the same column on the previous line.
+    /// Returns false (without moving) if the cursor is already on the first line.
+    pub fn move_up(&mut self) -> bool {
+        let line_start = self.get_line_start(self.cursor);
+        if line_start == 0 {
+            return false;
+        }
+        let col = self.text[line_start..self.cursor].graphemes(true).count();
+        let prev_line_start = self.get_line_start(line_start - 1);
+        self.cursor = self.get_idx_at_col(prev_line_start, col);
+        self.last_edit_kind = None;
+        true
+    }
+
+    /// Moves to the same column on the next line.
+    /// Returns false (without moving) if the cursor is already on the last line.
+    pub fn move_down(&mut self) -> bool {
+        let line_end = self.get_line_end(self.cursor);
+        if line_end == self.text.len() {
+            return false;
+        }
+        let col = self.text[self.get_line_start(self.cursor)..self.cursor].graphemes(true).count();
+        self.cursor = self.get_idx_at_col(line_end + 1, col);
+        self.last_edit_kind = None;
+        true
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.get_prev_word_start(self.cursor);
+        self.last_edit_kind = None;
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.cursor = self.get_next_word_end(self.cursor);
+        self.last_edit_kind = None;
+    }
+
+    /// Kills from the start of the line to the cursor (Ctrl+U).
+    /// At the start of a line, kills the line break before it instead.
+    pub fn kill_to_start(&mut self) {
+        let line_start = self.get_line_start(self.cursor);
+        match (self.cursor == line_start, self.cursor) {
+            (true, 0) => {}
+            (true, _) => self.kill_range(self.cursor - 1, self.cursor, true),
+            (false, _) => self.kill_range(line_start, self.cursor, true),
+        }
+    }
+
+    /// Kills from the cursor to the end of the line (Ctrl+K).
+    /// At the end of a line, kills the line break after it instead (joining the lines).
+    pub fn kill_to_end(&mut self) {
+        let line_end = self.get_line_end(self.cursor);
+        match self.cursor == line_end {
+            true => self.kill_range(self.cursor, (self.cursor + 1).min(self.text.len()), false),
+            false => self.kill_range(self.cursor, line_end, false),
+        }
+    }
+
+    /// Kills the word before the cursor (Ctrl+W, Ctrl+Backspace).
+    pub fn kill_word_before(&mut self) {
+        self.kill_range(self.get_prev_word_start(self.cursor), self.cursor, true);
+    }
+
+    /// Kills the word after the cursor (Alt+D).
+    pub fn kill_word_after(&mut self) {
+        self.kill_range(self.cursor, self.get_next_word_end(self.cursor), false);
+    }
+
+    /// Inserts the most recently killed text (Ctrl+Y).
+    pub fn yank(&mut self) {
+        let Some(killed_text) = self.kill_ring.last().cloned() else { return };
+        self.insert_str(&killed_text);
+        self.last_yank = Some((self.cursor - killed_text.len(), self.cursor, self.kill_ring.len() - 1));
+    }
+
+    /// Right after a yank, replaces the yanked text with the previous kill ring entry (Alt+Y).
+    pub fn yank_pop(&mut self) {
+        let Some((start, end, ring_idx)) = self.last_yank else { return };
+        let ring_idx = match ring_idx {
+            0 => self.kill_ring.len() - 1,
+            _ => ring_idx - 1,
+        };
+        let killed_text = self.kill_ring[ring_idx].clone();
+        self.text.replace_range(start..end, &killed_text);
+        self.cursor = start + killed_text.len();
+        self.last_yank = Some((start, self.cursor, ring_idx));
+    }
+
+    pub fn undo(&mut self) {
+        if let Some((text, cursor)) = self.undo_stack.pop() {
+            self.redo_stack.push((std::mem::replace(&mut self.text, text), self.cursor));
+            self.cursor = cursor;
+            self.last_edit_kind = None;
+            self.last_yank = None;
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some((text, cursor)) = self.redo_stack.pop() {
+            self.undo_stack.push((std::mem::replace(&mut self.text, text), self.cursor));
+            self.cursor = cursor;
+            self.last_edit_kind = None;
+            self.last_yank = None;
+        }
+    }
+
+    fn kill_range(&mut self, start: usize, end: usize, is_backwards: bool) {
+        if start >= end {
+            return;
+        }
+        let killed_text: String = self.text[start..end].to_string();
+        match (self.last_edit_kind, self.kill_ring.last_mut()) {
+            // consecutive kills build up a single kill ring entry
+            (Some(EditKind::Kill), Some(last_killed_text)) => {
+                match is_backwards {
+                    true => last_killed_text.insert_str(0, &killed_text),
+                    false => last_killed_text.push_str(&killed_text),
+                }
+            }
+            _ => {
+                self.kill_ring.push(killed_text);
+                if self.kill_ring.len() > KILL_RING_SIZE {
+                    self.kill_ring.remove(0);
+                }
+            }
+        }
+        self.save_undo_state(EditKind::Kill);
+        self.text.replace_range(start..end, "");
+        self.cursor = start;
+    }
+
+    /// Saves the current state for undo, unless this edit continues a run of edits of the same kind.
+    fn save_undo_state(&mut self, edit_kind: EditKind) {
+        self.last_yank = None;
+        if (self.last_edit_kind != Some(edit_kind)) || (edit_kind == EditKind::Other) {
+            self.undo_stack.push((self.text.clone(), self.cursor));
+            if self.undo_stack.len() > UNDO_STACK_SIZE {
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.last_edit_kind = Some(edit_kind);
+    }
+
+    fn get_line_start(&self, idx: usize) -> usize {
+        self.text[..idx].rfind('\n').map(|newline_idx| newline_idx + 1).unwrap_or(0)
+    }
+
+    fn get_line_end(&self, idx: usize) -> usize {
+        self.text[idx..].find('\n').map(|newline_idx| idx + newline_idx).unwrap_or(self.text.len())
+    }
+
+    /// Byte offset of the grapheme `col` graphemes into the line starting at `line_start` (or the end of that line).
+    fn get_idx_at_col(&self, line_start: usize, col: usize) -> usize {
+        let line_end = self.get_line_end(line_start);
+        self.text[line_start..line_end].grapheme_indices(true)
+            .nth(col)
+            .map(|(idx, _)| line_start + idx)
+            .unwrap_or(line_end)
+    }
+
+    fn get_prev_grapheme_boundary(&self, idx: usize) -> usize {
+        self.text[..idx].grapheme_indices(true).next_back().map(|(start, _)| start).unwrap_or(0)
+    }
+
+    fn get_next_grapheme_boundary(&self, idx: usize) -> usize {
+        self.text[idx..].graphemes(true).next().map(|grapheme| idx + grapheme.len()).unwrap_or(idx)
+    }
+
+    /// Start of the word before `idx` (skipping any whitespace right before `idx`).
+    fn get_prev_word_start(&self, idx: usize) -> usize {
+        self.text[..idx].split_word_bound_indices()
+            .rfind(|(_, word)| !word.trim().is_empty())
+            .map(|(start, _)| start)
+            .unwrap_or(0)
+    }
+
+    /// End of the word after `idx` (skipping any whitespace right after `idx`).
+    fn get_next_word_end(&self, idx: usize) -> usize {
+        self.text[idx..].split_word_bound_indices()
+            .find(|(_, word)| !word.trim().is_empty())
+            .map(|(start, word)| idx + start + word.len())
+            .unwrap_or(self.text.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(line_editor: &mut LineEditor, text: &str) {
+        for c in text.chars() {
+            line_editor.insert_char(c);
+        }
+    }
+
+    #[test]
+    fn edits_keep_graphemes_whole() {
+        let mut line_editor = LineEditor::new();
+        // a combining accent, an emoji with a skin tone modifier, and a CJK character
+        type_text(&mut line_editor, "e\u{301}\u{1F44D}\u{1F3FD}\u{4E2D}");
+        assert_eq!(line_editor.get_cursor(), 14);
+
+        line_editor.move_left();
+        assert_eq!(line_editor.get_cursor(), 11);
+        line_editor.delete_grapheme_before();
+        assert_eq!(line_editor.get_text(), "e\u{301}\u{4E2D}");
+        assert_eq!(line_editor.get_cursor(), 3);
+
+        line_editor.move_left();
+        assert_eq!(line_editor.get_cursor(), 0);
+        line_editor.delete_grapheme_after();
+        assert_eq!(line_editor.get_text(), "\u{4E2D}");
+
+        line_editor.move_right();
+        line_editor.insert_str("\u{1F44D}");
+        line_editor.move_left();
+        line_editor.move_left();
+        line_editor.insert_char('a');
+        assert_eq!(line_editor.get_text(), "a\u{4E2D}\u{1F44D}");
+    }
+
+    #[test]
+    fn word_motion_across_scripts() {
+        let mut line_editor = LineEditor::new();
+        line_editor.set_text("abc \u{4F60}\u{597D} e\u{301}tude");
+        assert_eq!(line_editor.get_cursor(), 18);
+
+        // each CJK ideograph is a word of its own, and the combining accent stays in its word
+        let mut word_starts = Vec::new();
+        for _ in 0..5 {
+            line_editor.move_word_left();
+            word_starts.push(line_editor.get_cursor());
+        }
+        assert_eq!(word_starts, vec![11, 7, 4, 0, 0]);
+
+        let mut word_ends = Vec::new();
+        for _ in 0..5 {
+            line_editor.move_word_right();
+            word_ends.push(line_editor.get_cursor());
+        }
+        assert_eq!(word_ends, vec![3, 7, 10, 18, 18]);
+
+        line_editor.kill_word_before();
+        assert_eq!(line_editor.get_text(), "abc \u{4F60}\u{597D} ");
+    }
+
+    #[test]
+    fn cursor_is_clamped_at_the_ends() {
+        let mut line_editor = LineEditor::new();
+        line_editor.move_left();
+        line_editor.move_right();
+        line_editor.move_word_left();
+        line_editor.move_word_right();
+        line_editor.delete_grapheme_before();
+        line_editor.delete_grapheme_after();
+        line_editor.kill_to_start();
+        line_editor.kill_to_end();
+        assert!(!line_editor.move_up());
+        assert!(!line_editor.move_down());
+        assert_eq!((line_editor.get_text(), line_editor.get_cursor()), ("", 0));
+
+        line_editor.set_text("a\u{1F44D}b\nxyz");
+        line_editor.move_right();
+        assert_eq!(line_editor.get_cursor(), 10);
+        assert!(!line_editor.move_down());
+
+        // the column is counted in graphemes, and clamped to the end of a shorter line
+        line_editor.move_left();
+        assert!(line_editor.move_up());
+        assert_eq!(line_editor.get_cursor(), 5);
+        line_editor.move_end();
+        assert!(line_editor.move_down());
+        assert_eq!(line_editor.get_cursor(), 10);
+
+        line_editor.move_text_start();
+        line_editor.move_left();
+        assert_eq!(line_editor.get_cursor(), 0);
+        line_editor.move_text_end();
+        line_editor.delete_grapheme_after();
+        assert_eq!((line_editor.get_text(), line_editor.get_cursor()), ("a\u{1F44D}b\nxyz", 10));
+    }
+
+    #[test]
+    fn undo_groups_words_and_runs_of_deletes() {
+        let mut line_editor = LineEditor::new();
+        type_text(&mut line_editor, "hi there");
+
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "hi ");
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "hi");
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "");
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "");
+
+        line_editor.redo();
+        line_editor.redo();
+        line_editor.redo();
+        assert_eq!((line_editor.get_text(), line_editor.get_cursor()), ("hi there", 8));
+        line_editor.redo();
+        assert_eq!(line_editor.get_text(), "hi there");
+
+        // a run of deletes is one step, but moving the cursor starts a new one
+        line_editor.delete_grapheme_before();
+        line_editor.delete_grapheme_before();
+        line_editor.move_left();
+        line_editor.delete_grapheme_before();
+        assert_eq!(line_editor.get_text(), "hi te");
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "hi the");
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "hi there");
+
+        // a new edit drops the redo steps
+        line_editor.undo();
+        line_editor.insert_char('x');
+        line_editor.redo();
+        assert_eq!(line_editor.get_text(), "hi x");
+
+        // reset forgets the undo steps
+        line_editor.reset();
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "");
+    }
+
+    #[test]
+    fn kills_merge_and_yank_pop_cycles() {
+        let mut line_editor = LineEditor::new();
+        line_editor.set_text("one two three");
+
+        // consecutive kills build up one kill ring entry, in text order
+        line_editor.kill_word_before();
+        line_editor.kill_word_before();
+        assert_eq!(line_editor.get_text(), "one ");
+
+        // moving ends the run, then a backward and a forward kill merge around the cursor
+        line_editor.move_left();
+        line_editor.kill_word_before();
+        line_editor.kill_to_end();
+        assert_eq!(line_editor.get_text(), "");
+
+        line_editor.yank();
+        assert_eq!(line_editor.get_text(), "one ");
+        line_editor.yank_pop();
+        assert_eq!((line_editor.get_text(), line_editor.get_cursor()), ("two three", 9));
+        line_editor.yank_pop();
+        assert_eq!(line_editor.get_text(), "one ");
+
+        // only right after a yank
+        line_editor.insert_char('x');
+        line_editor.yank_pop();
+        assert_eq!(line_editor.get_text(), "one x");
+
+        // a yank is a single undo step
+        line_editor.yank();
+        line_editor.undo();
+        assert_eq!(line_editor.get_text(), "one x");
+    }
+
+    #[test]
+    fn kills_at_line_ends_take_the_line_break() {
+        let mut line_editor = LineEditor::new();
+        line_editor.set_text("ab\ncd");
+        line_editor.move_home();
+        line_editor.kill_to_start();
+        assert_eq!((line_editor.get_text(), line_editor.get_cursor()), ("abcd", 2));
+
+        line_editor.set_text("ab\ncd");
+        line_editor.move_text_start();
+        line_editor.move_end();
+        line_editor.kill_to_end();
+        assert_eq!(line_editor.get_text(), "abcd");
+    }
+}
@@ -0,0 +1,202 @@
+use serialport5::SerialPort;
+
+/// Start of a mark that the OS driver puts in the received data, when line errors are marked (PARMRK).
+/// A real 0xFF byte is received as two of them.
+const MARK_BYTE: u8 = 0xFF;
+
+/// Something wrong on the line, rather than data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineError {
+    /// The line was held low for longer than a character (e.g., a SysRq, or a LIN wakeup).
+    Break,
+    /// A character without its stop bit (e.g., a baud rate mismatch). Has the byte as it was received.
+    Framing(u8),
+    /// A character with the wrong parity bit. Has the byte as it was received.
+    Parity(u8),
+}
+
+impl LineError {
+    /// Text for the transfer log (e.g., "Framing error (received 0x3F)").
+    pub fn get_description(&self) -> String {
+        match self {
+            LineError::Break => String::from("BREAK received"),
+            LineError::Framing(byte) => format!("Framing error (received 0x{:02X})", byte),
+            LineError::Parity(byte) => format!("Parity error (received 0x{:02X})", byte),
+        }
+    }
+}
+
+/// A part of the received data: plain data, or a line error, in the order they were received.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RxdSegment {
+    Data(Vec<u8>),
+    LineError(LineError),
+}
+
+/// Takes the line error marks out of the received data.
+/// The driver marks a break or an error as 0xFF 0x00 <byte> (a break's byte is 0x00), and doubles a real 0xFF.
+/// It doesn't say which error it was: a marked byte is a parity error when parity is on, and a framing error otherwise.
+pub struct LineErrorDecoder {
+    is_parity_enabled: bool,
+
+    /// The start of a mark, when a read ended in the middle of one.
+    pending: Vec<u8>,
+}
+
+impl LineErrorDecoder {
+    pub fn new(is_parity_enabled: bool) -> Self {
+        Self {
+            is_parity_enabled,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn decode(&mut self, data: &[u8]) -> Vec<RxdSegment> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(data);
+
+        let mut segments: Vec<RxdSegment> = Vec::new();
+        let mut plain_data: Vec<u8> = Vec::new();
+        let mut idx = 0;
+        while idx < bytes.len() {
+            if bytes[idx] != MARK_BYTE {
+                plain_data.push(bytes[idx]);
+                idx += 1;
+                continue;
+            }
+            match (bytes.get(idx + 1), bytes.get(idx + 2)) {
+                (Some(&MARK_BYTE), _) => {
+                    plain_data.push(MARK_BYTE);
+                    idx += 2;
+                }
+                (Some(0x00), Some(&byte)) => {
+                    if !plain_data.is_empty() {
+                        segments.push(RxdSegment::Data(std::mem::take(&mut plain_data)));
+                    }
+                    segments.push(RxdSegment::LineError(match (byte, self.is_parity_enabled) {
+                        (0x00, _) => LineError::Break,
+                        (_, true) => LineError::Parity(byte),
+                        (_, false) => LineError::Framing(byte),
+                    }));
+                    idx += 3;
+                }
+                (Some(0x00), None) | (None, _) => {
+                    // the rest of the mark comes with the next read
+                    self.pending = bytes[idx..].to_vec();
+                    break;
+                }
+                (Some(_), _) => {
+                    // not a mark (shouldn't happen while marking is on), so it's just data
+                    plain_data.push(MARK_BYTE);
+                    idx += 1;
+                }
+            }
+        }
+        if !plain_data.is_empty() {
+            segments.push(RxdSegment::Data(plain_data));
+        }
+        segments
+    }
+
+    /// The start of a mark that the last read ended with, as plain data. For when nothing more has been received:
+    /// the driver adds a whole mark at once, so it wasn't a mark (e.g., a lone 0xFF, if the driver didn't turn marking on).
+    pub fn take_unfinished_mark(&mut self) -> Option<Vec<u8>> {
+        match self.pending.is_empty() {
+            true => None,
+            false => Some(std::mem::take(&mut self.pending)),
+        }
+    }
+}
+
+/// Asks the OS driver to mark breaks, framing errors, and parity errors in the received data (rather than
+/// dropping them, or passing them on as 0x00 bytes). The marks are taken out by a `LineErrorDecoder`.
+#[cfg(unix)]
+pub fn enable_line_error_marking(port: &SerialPort) -> Result<(), String> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = port.as_raw_fd();
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    // SAFETY: `fd` is the open port, and `termios` is a valid termios struct to fill in.
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(format!("Error reading the port settings: {}", std::io::Error::last_os_error()));
+    }
+    termios.c_iflag |= libc::PARMRK | libc::INPCK;
+    termios.c_iflag &= !(libc::IGNPAR | libc::IGNBRK | libc::BRKINT | libc::ISTRIP);
+    // SAFETY: as above; the settings are the port's own, with just the input flags changed.
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(format!("Error changing the port settings: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn enable_line_error_marking(_port: &SerialPort) -> Result<(), String> {
+    Err(String::from("Detecting received breaks and line errors isn't supported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubled_mark_byte_is_data() {
+        let mut decoder = LineErrorDecoder::new(false);
+        assert_eq!(decoder.decode(&[0x41, 0xFF, 0xFF, 0x42]), vec![RxdSegment::Data(vec![0x41, 0xFF, 0x42])]);
+        assert_eq!(decoder.take_unfinished_mark(), None);
+    }
+
+    #[test]
+    fn break_and_errors_split_the_data() {
+        let mut decoder = LineErrorDecoder::new(false);
+        assert_eq!(decoder.decode(&[0x41, 0xFF, 0x00, 0x00, 0x42, 0xFF, 0x00, 0x3F]), vec![
+            RxdSegment::Data(vec![0x41]),
+            RxdSegment::LineError(LineError::Break),
+            RxdSegment::Data(vec![0x42]),
+            RxdSegment::LineError(LineError::Framing(0x3F)),
+        ]);
+    }
+
+    #[test]
+    fn marked_byte_is_a_parity_error_when_parity_is_on() {
+        let mut decoder = LineErrorDecoder::new(true);
+        assert_eq!(decoder.decode(&[0xFF, 0x00, 0x3F, 0xFF, 0x00, 0x00]), vec![
+            RxdSegment::LineError(LineError::Parity(0x3F)),
+            RxdSegment::LineError(LineError::Break),
+        ]);
+    }
+
+    #[test]
+    fn mark_split_across_reads() {
+        let marked_data = [0x41, 0xFF, 0x00, 0x3F, 0xFF, 0xFF, 0x42];
+        for split_idx in 1..marked_data.len() {
+            let mut decoder = LineErrorDecoder::new(false);
+            let mut segments = decoder.decode(&marked_data[..split_idx]);
+            segments.extend(decoder.decode(&marked_data[split_idx..]));
+            // data can come in more than one segment, when a read ends in the middle
+            let mut merged_segments: Vec<RxdSegment> = Vec::new();
+            for segment in segments {
+                match (merged_segments.last_mut(), segment) {
+                    (Some(RxdSegment::Data(data)), RxdSegment::Data(more_data)) => data.extend(more_data),
+                    (_, segment) => merged_segments.push(segment),
+                }
+            }
+            assert_eq!(merged_segments, vec![
+                RxdSegment::Data(vec![0x41]),
+                RxdSegment::LineError(LineError::Framing(0x3F)),
+                RxdSegment::Data(vec![0xFF, 0x42]),
+            ], "split at {}", split_idx);
+        }
+    }
+
+    #[test]
+    fn lone_mark_byte_is_data() {
+        let mut decoder = LineErrorDecoder::new(false);
+        assert_eq!(decoder.decode(&[0x41, 0xFF]), vec![RxdSegment::Data(vec![0x41])]);
+        assert_eq!(decoder.decode(&[0x42]), vec![RxdSegment::Data(vec![0xFF, 0x42])]);
+
+        assert_eq!(decoder.decode(&[0x43, 0xFF, 0x00]), vec![RxdSegment::Data(vec![0x43])]);
+        assert_eq!(decoder.take_unfinished_mark(), Some(vec![0xFF, 0x00]));
+        assert_eq!(decoder.take_unfinished_mark(), None);
+        assert_eq!(decoder.decode(&[0x44]), vec![RxdSegment::Data(vec![0x44])]);
+    }
+}
@@ -0,0 +1,206 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::app::App;
+use crate::highlight::get_highlighted_runs;
+use crate::tui::{get_timestamp_gutter_string, get_transfer_log_type_style};
+
+pub enum LogExportFormat {
+    /// Plain text with ANSI escape codes for the colors (view with `less -R` or `cat`).
+    Ansi,
+    Html,
+}
+
+impl LogExportFormat {
+    fn get_file_extension(&self) -> &'static str {
+        match self {
+            LogExportFormat::Ansi => "ansi.txt",
+            LogExportFormat::Html => "html",
+        }
+    }
+}
+
+/// Writes the transfer log to a new file in the current directory, styled the same way as the
+/// live view (log type colors, timestamp gutter, and highlight rules).
+/// Returns the path of the file that was written.
+pub fn export_transfer_log(app: &App, format: LogExportFormat) -> Result<PathBuf, Box<dyn Error>> {
+    let file_path = PathBuf::from(format!(
+        "baud-boss-log-{}.{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        format.get_file_extension()));
+
+    let mut out = String::new();
+    if let LogExportFormat::Html = format {
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str(&format!("<title>{}</title>\n", escape_html(&get_export_title(app))));
+        out.push_str("</head>\n<body style=\"background-color:#000000;color:#e5e5e5\">\n<pre>\n");
+    }
+
+    let transfer_log = &app.main_screen_transfer_log;
+    let mut prev_timestamp = None;
+    for entry_idx in transfer_log.first_available_idx()..transfer_log.len() {
+        let Some(entry) = transfer_log.get(entry_idx) else { continue };
+        let gutter = get_timestamp_gutter_string(
+            app.app_config.timestamp_mode, entry.timestamp, prev_timestamp, app.session_start_time);
+        let line_style = get_transfer_log_type_style(&entry.log_type);
+        let line_text = entry.get_data_as_string();
+
+        let mut runs: Vec<(&str, Style)> = vec![(&gutter, line_style.fg(Color::DarkGray))];
+        runs.extend(
+            get_highlighted_runs(&line_text, &app.app_config.highlight_rules, &[])
+                .into_iter()
+                .map(|(run_text, style)| (run_text, line_style.patch(style)))
+        );
+
+        for (run_text, style) in runs.into_iter().filter(|(run_text, _)| !run_text.is_empty()) {
+            match format {
+                LogExportFormat::Ansi => {
+                    out.push_str(&get_ansi_sgr(style));
+                    out.push_str(run_text);
+                    out.push_str("\x1b[0m");
+                }
+                LogExportFormat::Html => {
+                    out.push_str(&format!("<span style=\"{}\">{}</span>",
+                        get_html_css(style), escape_html(run_text)));
+                }
+            }
+        }
+        out.push('\n');
+        prev_timestamp = Some(entry.timestamp);
+    }
+
+    if let LogExportFormat::Html = format {
+        out.push_str("</pre>\n</body>\n</html>\n");
+    }
+
+    std::fs::write(&file_path, out)?;
+    Ok(file_path)
+}
+
+fn get_export_title(app: &App) -> String {
+    format!("baud-boss log: '{}' @ {} baud, started {}",
+        app.selected_serial_port.clone().unwrap_or_default(),
+        app.app_config.baud_rate.unwrap_or_default(),
+        app.session_start_time.format("%Y-%m-%d %H:%M:%S"))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Builds the ANSI "Select Graphic Rendition" escape sequence for a style.
+fn get_ansi_sgr(style: Style) -> String {
+    let mut codes: Vec<String> = Vec::new();
+    if style.add_modifier.contains(Modifier::BOLD) { codes.push(String::from("1")); }
+    if style.add_modifier.contains(Modifier::DIM) { codes.push(String::from("2")); }
+    if style.add_modifier.contains(Modifier::ITALIC) { codes.push(String::from("3")); }
+    if style.add_modifier.contains(Modifier::UNDERLINED) { codes.push(String::from("4")); }
+    if style.add_modifier.contains(Modifier::REVERSED) { codes.push(String::from("7")); }
+    if let Some(fg) = style.fg {
+        codes.push(get_ansi_color_code(fg, false));
+    }
+    if let Some(bg) = style.bg {
+        codes.push(get_ansi_color_code(bg, true));
+    }
+    format!("\x1b[0;{}m", codes.join(";"))
+}
+
+fn get_ansi_color_code(color: Color, is_background: bool) -> String {
+    let base_offset = match is_background {
+        true => 10,
+        false => 0,
+    };
+    match color {
+        Color::Reset => format!("{}", 39 + base_offset),
+        Color::Black => format!("{}", 30 + base_offset),
+        Color::Red => format!("{}", 31 + base_offset),
+        Color::Green => format!("{}", 32 + base_offset),
+        Color::Yellow => format!("{}", 33 + base_offset),
+        Color::Blue => format!("{}", 34 + base_offset),
+        Color::Magenta => format!("{}", 35 + base_offset),
+        Color::Cyan => format!("{}", 36 + base_offset),
+        Color::Gray => format!("{}", 37 + base_offset),
+        Color::DarkGray => format!("{}", 90 + base_offset),
+        Color::LightRed => format!("{}", 91 + base_offset),
+        Color::LightGreen => format!("{}", 92 + base_offset),
+        Color::LightYellow => format!("{}", 93 + base_offset),
+        Color::LightBlue => format!("{}", 94 + base_offset),
+        Color::LightMagenta => format!("{}", 95 + base_offset),
+        Color::LightCyan => format!("{}", 96 + base_offset),
+        Color::White => format!("{}", 97 + base_offset),
+        Color::Indexed(idx) => format!("{};5;{}", 38 + base_offset, idx),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", 38 + base_offset, r, g, b),
+    }
+}
+
+fn get_html_css(style: Style) -> String {
+    let mut css: Vec<String> = Vec::new();
+    if let Some(fg) = style.fg.and_then(get_html_color) {
+        css.push(format!("color:{}", fg));
+    }
+    if let Some(bg) = style.bg.and_then(get_html_color) {
+        css.push(format!("background-color:{}", bg));
+    }
+    if style.add_modifier.contains(Modifier::BOLD) { css.push(String::from("font-weight:bold")); }
+    if style.add_modifier.contains(Modifier::DIM) { css.push(String::from("opacity:0.6")); }
+    if style.add_modifier.contains(Modifier::ITALIC) { css.push(String::from("font-style:italic")); }
+    if style.add_modifier.contains(Modifier::UNDERLINED) { css.push(String::from("text-decoration:underline")); }
+    css.join(";")
+}
+
+/// Maps a terminal color to a CSS color (using the xterm default palette).
+/// Returns None for `Color::Reset`, which keeps the page's default.
+fn get_html_color(color: Color) -> Option<String> {
+    let hex = match color {
+        Color::Reset => return None,
+        Color::Black => "#000000",
+        Color::Red => "#cd0000",
+        Color::Green => "#00cd00",
+        Color::Yellow => "#cdcd00",
+        Color::Blue => "#0000ee",
+        Color::Magenta => "#cd00cd",
+        Color::Cyan => "#00cdcd",
+        Color::Gray => "#e5e5e5",
+        Color::DarkGray => "#7f7f7f",
+        Color::LightRed => "#ff0000",
+        Color::LightGreen => "#00ff00",
+        Color::LightYellow => "#ffff00",
+        Color::LightBlue => "#5c5cff",
+        Color::LightMagenta => "#ff00ff",
+        Color::LightCyan => "#00ffff",
+        Color::White => "#ffffff",
+        Color::Rgb(r, g, b) => return Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        Color::Indexed(idx) => return Some(get_xterm_256_color_hex(idx)),
+    };
+    Some(hex.to_string())
+}
+
+fn get_xterm_256_color_hex(idx: u8) -> String {
+    match idx {
+        0..=15 => {
+            let basic_colors = [
+                Color::Black, Color::Red, Color::Green, Color::Yellow,
+                Color::Blue, Color::Magenta, Color::Cyan, Color::Gray,
+                Color::DarkGray, Color::LightRed, Color::LightGreen, Color::LightYellow,
+                Color::LightBlue, Color::LightMagenta, Color::LightCyan, Color::White,
+            ];
+            get_html_color(basic_colors[idx as usize]).unwrap_or_default()
+        }
+        16..=231 => {
+            // 6x6x6 color cube
+            let cube_idx = idx - 16;
+            let levels = [0, 95, 135, 175, 215, 255];
+            format!("#{:02x}{:02x}{:02x}",
+                levels[(cube_idx / 36) as usize],
+                levels[((cube_idx / 6) % 6) as usize],
+                levels[(cube_idx % 6) as usize])
+        }
+        232..=255 => {
+            // grayscale ramp
+            let level = 8 + (idx - 232) * 10;
+            format!("#{:02x}{:02x}{:02x}", level, level, level)
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::app::{TransferLogEntry, TransferLogType};
+
+/// State of the live filter ("grep mode") on the Main screen's transfer log.
+/// The filter only changes which entries are shown; nothing is removed from the log.
+pub struct LogFilterState {
+    pub expression: String,
+    pub is_regex: bool,
+
+    /// Show only the lines that do NOT match the expression (like `grep -v`).
+    pub is_inverted: bool,
+
+    pub hide_echo: bool,
+
+    /// Hides the error entries, including line errors (framing and parity errors, and breaks).
+    pub hide_errors: bool,
+
+    /// Whether the filter prompt is open (i.e., keypresses go to the expression).
+    pub is_editing: bool,
+
+    /// Set when the filter changes, so the next render keeps the view at the same place in the log.
+    pub restore_view_pending: bool,
+
+    /// Incremented every time the filter changes, so that anything derived from it knows to rescan the log.
+    pub generation: u64,
+
+    /// Set when the expression is an invalid regex.
+    pub error_message: Option<String>,
+
+    compiled_expression: Option<Regex>,
+}
+
+impl LogFilterState {
+    pub fn new() -> Self {
+        Self {
+            expression: String::new(),
+            is_regex: false,
+            is_inverted: false,
+            hide_echo: false,
+            hide_errors: false,
+            is_editing: false,
+            restore_view_pending: false,
+            generation: 0,
+            error_message: None,
+            compiled_expression: None,
+        }
+    }
+
+    /// Whether the filter hides anything.
+    pub fn is_active(&self) -> bool {
+        self.compiled_expression.is_some() || self.hide_echo || self.hide_errors
+    }
+
+    /// Recompiles the expression. Must be called after changing any of the filter's settings.
+    /// Uses "smart case": the match is case-insensitive unless the expression has an uppercase letter.
+    pub fn compile_expression(&mut self) {
+        self.restore_view_pending = true;
+        self.generation += 1;
+        self.error_message = None;
+        self.compiled_expression = None;
+        if self.expression.is_empty() {
+            return;
+        }
+
+        let pattern = match self.is_regex {
+            true => self.expression.clone(),
+            false => regex::escape(&self.expression),
+        };
+        let is_case_insensitive = !self.expression.chars().any(|c| c.is_uppercase());
+        match RegexBuilder::new(&pattern).case_insensitive(is_case_insensitive).build() {
+            Ok(regex) => {
+                self.compiled_expression = Some(regex);
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Invalid regex: {}", e));
+            }
+        }
+    }
+
+    /// Removes the filter entirely, showing the full log.
+    pub fn clear(&mut self) {
+        self.expression.clear();
+        self.is_inverted = false;
+        self.hide_echo = false;
+        self.hide_errors = false;
+        self.compile_expression();
+    }
+
+    pub fn is_entry_visible(&self, entry: &TransferLogEntry) -> bool {
+        match entry.log_type {
+            TransferLogType::EchoData if self.hide_echo => return false,
+            TransferLogType::ErrorData | TransferLogType::FramingErrorData | TransferLogType::ParityErrorData
+                | TransferLogType::BreakData if self.hide_errors => return false,
+            _ => {}
+        }
+        match &self.compiled_expression {
+            Some(regex) => regex.is_match(&entry.get_data_as_string()) != self.is_inverted,
+            None => true,
+        }
+    }
+
+    /// Short description of the filter, for the transfer log's title (e.g., "Filter: !'spi', no echo").
+    pub fn get_description(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if self.compiled_expression.is_some() {
+            parts.push(format!("{}{}'{}'",
+                if self.is_inverted { "!" } else { "" },
+                if self.is_regex { "regex " } else { "" },
+                self.expression));
+        }
+        if self.hide_echo {
+            parts.push(String::from("no echo"));
+        }
+        if self.hide_errors {
+            parts.push(String::from("no errors"));
+        }
+        format!("Filter: {}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_entry(text: &str, log_type: TransferLogType) -> TransferLogEntry {
+        TransferLogEntry::new(text.as_bytes().to_vec(), log_type, true, chrono::Local::now())
+    }
+
+    fn make_filter(expression: &str, is_regex: bool, is_inverted: bool) -> LogFilterState {
+        let mut filter = LogFilterState::new();
+        filter.expression = expression.to_string();
+        filter.is_regex = is_regex;
+        filter.is_inverted = is_inverted;
+        filter.compile_expression();
+        filter
+    }
+
+    #[test]
+    fn plain_and_regex_expressions() {
+        let filter = make_filter("a.c", false, false);
+        assert!(filter.is_entry_visible(&make_entry("xa.cx", TransferLogType::SerialData)));
+        assert!(!filter.is_entry_visible(&make_entry("abc", TransferLogType::SerialData)));
+
+        let filter = make_filter("a.c", true, false);
+        assert!(filter.is_entry_visible(&make_entry("abc", TransferLogType::SerialData)));
+
+        // smart case
+        let filter = make_filter("temp", false, false);
+        assert!(filter.is_entry_visible(&make_entry("TEMP=21", TransferLogType::SerialData)));
+        let filter = make_filter("Temp", false, false);
+        assert!(!filter.is_entry_visible(&make_entry("TEMP=21", TransferLogType::SerialData)));
+
+        let filter = make_filter("(", true, false);
+        assert!(filter.error_message.is_some());
+        assert!(!filter.is_active());
+        assert!(filter.is_entry_visible(&make_entry("anything", TransferLogType::SerialData)));
+    }
+
+    #[test]
+    fn inverted_expression() {
+        let filter = make_filter("^ok$", true, true);
+        assert!(!filter.is_entry_visible(&make_entry("ok", TransferLogType::SerialData)));
+        assert!(filter.is_entry_visible(&make_entry("not ok", TransferLogType::SerialData)));
+
+        // inverting nothing hides nothing
+        let filter = make_filter("", false, true);
+        assert!(!filter.is_active());
+        assert!(filter.is_entry_visible(&make_entry("ok", TransferLogType::SerialData)));
+    }
+
+    #[test]
+    fn hidden_log_types() {
+        let mut filter = LogFilterState::new();
+        filter.hide_echo = true;
+        filter.compile_expression();
+        assert!(filter.is_active());
+        assert!(!filter.is_entry_visible(&make_entry("sent", TransferLogType::EchoData)));
+        assert!(filter.is_entry_visible(&make_entry("error", TransferLogType::ErrorData)));
+
+        filter.hide_echo = false;
+        filter.hide_errors = true;
+        filter.compile_expression();
+        for log_type in [TransferLogType::ErrorData, TransferLogType::FramingErrorData, TransferLogType::ParityErrorData, TransferLogType::BreakData] {
+            assert!(!filter.is_entry_visible(&make_entry("error", log_type)));
+        }
+        for log_type in [TransferLogType::SerialData, TransferLogType::EchoData, TransferLogType::EventData] {
+            assert!(filter.is_entry_visible(&make_entry("error", log_type)));
+        }
+
+        // the log type is checked before the expression, even when inverted
+        let mut filter = make_filter("x", false, true);
+        filter.hide_errors = true;
+        filter.compile_expression();
+        assert!(!filter.is_entry_visible(&make_entry("y", TransferLogType::BreakData)));
+        assert!(filter.is_entry_visible(&make_entry("y", TransferLogType::SerialData)));
+    }
+
+    #[test]
+    fn description() {
+        let mut filter = make_filter("spi", false, true);
+        filter.hide_echo = true;
+        filter.compile_expression();
+        assert_eq!(filter.get_description(), "Filter: !'spi', no echo");
+
+        let mut filter = make_filter("0x[0-9a-f]+", true, false);
+        filter.hide_errors = true;
+        filter.compile_expression();
+        assert_eq!(filter.get_description(), "Filter: regex '0x[0-9a-f]+', no errors");
+
+        // an invalid expression isn't part of the filter
+        let mut filter = make_filter("(", true, true);
+        filter.hide_echo = true;
+        filter.hide_errors = true;
+        filter.compile_expression();
+        assert_eq!(filter.get_description(), "Filter: no echo, no errors");
+
+        filter.clear();
+        assert!(!filter.is_active());
+        assert_eq!(filter.generation, 3);
+    }
+}
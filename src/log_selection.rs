@@ -0,0 +1,103 @@
+use ratatui::layout::Rect;
+use unicode_width::UnicodeWidthChar;
+
+use crate::transfer_log::TransferLog;
+use crate::transfer_log_line_cache::TransferLogLineCache;
+
+/// A transfer log line, as drawn on one screen row in the last frame.
+pub struct RenderedLogRow {
+    pub entry_idx: usize,
+
+    /// Byte range of the entry's text shown on this row (a wrapped entry takes up several rows).
+    pub text_start: usize,
+    pub text_end: usize,
+}
+
+/// Where the transfer log was drawn in the last frame, so that mouse positions can be mapped back to the log.
+pub struct RenderedLogLayout {
+    /// The inside of the transfer log's block (without the borders).
+    pub area: Rect,
+
+    /// One element per screen row, from the top of `area`.
+    pub rows: Vec<RenderedLogRow>,
+
+    pub gutter_width: usize,
+    pub horiz_scroll_val: usize,
+}
+
+impl RenderedLogLayout {
+    pub fn new() -> Self {
+        Self {
+            area: Rect::default(),
+            rows: Vec::new(),
+            gutter_width: 0,
+            horiz_scroll_val: 0,
+        }
+    }
+
+    /// Column within the entry's text (not counting the gutter) at screen column `x`.
+    fn get_text_col(&self, x: u16) -> usize {
+        ((x.saturating_sub(self.area.x)) as usize + self.horiz_scroll_val).saturating_sub(self.gutter_width)
+    }
+}
+
+/// A mouse selection (click and drag) in the transfer log, in screen coordinates (column, row).
+#[derive(Clone, Copy)]
+pub struct LogSelection {
+    pub anchor: (u16, u16),
+    pub end: (u16, u16),
+}
+
+impl LogSelection {
+    /// The (start, end) of the selection, in reading order. Both ends are included.
+    pub fn get_ordered_ends(&self) -> ((u16, u16), (u16, u16)) {
+        let (anchor, end) = (self.anchor, self.end);
+        match (anchor.1, anchor.0) <= (end.1, end.0) {
+            true => (anchor, end),
+            false => (end, anchor),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.end
+    }
+
+    /// The selected log text. Rows of the same (wrapped) entry are joined; separate entries are on separate lines.
+    pub fn get_selected_text(&self, layout: &RenderedLogLayout, transfer_log: &TransferLog, line_cache: &mut TransferLogLineCache) -> String {
+        let ((start_x, start_y), (end_x, end_y)) = self.get_ordered_ends();
+        let mut selected_text = String::new();
+        let mut prev_entry_idx: Option<usize> = None;
+
+        for y in start_y.max(layout.area.y)..=end_y {
+            let Some(row) = layout.rows.get((y - layout.area.y) as usize) else { break };
+            let Some(entry) = transfer_log.get(row.entry_idx) else { continue };
+            let row_text = &line_cache.get_text(row.entry_idx, &entry)[row.text_start..row.text_end];
+
+            let first_col = match y == start_y {
+                true => layout.get_text_col(start_x),
+                false => 0,
+            };
+            let last_col = match y == end_y {
+                true => Some(layout.get_text_col(end_x)),
+                false => None,
+            };
+
+            if prev_entry_idx.is_some_and(|prev_entry_idx| prev_entry_idx != row.entry_idx) {
+                selected_text.push('\n');
+            }
+            prev_entry_idx = Some(row.entry_idx);
+
+            let mut col: usize = 0;
+            for c in row_text.chars() {
+                if last_col.is_some_and(|last_col| col > last_col) {
+                    break;
+                }
+                if col >= first_col {
+                    selected_text.push(c);
+                }
+                col += c.width().unwrap_or(0);
+            }
+        }
+        selected_text
+    }
+}
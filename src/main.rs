@@ -2,30 +2,189 @@ mod app;
 mod tui;
 mod tui_list_state_tracker;
 mod serial;
+mod highlight;
+mod config_file;
+mod log_export;
+mod search;
+mod log_filter;
+mod transfer_log;
+mod transfer_log_view;
+mod transfer_log_line_cache;
+mod log_selection;
+mod clipboard;
+mod line_editor;
+mod send_history;
+mod snippets;
+mod hex;
+mod tx_queue;
+mod send_template;
+mod tx_scheduler;
+mod file_sender;
+mod file_transfer;
+mod xmodem;
+mod zmodem;
+mod modem_lines;
+mod flow_control;
+mod line_errors;
+mod reset_recipes;
+mod rs485;
+mod protocol_decoder;
+mod nmea;
+mod modbus;
 
-use app::{App, AppConfig, CurrentScreen, EchoMode, MainScreenActiveRegion, ScrollPosition};
+use app::{App, COMMON_BAUD_RATES, CurrentScreen, EchoMode, MainScreenActiveRegion, MultiLineSendPolicy, ScrollPosition};
 use tui::ui;
 use serial::bind_serial_port;
+use config_file::{find_workspace_config_file, ConfigFile};
+use highlight::HighlightRule;
+use log_export::{export_transfer_log, LogExportFormat};
+use transfer_log::TransferLog;
+use log_selection::LogSelection;
+use clipboard::{copy_to_clipboard, CLIPBOARD_MAX_BYTES};
+use send_history::{get_send_history_file_path, SendHistory, SendHistorySearchState};
+use snippets::{get_workspace_snippets_file, load_snippets};
+use tx_queue::TxQueueItem;
+use send_template::{expand_message_lines, split_message_lines, SendTextOptions};
+use tx_scheduler::parse_schedule_spec;
+use file_sender::{complete_path, FileSendMode, FileSendOptions, FileSender, SendFileMethod, SendFilePromptState};
+use file_transfer::{start_file_transfer_receive, start_file_transfer_send, FileTransferProtocol, FileTransferState, ReceiveFilePromptState};
+use zmodem::ZmodemRequest;
+use modem_lines::{ModemInputLines, ModemLines, ModemOutputLine};
+use flow_control::{take_xon_xoff, FlowControl};
+use line_errors::{enable_line_error_marking, LineErrorDecoder, RxdSegment};
+use reset_recipes::ResetRunner;
+use rs485::{BusEchoFilter, BusEchoMode, Rs485Config};
+use protocol_decoder::DecoderKind;
+use modbus::{get_frame_silence, ModbusPromptState, ModbusRequest, MODBUS_REPLY_TIMEOUT};
+
+use clap::Parser;
 
 use crossterm::event::{self, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use crossterm::execute;
 use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
 use crossterm::event::{DisableMouseCapture, KeyboardEnhancementFlags};
+use crossterm::event::{DisableBracketedPaste, EnableBracketedPaste};
 use crossterm::terminal::{disable_raw_mode, LeaveAlternateScreen};
 
 use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Margin, Rect};
+use ratatui::widgets::ListState;
 use ratatui::Terminal;
 
 use std::io;
 use std::io::{Read, Write};
 
 use std::error::Error;
+use std::path::PathBuf;
+
+/// How far one notch of the mouse wheel scrolls the transfer log.
+const MOUSE_WHEEL_SCROLL_LINES: usize = 3;
+const MOUSE_WHEEL_SCROLL_COLUMNS: usize = 6;
+
+/// How long sending a file can keep the event loop busy, when there's no delay between chunks.
+const FILE_SEND_MAX_TIME_PER_LOOP: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// How often the screen is redrawn during a file transfer, when the event loop runs every millisecond (about 30 Hz).
+const FILE_TRANSFER_REDRAW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(33);
+
+/// A feature-rich UART serial terminal
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Path to a config file [default: .baud-boss/config.toml, if it exists]
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Don't capture the mouse, so that the terminal's own text selection works
+    #[arg(long)]
+    no_mouse: bool,
+
+    /// Send this file once the serial port is open
+    #[arg(long, value_name = "PATH")]
+    send_file: Option<PathBuf>,
+
+    /// How to send the file: raw, lines, or hex [default: from the config file, or raw]
+    #[arg(long, value_name = "MODE")]
+    send_file_mode: Option<String>,
+
+    /// Flow control: none, rts_cts, or xon_xoff [default: from the config file, or none]
+    #[arg(long, value_name = "MODE")]
+    flow_control: Option<String>,
+
+    /// How long a BREAK (Alt+3) holds the line low [default: from the config file, or 250]
+    #[arg(long, value_name = "MS")]
+    break_duration_ms: Option<u64>,
+
+    /// Run this reset recipe (e.g., esp32_hard_reset) each time the port is opened [default: from the config file]
+    #[arg(long, value_name = "NAME")]
+    reset: Option<String>,
+
+    /// RS-485 (half-duplex) mode, with RTS enabling the driver [default: from the config file's [rs485] section]
+    #[arg(long)]
+    rs485: bool,
+
+    /// In RS-485 mode, what to do with our own bytes coming back from the bus: none, suppress, or mark
+    /// [default: from the config file, or none]
+    #[arg(long, value_name = "MODE")]
+    rs485_echo: Option<String>,
+
+    /// Protocol decoder for the transfer log: nmea [default: from the config file, or none]
+    #[arg(long, value_name = "NAME")]
+    decoder: Option<String>,
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    // create app, and load the config before touching the terminal (so errors are readable)
+    let mut app = App::new();
+    if let Some(config_file_path) = cli.config.or_else(find_workspace_config_file) {
+        let config_file = ConfigFile::load(&config_file_path)?;
+        config_file.apply_to(&mut app.app_config)?;
+    }
+    if cli.no_mouse {
+        app.app_config.mouse_capture = false;
+    }
+    if let Some(send_file_mode) = &cli.send_file_mode {
+        app.app_config.file_send_mode = FileSendMode::from_config_str(send_file_mode)?;
+    }
+    if let Some(flow_control) = &cli.flow_control {
+        app.app_config.flow_control = FlowControl::from_config_str(flow_control)?;
+    }
+    if let Some(break_duration_ms) = cli.break_duration_ms {
+        app.app_config.break_duration = std::time::Duration::from_millis(break_duration_ms);
+    }
+    if let Some(decoder) = &cli.decoder {
+        app.app_config.decoder = Some(DecoderKind::from_config_str(decoder)?);
+    }
+    if cli.rs485 && app.app_config.rs485.is_none() {
+        app.app_config.rs485 = Some(Rs485Config::new());
+    }
+    if let Some(rs485_echo) = &cli.rs485_echo {
+        let echo_mode = BusEchoMode::from_config_str(rs485_echo)?;
+        if let Some(rs485_config) = &mut app.app_config.rs485 {
+            rs485_config.echo_mode = echo_mode;
+        }
+    }
+    if let Some(reset) = cli.reset {
+        app.app_config.reset_on_connect = Some(reset);
+    }
+    if let Some(reset_on_connect) = &app.app_config.reset_on_connect {
+        if !app.app_config.reset_recipes.iter().any(|recipe| &recipe.name == reset_on_connect) {
+            return Err(format!("Unknown reset recipe '{}'", reset_on_connect).into());
+        }
+    }
+    app.send_file_on_start = cli.send_file;
+    app.main_screen_transfer_log = TransferLog::from_config(&app.app_config)?;
+
     // setup terminal
     enable_raw_mode()?;
     let mut stderr = io::stderr(); // This is a special case. Normally using stdout is fine
-    execute!(stderr, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stderr, EnterAlternateScreen, EnableBracketedPaste)?;
+    if app.app_config.mouse_capture {
+        execute!(stderr, EnableMouseCapture)?;
+    }
     let backend = CrosstermBackend::new(stderr);
     let mut terminal = Terminal::new(backend)?;
     
@@ -37,8 +196,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
     );
 
-    // create app and run it
-    let mut app = App::new();
+    // run the app
     let res = run_app(&mut terminal, &mut app);
 
     // restore terminal
@@ -46,7 +204,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
@@ -72,19 +231,43 @@ fn run_app<B: Backend>(
     // for i in 0_i32..=120_i32 {
     //     let fake_data = format!("Fake Incoming Data, line {}/120: {}\n",
     //         i, ".".repeat((100_i32-i).abs() as usize));
-    //     app.add_rxd_serial_data_to_transfer_log(fake_data.into_bytes());
+    //     app.add_rxd_serial_data_to_transfer_log(fake_data.into_bytes(), chrono::Local::now());
     // }
 
+    let mut last_draw_time: Option<std::time::Instant> = None;
     loop {
-        terminal.draw(|f| ui(f, app))?;
+        let is_redraw_due = match (is_file_transfer_running(app), last_draw_time) {
+            (true, Some(last_draw_time)) => last_draw_time.elapsed() >= FILE_TRANSFER_REDRAW_INTERVAL,
+            _ => true,
+        };
+        if is_redraw_due {
+            terminal.draw(|f| ui(f, app))?;
+            last_draw_time = Some(std::time::Instant::now());
+            app.show_spool_error_message();
+        }
 
-        if let Ok(true) = event::poll(std::time::Duration::from_millis(50)) {
-            if let Event::Key(key) = event::read()? {
-                if key.kind != event::KeyEventKind::Release {
-                    // Skip events that are not KeyEventKind::Press
-                    
+        // wake up in time for the next scheduled send (and don't hold up a file transfer's replies)
+        let mut poll_timeout = match (is_file_transfer_running(app), app.tx_scheduler.get_time_until_next_send()) {
+            (true, _) => std::time::Duration::from_millis(1),
+            (false, Some(time_until_next_send)) => time_until_next_send.min(std::time::Duration::from_millis(50)),
+            (false, None) => std::time::Duration::from_millis(50),
+        };
+        // and for the next step of a reset recipe (the timing matters to the board)
+        if let Some(time_until_next_step) = app.reset_runner.as_ref().and_then(|reset_runner| reset_runner.get_time_until_next_step(std::time::Instant::now())) {
+            poll_timeout = poll_timeout.min(time_until_next_step);
+        }
+        // and often enough to see the silence between Modbus RTU frames
+        if app.app_config.decoder == Some(DecoderKind::ModbusRtu) {
+            let silence = get_rtu_frame_silence(app);
+            poll_timeout = poll_timeout.min(app.rtu_framer.get_time_until_frame_end(silence, std::time::Instant::now()).unwrap_or(silence));
+        }
+        if let Ok(true) = event::poll(poll_timeout) {
+            match event::read()? {
+                // Skip events that are not KeyEventKind::Press
+                Event::Key(key) if key.kind != event::KeyEventKind::Release => {
                     // Handle KeyEventKind::Press events:
-                    if app_handle_keypresses(app, key) {
+                    let is_quit_requested = app_handle_keypresses(app, key);
+                    if is_quit_requested {
                         break;
                     }
 
@@ -95,6 +278,13 @@ fn run_app<B: Backend>(
                     //     key.modifiers, key.code));
 
                 }
+                Event::Mouse(mouse) => {
+                    app_handle_mouse_event(app, mouse);
+                }
+                Event::Paste(pasted_text) => {
+                    app_handle_paste(app, &pasted_text);
+                }
+                _ => {}
             }
         }
 
@@ -106,6 +296,9 @@ fn run_app<B: Backend>(
                         if bytes_to_read > 0 {
                             app_handle_incoming_serial_data(app);
                         }
+                        else if let Some(rxd_data) = app.line_error_decoder.as_mut().and_then(|decoder| decoder.take_unfinished_mark()) {
+                            app_handle_rxd_data(app, rxd_data, chrono::Local::now());
+                        }
                     }
                     Err(e) => {
                         app.add_error_to_transfer_log(format!("Error checking bytes to read: {}", e));
@@ -114,6 +307,41 @@ fn run_app<B: Backend>(
             }
             None => { }
         }
+
+        if app.bound_serial_port.is_some() && app.modem_lines.take_poll_due() {
+            app_poll_modem_lines(app);
+        }
+        if app.break_end_time.is_some_and(|break_end_time| std::time::Instant::now() >= break_end_time) {
+            app_end_break(app);
+        }
+        if app.reset_runner.is_some() {
+            app_run_reset(app);
+        }
+        // the line has only been silent if nothing is waiting to be read
+        let is_rxd_waiting = app.bound_serial_port.as_ref().is_some_and(|port| port.bytes_to_read().is_ok_and(|count| count > 0));
+        if !is_rxd_waiting {
+            if let Some((frame, frame_timestamp)) = app.rtu_framer.take_finished_frame(get_rtu_frame_silence(app), std::time::Instant::now()) {
+                app_handle_rtu_frame(app, frame, frame_timestamp);
+            }
+        }
+        app_check_modbus_reply_timeout(app);
+
+        // a file transfer has the port to itself while it runs
+        if is_file_transfer_running(app) {
+            app_run_file_transfer(app, None);
+            continue;
+        }
+
+        // send whatever's queued up (e.g., the next line of a multi-line block that's being sent line by line)
+        if !app.tx_scheduler.jobs.is_empty() {
+            app_run_scheduled_sends(app);
+        }
+        if !app.tx_queue.is_empty() {
+            let _ = app_send_tx_queue(app); // a write error is already in the transfer log
+        }
+        if app.file_sender.is_some() {
+            app_run_file_send(app);
+        }
     }
     Ok(())
 }
@@ -122,26 +350,26 @@ fn app_handle_incoming_serial_data(app: &mut App) -> () {
     // TODO: handle expect better maybe
     let port = app.bound_serial_port.as_mut().expect("Serial port unbound itself between seeing if bytes are available, and reading them.");
 
-    let mut serial_buf: Vec<u8> = vec![0; 32];
+    let mut serial_buf: Vec<u8> = vec![0; 1024];
     let bytes_read_count = port.read(serial_buf.as_mut_slice());
+    let read_timestamp = chrono::Local::now(); // take the timestamp as soon as the bytes are read
 
     match bytes_read_count {
         Ok(bytes_read_count) => {
             if bytes_read_count > 0 {
-                let data = &serial_buf[..bytes_read_count];
-                match std::str::from_utf8(data) {
-                    Ok(data_str) => {
-                        app.add_rxd_serial_data_to_transfer_log(data_str.to_string().into_bytes());
-                    }
-                    Err(_) => {
-                        // FIXME: still show the data somehow (hex or similar)
-                        app.add_error_to_transfer_log(
-                            format!("Error converting incoming data to UTF-8"));
+                let rxd_data = serial_buf[..bytes_read_count].to_vec();
+                let rxd_segments = match &mut app.line_error_decoder {
+                    Some(line_error_decoder) => line_error_decoder.decode(&rxd_data),
+                    None => vec![RxdSegment::Data(rxd_data)],
+                };
+                for rxd_segment in rxd_segments {
+                    match rxd_segment {
+                        RxdSegment::Data(data) => app_handle_rxd_data(app, data, read_timestamp),
+                        // the transfer log is paused while a file transfer runs (which sees the error as bad data)
+                        RxdSegment::LineError(_) if is_file_transfer_running(app) => {}
+                        RxdSegment::LineError(line_error) => app.add_line_error_to_transfer_log(line_error, read_timestamp),
                     }
                 }
-                // TODO: delete very old data from this buffer to prevent memory leak
-                // TODO: push the data with color formatting maybe (for different types of data [e.g., EOL, end-of-message, non-printable-as-hex, etc.])
-                // TODO: write to files/logs, etc.
             }
         }
         Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
@@ -154,6 +382,87 @@ fn app_handle_incoming_serial_data(app: &mut App) -> () {
     }
 }
 
+fn app_handle_rxd_data(app: &mut App, mut rxd_data: Vec<u8>, read_timestamp: chrono::DateTime<chrono::Local>) {
+    // on a shared RS-485 bus, what we write can come back to us
+    let bus_echo_mode = app.app_config.rs485.as_ref().map_or(BusEchoMode::None, |rs485_config| rs485_config.echo_mode);
+    if bus_echo_mode != BusEchoMode::None {
+        let (bus_echo, rest) = app.bus_echo_filter.take_echo(&rxd_data);
+        if bus_echo_mode == BusEchoMode::Mark && !bus_echo.is_empty() && !is_file_transfer_running(app) {
+            app_add_bus_echo_to_transfer_log(app, &bus_echo);
+        }
+        if rest.is_empty() {
+            return;
+        }
+        rxd_data = rest;
+    }
+
+    if is_file_transfer_running(app) {
+        // the transfer log is paused while a file transfer runs
+        app_run_file_transfer(app, Some(&rxd_data));
+        return;
+    }
+    if app.app_config.flow_control == FlowControl::Software {
+        let (rest, is_paused) = take_xon_xoff(&rxd_data);
+        if let Some(is_paused) = is_paused {
+            app.is_tx_paused_by_xoff = is_paused;
+        }
+        rxd_data = rest;
+    }
+    if let Some(file_sender) = &mut app.file_sender {
+        file_sender.handle_rxd_data(&rxd_data);
+    }
+    if let Some(zmodem_request) = app.zmodem_detector.handle_rxd_data(&rxd_data) {
+        app_offer_zmodem_transfer(app, zmodem_request);
+    }
+    match app.app_config.decoder {
+        Some(DecoderKind::ModbusRtu) => {
+            let silence = get_rtu_frame_silence(app);
+            if let Some((frame, frame_timestamp)) = app.rtu_framer.handle_rxd_data(&rxd_data, read_timestamp, silence, std::time::Instant::now()) {
+                app_handle_rtu_frame(app, frame, frame_timestamp);
+            }
+        }
+        _ => {
+            match std::str::from_utf8(&rxd_data) {
+                Ok(_) => {
+                    app.add_rxd_serial_data_to_transfer_log(rxd_data, read_timestamp);
+                }
+                Err(_) => {
+                    // FIXME: still show the data somehow (hex or similar)
+                    app.add_error_to_transfer_log(
+                        String::from("Error converting incoming data to UTF-8"));
+                }
+            }
+        }
+    }
+    // TODO: push the data with color formatting maybe (for different types of data [e.g., EOL, end-of-message, non-printable-as-hex, etc.])
+    // TODO: write to files/logs, etc.
+}
+
+fn get_rtu_frame_silence(app: &App) -> std::time::Duration {
+    get_frame_silence(app.app_config.baud_rate.unwrap_or(9600))
+}
+
+/// Logs a received Modbus RTU frame, and shows it in the master console if it's the reply it's waiting for.
+fn app_handle_rtu_frame(app: &mut App, frame: Vec<u8>, frame_timestamp: chrono::DateTime<chrono::Local>) {
+    if let Some(modbus_prompt) = &mut app.modbus_prompt {
+        if let Some((request, _)) = &modbus_prompt.pending_request {
+            if let Some(reply) = request.parse_reply(&frame) {
+                modbus_prompt.last_reply = Some(reply);
+                modbus_prompt.pending_request = None;
+            }
+        }
+    }
+    app.add_rxd_frame_to_transfer_log(frame, frame_timestamp);
+}
+
+fn app_check_modbus_reply_timeout(app: &mut App) {
+    let Some(modbus_prompt) = &mut app.modbus_prompt else { return };
+    if modbus_prompt.pending_request.as_ref().is_some_and(|(_, send_time)| send_time.elapsed() >= MODBUS_REPLY_TIMEOUT) {
+        modbus_prompt.pending_request = None;
+        modbus_prompt.last_reply = Some(Err(String::from("No reply (timed out)")));
+    }
+}
+
 /// Handle keypresses for the app (next-screen logic, quit logic, input logic, etc.)
 /// Returns true if the app should exit
 fn app_handle_keypresses(app: &mut App, key: KeyEvent) -> bool {
@@ -180,7 +489,8 @@ fn app_handle_keypresses(app: &mut App, key: KeyEvent) -> bool {
         },
 
         CurrentScreen::Config1 => {
-            if is_keypress_quit_event(key, true) {
+            // don't quit on 'q' while typing a new rule
+            if is_keypress_quit_event(key, app.config1_rule_input.is_none()) {
                 return true; // exit program
             }
             app_handle_keypresses_for_config1_screen(app, key);
@@ -207,28 +517,32 @@ fn app_handle_keypresses_for_pick_serial_port_screen(app: &mut App, key: KeyEven
             app.pick_serial_port_list_state.next();
         }
         KeyCode::Enter => {
-            // read and store the selected serial port
-            let selected_port = app.pick_serial_port_list_state.get_selected();
-            match selected_port {
-                Some(port) => {
-                    app.selected_serial_port = Some(port);
-                    match app.app_config.baud_rate {
-                        None | Some(0) => {
-                            app.current_screen = CurrentScreen::PickBaudRate;
-                        }
-                        _ => {
-                            // if the baud rate is already set, just go to the main screen
-                            app_transition_to_main(app);
-                        }
-                    }
+            app_confirm_selected_serial_port(app);
+        }
+        _ => {}
+    }
+}
+
+fn app_confirm_selected_serial_port(app: &mut App) {
+    // read and store the selected serial port
+    let selected_port = app.pick_serial_port_list_state.get_selected();
+    match selected_port {
+        Some(port) => {
+            app.selected_serial_port = Some(port);
+            match app.app_config.baud_rate {
+                None | Some(0) => {
+                    app.current_screen = CurrentScreen::PickBaudRate;
                 }
-                None => {
-                    app.selected_serial_port = None;
-                    // don't change screens
+                _ => {
+                    // if the baud rate is already set, just go to the main screen
+                    app_transition_to_main(app);
                 }
             }
         }
-        _ => {}
+        None => {
+            app.selected_serial_port = None;
+            // don't change screens
+        }
     }
 }
 
@@ -259,40 +573,176 @@ fn app_handle_keypresses_for_pick_baud_rate_screen(app: &mut App, key: KeyEvent)
         (KeyModifiers::NONE, KeyCode::Backspace) => {
             app.pick_baud_rate_input_field.pop();
         }
+        (KeyModifiers::NONE, KeyCode::Up) => {
+            let selected_idx = match app.pick_baud_rate_list_state.selected() {
+                Some(idx) => idx.saturating_sub(1),
+                None => COMMON_BAUD_RATES.len() - 1,
+            };
+            app_select_common_baud_rate(app, selected_idx);
+        }
+        (KeyModifiers::NONE, KeyCode::Down) => {
+            let selected_idx = match app.pick_baud_rate_list_state.selected() {
+                Some(idx) => (idx + 1).min(COMMON_BAUD_RATES.len() - 1),
+                None => 0,
+            };
+            app_select_common_baud_rate(app, selected_idx);
+        }
         (KeyModifiers::NONE, KeyCode::Enter) => {
-            // store the baud rate
-            let baud_rate = app.pick_baud_rate_input_field.parse::<u32>();
-            match baud_rate {
-                Ok(rate) => {
-                    app.app_config.baud_rate = Some(rate);
-                    app_transition_to_main(app);
-                }
-                Err(_) => {
-                    // this shouldn't really happen, just clear the field and let them try again though
-                    app.pick_baud_rate_input_field.clear();
-                }
-            }
+            app_confirm_baud_rate_input(app);
         }
         _ => {}
     }
 }
 
+/// Selects a baud rate in the list of common baud rates, and puts it in the input field.
+fn app_select_common_baud_rate(app: &mut App, idx: usize) {
+    app.pick_baud_rate_list_state.select(Some(idx));
+    app.pick_baud_rate_input_field = COMMON_BAUD_RATES[idx].to_string();
+}
+
+fn app_confirm_baud_rate_input(app: &mut App) {
+    // store the baud rate
+    let baud_rate = app.pick_baud_rate_input_field.parse::<u32>();
+    match baud_rate {
+        Ok(rate) => {
+            app.app_config.baud_rate = Some(rate);
+            app_transition_to_main(app);
+        }
+        Err(_) => {
+            // this shouldn't really happen, just clear the field and let them try again though
+            app.pick_baud_rate_input_field.clear();
+        }
+    }
+}
+
 fn app_handle_keypresses_for_main_screen(app: &mut App, key: KeyEvent) -> () {
+    // the filter and search prompts take all keypresses while they're open
+    if app.main_screen_filter.is_editing {
+        app_handle_keypresses_for_main_screen_filter_prompt(app, key);
+        return;
+    }
+    if app.main_screen_search.is_editing {
+        app_handle_keypresses_for_main_screen_search_prompt(app, key);
+        return;
+    }
+    if app.main_input_send_history_search.is_some() {
+        app_handle_keypresses_for_send_history_search_prompt(app, key);
+        return;
+    }
+    if app.snippet_picker_list_state.is_some() {
+        app_handle_keypresses_for_snippet_picker(app, key);
+        return;
+    }
+    if app.main_input_schedule_prompt.is_some() {
+        app_handle_keypresses_for_schedule_prompt(app, key);
+        return;
+    }
+    if app.send_file_prompt.is_some() {
+        app_handle_keypresses_for_send_file_prompt(app, key);
+        return;
+    }
+    if app.receive_file_prompt.is_some() {
+        app_handle_keypresses_for_receive_file_prompt(app, key);
+        return;
+    }
+    if app.modbus_prompt.is_some() {
+        app_handle_keypresses_for_modbus_prompt(app, key);
+        return;
+    }
+    if app.file_transfer.is_some() {
+        app_handle_keypresses_for_file_transfer_popup(app, key);
+        return;
+    }
+
     // key handler (main_screen_active_region-independent)
     match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('f')) => {
+            app.main_screen_search.is_editing = true;
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('g')) => {
+            app.main_screen_filter.is_editing = true;
+        }
         (KeyModifiers::CONTROL, KeyCode::Char('?')) => {
             app.current_screen = CurrentScreen::Help;
         }
+        (KeyModifiers::CONTROL, KeyCode::Char('t')) => {
+            app.current_screen = CurrentScreen::Config1;
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+            app_open_snippet_picker(app);
+            return;
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('o')) => {
+            app.send_file_prompt = Some(SendFilePromptState {
+                path: String::new(),
+                method: SendFileMethod::Paced(app.app_config.file_send_mode),
+                is_auto_detected: false,
+            });
+            return;
+        }
+        (KeyModifiers::ALT, KeyCode::Char('1')) => {
+            app_toggle_modem_output_line(app, ModemOutputLine::Dtr);
+            return;
+        }
+        (KeyModifiers::ALT, KeyCode::Char('2')) => {
+            app_toggle_modem_output_line(app, ModemOutputLine::Rts);
+            return;
+        }
+        (KeyModifiers::ALT, KeyCode::Char('m')) => {
+            if app.app_config.decoder != Some(DecoderKind::ModbusRtu) {
+                // replies are only split into frames by the Modbus RTU decoder
+                app.app_config.decoder = Some(DecoderKind::ModbusRtu);
+                app.status_message = Some(String::from("Decoder set to Modbus RTU"));
+            }
+            app.modbus_prompt = Some(ModbusPromptState {
+                spec: String::new(),
+                pending_request: None,
+                last_reply: None,
+            });
+            return;
+        }
+        (KeyModifiers::ALT, KeyCode::Char('3')) => {
+            app_start_break(app);
+            return;
+        }
+        (KeyModifiers::ALT, KeyCode::Char(key_char)) if app.app_config.reset_recipes.iter().any(|recipe| recipe.alt_key == Some(key_char)) => {
+            if let Some(recipe) = app.app_config.reset_recipes.iter().find(|recipe| recipe.alt_key == Some(key_char)) {
+                app_start_reset(app, &recipe.name.clone());
+            }
+            return;
+        }
+        (KeyModifiers::ALT, KeyCode::Char('o')) => {
+            app.receive_file_prompt = Some(ReceiveFilePromptState {
+                path: String::new(),
+                protocol: FileTransferProtocol::Ymodem,
+                is_auto_detected: false,
+            });
+            return;
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('x')) if app.file_sender.is_some() => {
+            if let Some(file_sender) = app.file_sender.take() {
+                app.add_event_to_transfer_log(format!("Cancelled sending '{}' ({} of {} bytes sent)",
+                    file_sender.file_path.display(), file_sender.sent_byte_count, file_sender.total_byte_count));
+            }
+            return;
+        }
+        (KeyModifiers::NONE, KeyCode::F(function_key)) => {
+            match app.snippets.iter().position(|snippet| snippet.function_key == Some(function_key)) {
+                Some(snippet_idx) => app_send_snippet(app, snippet_idx),
+                None => app.status_message = Some(format!("No snippet is bound to F{}", function_key)),
+            }
+            return;
+        }
         (KeyModifiers::CONTROL, KeyCode::Char('b')) => {
             // TODO: if the PickBaudRate screen was skipped, then going back should skip right to the PickSerialPort screen
             app.current_screen = CurrentScreen::PickBaudRate;
         }
 
         (KeyModifiers::NONE, KeyCode::Esc | KeyCode::Tab) => {
-            app.main_screen_active_region = app.main_screen_active_region.next();
+            app.main_screen_active_region = app.main_screen_active_region.next(!app.tx_scheduler.jobs.is_empty());
         }
         (KeyModifiers::SHIFT, KeyCode::Tab) | (_, KeyCode::BackTab) => {
-            app.main_screen_active_region = app.main_screen_active_region.prev();
+            app.main_screen_active_region = app.main_screen_active_region.prev(!app.tx_scheduler.jobs.is_empty());
         }
         _ => {}
     }
@@ -300,94 +750,118 @@ fn app_handle_keypresses_for_main_screen(app: &mut App, key: KeyEvent) -> () {
     match app.main_screen_active_region {
         MainScreenActiveRegion::Input => {
             match (key.modifiers, key.code) {
-                (KeyModifiers::CONTROL, KeyCode::Char('h') | KeyCode::Backspace) => {
-                    // Ctrl+Backspace should delete the last word
-                    app.main_input = app.main_input.trim_end().to_string(); // first, remove all trailing spaces
-
-                    let last_space_idx = app.main_input.rfind(' ').unwrap_or(0);
-                    app.main_input.truncate(last_space_idx);
-                    
-                    // add a trailing space if there's still text
-                    if app.main_input.len() > 0 {
-                        app.main_input.push(' ');
+                // readline-style editing (Ctrl+B/Ctrl+F aren't used for motion, as Ctrl+B goes back to the baud rate screen)
+                (KeyModifiers::CONTROL, KeyCode::Char('h') | KeyCode::Backspace | KeyCode::Char('w'))
+                | (KeyModifiers::ALT, KeyCode::Backspace) => {
+                    // Ctrl+Backspace should delete the last word (it comes in as Ctrl+H sometimes)
+                    app.main_input.kill_word_before();
+                }
+                (KeyModifiers::ALT, KeyCode::Char('d')) | (KeyModifiers::CONTROL, KeyCode::Delete) => {
+                    app.main_input.kill_word_after();
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('u')) => {
+                    app.main_input.kill_to_start();
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('k')) => {
+                    app.main_input.kill_to_end();
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('y')) => {
+                    app.main_input.yank();
+                }
+                (KeyModifiers::ALT, KeyCode::Char('y')) => {
+                    app.main_input.yank_pop();
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
+                    let mut search = SendHistorySearchState::new();
+                    search.update_match(&app.main_input_send_history);
+                    app.main_input_send_history_search = Some(search);
+                }
+                (KeyModifiers::CONTROL, KeyCode::Char('z') | KeyCode::Char('_')) => {
+                    app.main_input.undo();
+                }
+                (_, KeyCode::Char('Z') | KeyCode::Char('z')) if key.modifiers == (KeyModifiers::CONTROL | KeyModifiers::SHIFT) => {
+                    app.main_input.redo();
+                }
+                (KeyModifiers::ALT, KeyCode::Char('z')) => {
+                    // for terminals that don't report Ctrl+Shift+Z
+                    app.main_input.redo();
+                }
+                (KeyModifiers::ALT, KeyCode::Char('e')) => {
+                    app.app_config.send_parse_escapes = !app.app_config.send_parse_escapes;
+                }
+                (KeyModifiers::ALT, KeyCode::Char('t')) => {
+                    app.app_config.send_expand_templates = !app.app_config.send_expand_templates;
+                }
+                (KeyModifiers::ALT, KeyCode::Char('r')) => {
+                    match app.main_input.get_text().is_empty() {
+                        true => app.status_message = Some(String::from("Type the message to repeat in the Send box first")),
+                        false => app.main_input_schedule_prompt = Some(String::new()),
                     }
                 }
 
-                // left and right arrow keys to move the cursor
+                // cursor motion
                 (KeyModifiers::NONE, KeyCode::Left) => {
-                    // move the cursor left
-                    match app.main_input_cursor_position {
-                        Some(pos) => {
-                            if pos > 0 {
-                                app.main_input_cursor_position = Some(pos - 1);
-                            }
-                        }
-                        None => {
-                            if app.main_input.len() > 0 {
-                                app.main_input_cursor_position = Some(app.main_input.len() - 1);
-                            }
-                        }
-                    }
+                    app.main_input.move_left();
                 }
                 (KeyModifiers::NONE, KeyCode::Right) => {
-                    // move the cursor right
-                    match app.main_input_cursor_position {
-                        Some(pos) => {
-                            if pos < (app.main_input.len() - 1) {
-                                app.main_input_cursor_position = Some(pos + 1);
-                            }
-                            else {
-                                app.main_input_cursor_position = None;
-                            }
-                        }
-                        None => {}
-                    }
+                    app.main_input.move_right();
                 }
-                
-                // home and end keys to jump the cursor
-                (KeyModifiers::NONE, KeyCode::Home) => {
-                    if app.main_input.len() > 0 {
-                        app.main_input_cursor_position = Some(0);
-                    }
+                (KeyModifiers::CONTROL, KeyCode::Left) | (KeyModifiers::ALT, KeyCode::Char('b')) => {
+                    app.main_input.move_word_left();
+                }
+                (KeyModifiers::CONTROL, KeyCode::Right) | (KeyModifiers::ALT, KeyCode::Char('f')) => {
+                    app.main_input.move_word_right();
+                }
+                (KeyModifiers::NONE, KeyCode::Home) | (KeyModifiers::CONTROL, KeyCode::Char('a')) => {
+                    app.main_input.move_home();
+                }
+                (KeyModifiers::NONE, KeyCode::End) | (KeyModifiers::CONTROL, KeyCode::Char('e')) => {
+                    app.main_input.move_end();
                 }
-                (KeyModifiers::NONE, KeyCode::End) => {
-                    app.main_input_cursor_position = None;
+                (KeyModifiers::CONTROL, KeyCode::Home) => {
+                    app.main_input.move_text_start();
+                }
+                (KeyModifiers::CONTROL, KeyCode::End) => {
+                    app.main_input.move_text_end();
                 }
 
                 (KeyModifiers::NONE, KeyCode::Up) => {
-                    // go back in the send history
-                    app.main_input_cursor_position = None;
+                    // move up within a multi-line block, and then go back in the send history
+                    if app.main_input.move_up() {
+                        return;
+                    }
                     match app.main_input_send_history_index {
                         Some(index) => {
+                            let index = index.min(app.main_input_send_history.len());
                             if index > 0 {
                                 app.main_input_send_history_index = Some(index - 1);
-                                app.main_input = app.main_input_send_history[index - 1].clone();
+                                app.main_input.set_text(app.main_input_send_history.get(index - 1));
                             }
                         }
                         None => {
-                            if app.main_input_send_history.len() > 0 {
+                            if !app.main_input_send_history.is_empty() {
                                 app.main_input_send_history_index = Some(app.main_input_send_history.len() - 1);
-                                app.main_input_typing_in_progress_but_not_sent = Some(app.main_input.clone());
-                                app.main_input = app.main_input_send_history.last().unwrap_or(&String::new()).clone();
+                                app.main_input_typing_in_progress_but_not_sent = Some(app.main_input.get_text().to_string());
+                                app.main_input.set_text(app.main_input_send_history.get(app.main_input_send_history.len() - 1));
                             }
                         }
                     }
                 }
                 (KeyModifiers::NONE, KeyCode::Down) => {
-                    app.main_input_cursor_position = None;
-                    // go forward in the send history
+                    // move down within a multi-line block, and then go forward in the send history
+                    if app.main_input.move_down() {
+                        return;
+                    }
                     match app.main_input_send_history_index {
                         Some(index) => {
-                            if index < (app.main_input_send_history.len() - 1) {
+                            // (the history can be shorter than the index, e.g., if it was reloaded for another port)
+                            if index + 1 < app.main_input_send_history.len() {
                                 app.main_input_send_history_index = Some(index + 1);
-                                app.main_input = app.main_input_send_history[index + 1].clone();
-                            }
-                            else if index == (app.main_input_send_history.len() - 1) {
-                                app.main_input_send_history_index = None;
-                                app.main_input = app.main_input_typing_in_progress_but_not_sent.take().unwrap_or(String::new());
+                                app.main_input.set_text(app.main_input_send_history.get(index + 1));
                             }
                             else {
-                                panic!("Index out of bounds in send history");
+                                app.main_input_send_history_index = None;
+                                app.main_input.set_text(&app.main_input_typing_in_progress_but_not_sent.take().unwrap_or_default());
                             }
                         }
                         None => {
@@ -398,90 +872,51 @@ fn app_handle_keypresses_for_main_screen(app: &mut App, key: KeyEvent) -> () {
                 
 
                 (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
-                    match app.main_input_cursor_position {
-                        Some(pos) => {
-                            app.main_input.insert(pos, c);
-                            app.main_input_cursor_position = Some(pos + 1);
-                        }
-                        None => {
-                            app.main_input.push(c);
-                        }
-                    }
+                    app.main_input.insert_char(c);
                 }
                 (KeyModifiers::NONE, KeyCode::Backspace) => {
-                    match app.main_input_cursor_position {
-                        Some(main_input_cursor_position) => {
-                            if (app.main_input.len() > 0) && (main_input_cursor_position > 0) {
-                                app.main_input.remove(main_input_cursor_position - 1);
-                                app.main_input_cursor_position = Some(main_input_cursor_position - 1);
-                            }
-                            if main_input_cursor_position == app.main_input.len() {
-                                // if we're now back at the new end, mark as such
-                                app.main_input_cursor_position = None
-                            }
-                        }
-                        None => {
-                            app.main_input.pop();
-                        }
-                    }
+                    app.main_input.delete_grapheme_before();
                 }
                 (KeyModifiers::NONE, KeyCode::Delete) => {
-                    match app.main_input_cursor_position {
-                        Some(main_input_cursor_position) => {
-                            if main_input_cursor_position < app.main_input.len() {
-                                app.main_input.remove(main_input_cursor_position);
-
-                                if main_input_cursor_position == app.main_input.len() {
-                                    app.main_input_cursor_position = None;
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
+                    app.main_input.delete_grapheme_after();
+                }
+                // Shift+Enter and Ctrl+Enter need a terminal that reports them; Alt+Enter works almost everywhere
+                (KeyModifiers::SHIFT | KeyModifiers::CONTROL | KeyModifiers::ALT, KeyCode::Enter) => {
+                    app.main_input.insert_char('\n');
                 }
-                // TODO: control+enter and shift+enter should allow adding newlines within a text block
                 (KeyModifiers::NONE, KeyCode::Enter) => {
-                    match &mut app.bound_serial_port {
-                        Some(port) => {
-                            let mut data_string = app.main_input.clone();
-                            data_string.push_str(&app.app_config.end_of_line);
-                            let data = data_string.as_bytes();
-
-                            match port.write(data) {
-                                Ok(_) => {
-                                    app.main_input_send_history.push(app.main_input.clone());
-                                    app.main_input_send_history_index = None;
-
-                                    match app.app_config.echo_mode {
-                                        EchoMode::On => {
-                                            // TODO: show newline chars well
-                                            app.add_echo_to_transfer_log(
-                                                app.main_input.clone().into_bytes());
-                                        }
-                                        EchoMode::Off => {}
-                                    }
-
-                                    app.main_input.clear();
-                                    app.main_input_cursor_position = None;
-                                }
-                                Err(e) => {
-                                    // TODO: handle this disconnect situation better - probably go back to port selection screen
-                                    app.main_input.push_str(&format!("Error writing to serial port: {}", e));
-                                }
-                            }
-
-                        }
-                        None => {
-                            // this should never really happen
-                            // TODO: move this error message to the other side
-                            app.main_input.push_str("Error: Serial port unbound itself between seeing if bytes are available, and reading them.");
-                        }
-                    }
+                    app_send_main_input(app);
                 }
                 _ => {}
             }
         }
         
+        MainScreenActiveRegion::SchedulerPanel => {
+            let job_count = app.tx_scheduler.jobs.len();
+            let selected_job_idx = app.scheduler_panel_list_state.selected().unwrap_or(0).min(job_count.saturating_sub(1));
+            match key.code {
+                KeyCode::Up | KeyCode::Char('k') => {
+                    app.scheduler_panel_list_state.select(Some(selected_job_idx.saturating_sub(1)));
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    app.scheduler_panel_list_state.select(Some((selected_job_idx + 1).min(job_count.saturating_sub(1))));
+                }
+                KeyCode::Char(' ') | KeyCode::Char('p') => {
+                    app.tx_scheduler.toggle_pause(selected_job_idx);
+                }
+                KeyCode::Char('x') | KeyCode::Delete => {
+                    app.tx_scheduler.cancel(selected_job_idx);
+                }
+                KeyCode::Char('X') => {
+                    app.tx_scheduler.jobs.clear();
+                }
+                _ => {}
+            }
+            if app.tx_scheduler.jobs.is_empty() {
+                app.main_screen_active_region = MainScreenActiveRegion::Input;
+            }
+        }
+
         MainScreenActiveRegion::OutputScrollBars => {
             match key.code {
                 KeyCode::Esc => {
@@ -550,6 +985,54 @@ fn app_handle_keypresses_for_main_screen(app: &mut App, key: KeyEvent) -> () {
                 }
 
 
+                KeyCode::Char('/') => {
+                    app.main_screen_search.is_editing = true;
+                }
+                KeyCode::Char('&') => {
+                    app.main_screen_filter.is_editing = true;
+                }
+                KeyCode::Char('F') => {
+                    app.main_screen_filter.clear();
+                }
+                KeyCode::Char('n') => {
+                    app.main_screen_search.select_next_match();
+                }
+                KeyCode::Char('N') => {
+                    app.main_screen_search.select_prev_match();
+                }
+
+                KeyCode::Char('t') => {
+                    // cycle through the timestamp gutter modes
+                    app.app_config.timestamp_mode = app.app_config.timestamp_mode.next();
+                }
+                KeyCode::Char('w') => {
+                    app.app_config.line_wrap = !app.app_config.line_wrap;
+                }
+                KeyCode::Char('d') => {
+                    match app.app_config.decoder {
+                        Some(_) => app.app_config.decoded_view = app.app_config.decoded_view.next(),
+                        None => app.status_message = Some(String::from("No decoder is picked (pick one on the Config screen, Ctrl+T)")),
+                    }
+                }
+
+                KeyCode::Char('e') => {
+                    app_export_transfer_log(app, LogExportFormat::Html);
+                }
+                KeyCode::Char('E') => {
+                    app_export_transfer_log(app, LogExportFormat::Ansi);
+                }
+
+                // copy to the clipboard (a range is copied by selecting it with the mouse)
+                KeyCode::Char('y') => {
+                    app_copy_current_log_line(app);
+                }
+                KeyCode::Char('Y') => {
+                    app_copy_visible_log_lines(app);
+                }
+                KeyCode::Char('a') => {
+                    app_copy_filtered_log(app);
+                }
+
                 KeyCode::Enter => {
                     // TODO: send the data
                     // FIXME: confirm this can be removed; I think it can
@@ -591,47 +1074,1132 @@ fn app_handle_keypresses_for_main_screen(app: &mut App, key: KeyEvent) -> () {
     }
 }
 
-fn app_handle_keypresses_for_config1_screen(app: &mut App, key: KeyEvent) -> () {
-    match key.code {
-        KeyCode::Esc | KeyCode::Enter => {
-            app.current_screen = CurrentScreen::Main;
-        }
-        // TODO: handle changing the config
-        _ => {}
+/// Sends the Send box's text, with the EOL after each line. A multi-line block is sent according to
+/// the multi-line send policy: all at once, or line by line (with the multi-line delay after each line).
+fn app_send_main_input(app: &mut App) {
+    let text = app.main_input.get_text().to_string();
+    let lines = split_message_lines(&text);
+    let eol = app.app_config.end_of_line.clone();
+    let is_line_by_line = matches!(app.app_config.multi_line_send_policy, MultiLineSendPolicy::LineByLine);
+    if let Err(e) = app_queue_message(app, &lines, false, &eol, is_line_by_line, std::time::Duration::ZERO) {
+        // keep the text, so that it can be fixed
+        app.status_message = Some(format!("Not sent: {}", e));
+        return;
+    }
+
+    if let Err(e) = app.main_input_send_history.push(text) {
+        app.status_message = Some(e);
     }
+    app.main_input_send_history_index = None;
+    app.main_input.reset();
 }
 
-fn app_handle_keypresses_for_help_screen(app: &mut App, key: KeyEvent) -> () {
-    match key.code {
-        KeyCode::Esc => {
-            app.current_screen = CurrentScreen::Main;
+/// Sends a snippet, the same way as the Send box's text.
+fn app_send_snippet(app: &mut App, snippet_idx: usize) {
+    let snippet = &app.snippets[snippet_idx];
+    let data = snippet.data.clone();
+    let lines = split_message_lines(&data);
+    let eol = snippet.get_end_of_line(&app.app_config.end_of_line).to_string();
+    let (name, is_hex, delay_after) = (snippet.name.clone(), snippet.is_hex, snippet.delay_after);
+    if let Err(e) = app_queue_message(app, &lines, is_hex, &eol, false, delay_after) {
+        app.status_message = Some(format!("Error in snippet '{}': {}", name, e));
+    }
+}
+
+/// Expands the lines of a message (escapes and templates, if they're on), and queues it to be sent:
+/// all at once (then waiting `delay_after`), or line by line (with the multi-line delay after each line).
+/// Nothing is queued if expanding any line fails. Returns the error if writing it to the port fails.
+fn app_queue_message(app: &mut App, lines: &[&str], is_hex: bool, eol: &str, is_line_by_line: bool, delay_after: std::time::Duration) -> Result<(), String> {
+    let options = SendTextOptions {
+        is_hex,
+        parse_escapes: app.app_config.send_parse_escapes,
+        expand_templates: app.app_config.send_expand_templates,
+    };
+    let expanded_lines = expand_message_lines(lines, eol, options, &mut app.send_template_context)?;
+    match (is_line_by_line, expanded_lines.len()) {
+        (true, 2..) => {
+            for (data, echo_line) in expanded_lines {
+                app.tx_queue.push(TxQueueItem {
+                    data,
+                    echo_lines: vec![echo_line],
+                    delay_after: app.app_config.multi_line_send_delay,
+                });
+            }
         }
         _ => {
-            app.current_screen = CurrentScreen::Main;
+            let (data_per_line, echo_lines): (Vec<Vec<u8>>, Vec<String>) = expanded_lines.into_iter().unzip();
+            app.tx_queue.push(TxQueueItem {
+                data: data_per_line.concat(),
+                echo_lines,
+                delay_after,
+            });
         }
     }
+    app_send_tx_queue(app)
 }
 
-/// Attempts to transition the app to the main screen by opening the serial port.
-/// If an error occurs, the app will revert back to the serial port selection screen, with an error message.
-fn app_transition_to_main(app: &mut App) -> () {
-    // attempt to open the serial port
-    match (&app.selected_serial_port, app.app_config.baud_rate) {
-        (Some(port_name), Some(baud_rate)) => {
-            match bind_serial_port(&port_name, baud_rate) {
-                Ok(serial_port) => {
-                    app.bound_serial_port = Some(serial_port);
-                    app.current_screen = CurrentScreen::Main;
-                }
-                Err(e) => {
-                    app.general_error_message = Some(format!("Error binding serial port: {}", e));
-                    app.current_screen = CurrentScreen::PickSerialPort;
-                }
-            }
+/// Writes the queued data that's ready to be sent (i.e., once the delay after the previous item has passed),
+/// unless sending is paused (by flow control, a BREAK, or a reset); then it stays queued.
+/// On a write error, the queue is dropped, and the error is logged (and returned).
+fn app_send_tx_queue(app: &mut App) -> Result<(), String> {
+    if app.get_tx_pause_reason().is_some() {
+        return Ok(());
+    }
+    while let Some(item) = app.tx_queue.pop_ready() {
+        let echo_lines: Vec<&str> = item.echo_lines.iter().map(|line| line.as_str()).collect();
+        if let Err(e) = app_write_to_serial_port(app, &item.data, &echo_lines) {
+            // TODO: handle this disconnect situation better - probably go back to port selection screen
+            app.add_error_to_transfer_log(e.clone());
+            app.tx_queue.clear();
+            app.tx_scheduler.pause_all(); // rather than failing again every interval
+            return Err(e);
         }
-        (None, _) => {
-            // this should never really happen
-            app.general_error_message = Some(format!("Error: No serial port selected"));
+    }
+    Ok(())
+}
+
+/// Sends the messages of the scheduled jobs that are due.
+fn app_run_scheduled_sends(app: &mut App) {
+    let eol = app.app_config.end_of_line.clone();
+    for scheduled_send in app.tx_scheduler.take_due_sends() {
+        let lines = split_message_lines(&scheduled_send.text);
+        let eol = match scheduled_send.is_hex {
+            true => "",
+            false => eol.as_str(),
+        };
+        if let Err(e) = app_queue_message(app, &lines, scheduled_send.is_hex, eol, false, std::time::Duration::ZERO) {
+            app.tx_scheduler.pause_job(scheduled_send.job_id);
+            app.status_message = Some(format!("Paused scheduled send #{}: {}", scheduled_send.job_id, e));
+        }
+    }
+    if app.tx_scheduler.jobs.is_empty() && matches!(app.main_screen_active_region, MainScreenActiveRegion::SchedulerPanel) {
+        app.main_screen_active_region = MainScreenActiveRegion::Input;
+    }
+}
+
+fn app_handle_keypresses_for_schedule_prompt(app: &mut App, key: KeyEvent) {
+    let Some(schedule_spec) = &mut app.main_input_schedule_prompt else { return };
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) => {
+            app.main_input_schedule_prompt = None;
+        }
+        (_, KeyCode::Enter) => {
+            match parse_schedule_spec(schedule_spec) {
+                Ok((interval, count, is_hex)) => {
+                    let job_id = app.tx_scheduler.add_job(app.main_input.get_text().to_string(), is_hex, interval, count);
+                    app.status_message = Some(format!("Scheduled send #{} (Tab to the scheduler panel to pause or cancel it)", job_id));
+                    app.main_input_schedule_prompt = None;
+                }
+                Err(e) => {
+                    app.status_message = Some(e);
+                }
+            }
+        }
+        (_, KeyCode::Backspace) => {
+            schedule_spec.pop();
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            schedule_spec.push(c);
+        }
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_modbus_prompt(app: &mut App, key: KeyEvent) {
+    let Some(modbus_prompt) = &mut app.modbus_prompt else { return };
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) => {
+            app.modbus_prompt = None;
+        }
+        (_, KeyCode::Enter) => {
+            match ModbusRequest::from_spec(&modbus_prompt.spec) {
+                Ok(request) => {
+                    let frame = request.build_frame();
+                    // the echo is the frame in hex, so that the decoder shows the request too
+                    let echo_line = frame.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+                    modbus_prompt.pending_request = Some((request, std::time::Instant::now()));
+                    modbus_prompt.last_reply = None;
+                    app.tx_queue.push(TxQueueItem {
+                        data: frame,
+                        echo_lines: vec![echo_line],
+                        delay_after: std::time::Duration::ZERO,
+                    });
+                    if let Err(e) = app_send_tx_queue(app) {
+                        if let Some(modbus_prompt) = &mut app.modbus_prompt {
+                            modbus_prompt.pending_request = None;
+                            modbus_prompt.last_reply = Some(Err(e));
+                        }
+                    }
+                }
+                Err(e) => {
+                    modbus_prompt.last_reply = Some(Err(e));
+                }
+            }
+        }
+        (_, KeyCode::Backspace) => {
+            modbus_prompt.spec.pop();
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            modbus_prompt.spec.push(c);
+        }
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_send_file_prompt(app: &mut App, key: KeyEvent) {
+    let Some(prompt) = &mut app.send_file_prompt else { return };
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) => {
+            app.send_file_prompt = None;
+        }
+        (_, KeyCode::Enter) => {
+            let (path, method) = (PathBuf::from(&prompt.path), prompt.method);
+            app.send_file_prompt = None;
+            match method {
+                SendFileMethod::Paced(mode) => app_start_file_send(app, &path, mode),
+                SendFileMethod::Protocol(protocol) => {
+                    let file_transfer = start_file_transfer_send(protocol, &path);
+                    app_start_file_transfer(app, file_transfer);
+                }
+            }
+        }
+        (_, KeyCode::Tab) => {
+            prompt.path = complete_path(&prompt.path);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+            prompt.method = prompt.method.next();
+        }
+        (_, KeyCode::Backspace) => {
+            prompt.path.pop();
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            prompt.path.push(c);
+        }
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_receive_file_prompt(app: &mut App, key: KeyEvent) {
+    let Some(prompt) = &mut app.receive_file_prompt else { return };
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) => {
+            app.receive_file_prompt = None;
+        }
+        (_, KeyCode::Enter) => {
+            let (path, protocol) = (PathBuf::from(&prompt.path), prompt.protocol);
+            app.receive_file_prompt = None;
+            let file_transfer = start_file_transfer_receive(protocol, &path);
+            app_start_file_transfer(app, file_transfer);
+        }
+        (_, KeyCode::Tab) => {
+            prompt.path = complete_path(&prompt.path);
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+            prompt.protocol = prompt.protocol.next();
+        }
+        (_, KeyCode::Backspace) => {
+            prompt.path.pop();
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            prompt.path.push(c);
+        }
+        _ => {}
+    }
+}
+
+/// The file transfer popup takes all keypresses: Ctrl+X cancels the transfer, and Esc/Enter close it once it's finished.
+fn app_handle_keypresses_for_file_transfer_popup(app: &mut App, key: KeyEvent) {
+    let Some(file_transfer) = &mut app.file_transfer else { return };
+    let is_running = file_transfer.get_status().state == FileTransferState::InProgress;
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('x')) if is_running => {
+            let cancel_bytes = file_transfer.cancel();
+            // the other side gives up on its own if this doesn't get through
+            let _ = app_write_to_serial_port(app, &cancel_bytes, &[]);
+            app_log_file_transfer_result(app);
+        }
+        (_, KeyCode::Esc | KeyCode::Enter) if !is_running => {
+            app.file_transfer = None;
+        }
+        _ => {}
+    }
+}
+
+/// Starts a file transfer (or shows why it couldn't start). It stops any file that's being sent (paced).
+fn app_start_file_transfer(app: &mut App, file_transfer: Result<Box<dyn file_transfer::FileTransfer>, String>) {
+    match file_transfer {
+        Ok(file_transfer) => {
+            app.file_sender = None;
+            app.tx_queue.clear();
+            app.file_transfer = Some(file_transfer);
+        }
+        Err(e) => {
+            app.status_message = Some(e);
+        }
+    }
+}
+
+/// Opens the receive (for `sz`) or send (for `rz`) prompt, when the other side starts a ZMODEM transfer.
+/// Nothing happens if something else is open, so that a prompt the user is typing in isn't replaced.
+fn app_offer_zmodem_transfer(app: &mut App, zmodem_request: ZmodemRequest) {
+    if app.file_transfer.is_some() || is_main_screen_prompt_open(app) || app.snippet_picker_list_state.is_some() {
+        return;
+    }
+    match zmodem_request {
+        ZmodemRequest::Receive => {
+            app.receive_file_prompt = Some(ReceiveFilePromptState {
+                path: String::new(),
+                protocol: FileTransferProtocol::Zmodem,
+                is_auto_detected: true,
+            });
+        }
+        ZmodemRequest::Send => {
+            app.send_file_prompt = Some(SendFilePromptState {
+                path: String::new(),
+                method: SendFileMethod::Protocol(FileTransferProtocol::Zmodem),
+                is_auto_detected: true,
+            });
+        }
+    }
+}
+
+/// Whether a file transfer is running (rather than finished, with its popup still open).
+fn is_file_transfer_running(app: &App) -> bool {
+    app.file_transfer.as_ref()
+        .is_some_and(|file_transfer| file_transfer.get_status().state == FileTransferState::InProgress)
+}
+
+/// Passes received data to the running file transfer (or just lets it check its timeouts, when None), and writes its reply.
+fn app_run_file_transfer(app: &mut App, rxd_data: Option<&[u8]>) {
+    let Some(file_transfer) = &mut app.file_transfer else { return };
+    let reply = match rxd_data {
+        Some(rxd_data) => file_transfer.handle_rxd_data(rxd_data),
+        None => file_transfer.tick(),
+    };
+    if !reply.is_empty() {
+        if let Err(e) = app_write_to_serial_port(app, &reply, &[]) {
+            app.add_error_to_transfer_log(e);
+            if let Some(file_transfer) = &mut app.file_transfer {
+                file_transfer.cancel();
+            }
+        }
+    }
+    if !is_file_transfer_running(app) {
+        app_log_file_transfer_result(app);
+    }
+}
+
+/// Adds how the file transfer ended to the transfer log (errors) or the status message, once it's finished.
+fn app_log_file_transfer_result(app: &mut App) {
+    let Some(file_transfer) = &app.file_transfer else { return };
+    let status = file_transfer.get_status();
+    match &status.state {
+        FileTransferState::InProgress => {}
+        FileTransferState::Done => {
+            app.status_message = Some(format!("{}: done ({} bytes, {} retries)",
+                status.title, status.transferred_byte_count, status.retry_count));
+        }
+        FileTransferState::Failed(e) => {
+            let error_message = format!("{}: failed after {} bytes: {}", status.title, status.transferred_byte_count, e);
+            app.add_error_to_transfer_log(error_message);
+        }
+    }
+}
+
+/// Starts sending a file (replacing any file that's still being sent), paced as set in the config.
+fn app_start_file_send(app: &mut App, path: &std::path::Path, mode: FileSendMode) {
+    let options = FileSendOptions {
+        mode,
+        chunk_size: app.app_config.file_send_chunk_size,
+        chunk_delay: app.app_config.file_send_chunk_delay,
+        line_delay: app.app_config.file_send_line_delay,
+        prompt_regex: app.app_config.file_send_prompt_regex.clone(),
+        prompt_timeout: app.app_config.file_send_prompt_timeout,
+    };
+    match FileSender::new(path, options, &app.app_config.end_of_line) {
+        Ok(file_sender) => {
+            app.file_sender = Some(file_sender);
+        }
+        Err(e) => {
+            app.status_message = Some(e);
+        }
+    }
+}
+
+/// Writes the next chunks of the file being sent, as their delays pass (and their prompts are received), unless sending is paused.
+fn app_run_file_send(app: &mut App) {
+    if app.get_tx_pause_reason().is_some() {
+        return;
+    }
+    let loop_start_time = std::time::Instant::now();
+    while loop_start_time.elapsed() < FILE_SEND_MAX_TIME_PER_LOOP {
+        let Some(file_sender) = &mut app.file_sender else { return };
+        match file_sender.take_next_chunk() {
+            Ok(Some(chunk)) => {
+                let echo_lines: Vec<&str> = chunk.echo_line.iter().map(|line| line.as_str()).collect();
+                if let Err(e) = app_write_to_serial_port(app, &chunk.data, &echo_lines) {
+                    app.add_error_to_transfer_log(e);
+                    app.file_sender = None;
+                    return;
+                }
+            }
+            Ok(None) => {
+                if file_sender.is_done() {
+                    app.status_message = Some(format!("Sent '{}' ({} bytes, {})", file_sender.file_path.display(),
+                        file_sender.total_byte_count, file_sender.options.mode.get_display_name()));
+                    app.file_sender = None;
+                }
+                return;
+            }
+            Err(e) => {
+                let error_message = format!("Stopped sending '{}': {}", file_sender.file_path.display(), e);
+                app.add_error_to_transfer_log(error_message);
+                app.file_sender = None;
+                return;
+            }
+        }
+    }
+}
+
+/// Reloads the snippets file, so that edits show up without restarting.
+fn app_load_snippets(app: &mut App) {
+    match load_snippets(&get_workspace_snippets_file()) {
+        Ok(snippets) => {
+            app.snippets = snippets;
+        }
+        Err(e) => {
+            app.status_message = Some(e.to_string());
+        }
+    }
+}
+
+fn app_open_snippet_picker(app: &mut App) {
+    app_load_snippets(app);
+    let mut list_state = ListState::default();
+    if !app.snippets.is_empty() {
+        list_state.select(Some(0));
+    }
+    app.snippet_picker_list_state = Some(list_state);
+}
+
+/// Switches to the next flow control mode, and applies it to the bound port.
+fn app_change_flow_control(app: &mut App) {
+    let flow_control = app.app_config.flow_control.next();
+    if let Some(port) = &mut app.bound_serial_port {
+        if let Err(e) = port.set_flow_control(flow_control.get_driver_flow_control()) {
+            app.config1_error_message = Some(format!("Error setting flow control: {}", e));
+            return;
+        }
+    }
+    app.app_config.flow_control = flow_control;
+    app.is_tx_paused_by_xoff = false;
+    app.config1_error_message = None;
+}
+
+/// Reads the modem control inputs, and logs the ones that changed.
+fn app_poll_modem_lines(app: &mut App) {
+    let Some(port) = &mut app.bound_serial_port else { return };
+    match ModemInputLines::read(port) {
+        Ok(inputs) => {
+            let changes = match &app.modem_lines.inputs {
+                Some(previous_inputs) => inputs.get_changes(previous_inputs),
+                None => Vec::new(),
+            };
+            app.modem_lines.inputs = Some(inputs);
+            if !changes.is_empty() {
+                app.add_event_to_transfer_log(changes.join(", "));
+            }
+        }
+        Err(e) => {
+            // e.g., a PTY, or an adapter without the lines; don't keep trying
+            app.modem_lines.is_unsupported = true;
+            app.status_message = Some(e);
+        }
+    }
+}
+
+fn app_toggle_modem_output_line(app: &mut App, line: ModemOutputLine) {
+    let Some(port) = &mut app.bound_serial_port else { return };
+    // the OS usually turns DTR and RTS on when the port is opened
+    let level = !app.modem_lines.get_output(line).unwrap_or(true);
+    match app.modem_lines.set_output(port, line, level) {
+        Ok(()) => app.add_event_to_transfer_log(format!("{} {} (set)", line.get_display_name(), if level { "on" } else { "off" })),
+        Err(e) => app.add_error_to_transfer_log(e),
+    }
+}
+
+/// Starts holding the line low; it's released by `app_end_break` once the BREAK duration has passed.
+fn app_start_break(app: &mut App) {
+    if app.break_end_time.is_some() {
+        return;
+    }
+    let Some(port) = &mut app.bound_serial_port else { return };
+    match port.set_break() {
+        Ok(()) => {
+            let break_duration = app.app_config.break_duration;
+            app.break_end_time = Some(std::time::Instant::now() + break_duration);
+            app.add_event_to_transfer_log(format!("BREAK sent ({} ms)", break_duration.as_millis()));
+        }
+        Err(e) => app.add_error_to_transfer_log(format!("Error sending BREAK: {}", e)),
+    }
+}
+
+fn app_end_break(app: &mut App) {
+    app.break_end_time = None;
+    let Some(port) = &mut app.bound_serial_port else { return };
+    if let Err(e) = port.clear_break() {
+        app.add_error_to_transfer_log(format!("Error ending BREAK: {}", e));
+    }
+}
+
+/// Starts running a reset recipe; its steps are run by `app_run_reset`, at their times.
+fn app_start_reset(app: &mut App, recipe_name: &str) {
+    if app.bound_serial_port.is_none() {
+        return;
+    }
+    if let Some(reset_runner) = &app.reset_runner {
+        app.status_message = Some(format!("Reset '{}' is still running", reset_runner.recipe_name));
+        return;
+    }
+    match app.app_config.reset_recipes.iter().find(|recipe| recipe.name == recipe_name) {
+        Some(recipe) => {
+            app.reset_runner = Some(ResetRunner::new(recipe));
+            app.add_event_to_transfer_log(format!("Reset: {}", recipe_name));
+            app_run_reset(app);
+        }
+        None => app.status_message = Some(format!("Unknown reset recipe '{}'", recipe_name)),
+    }
+}
+
+/// Makes the DTR/RTS changes of the running reset recipe that are due.
+fn app_run_reset(app: &mut App) {
+    let Some(reset_runner) = &mut app.reset_runner else { return };
+    let now = std::time::Instant::now();
+    let line_changes = reset_runner.take_due_line_changes(now);
+    let is_done = reset_runner.is_done(now);
+    let Some(port) = &mut app.bound_serial_port else {
+        app.reset_runner = None;
+        return;
+    };
+    for (line, level) in line_changes {
+        if let Err(e) = app.modem_lines.set_output(port, line, level) {
+            if let Some(reset_runner) = app.reset_runner.take() {
+                app.add_error_to_transfer_log(format!("Reset '{}' stopped: {}", reset_runner.recipe_name, e));
+            }
+            return;
+        }
+    }
+    if is_done {
+        app.reset_runner = None;
+    }
+}
+
+/// Writes `data` to the serial port, and echoes `echo_lines` to the transfer log (if echo is on).
+/// In RS-485 mode, the direction line is set around the write, and the bus echo is expected back.
+fn app_write_to_serial_port(app: &mut App, data: &[u8], echo_lines: &[&str]) -> Result<(), String> {
+    match (&mut app.bound_serial_port, &app.app_config.rs485) {
+        (Some(port), Some(rs485_config)) => {
+            if rs485_config.echo_mode != BusEchoMode::None {
+                app.bus_echo_filter.expect(data);
+            }
+            rs485_config.write_half_duplex(port, &mut app.modem_lines, data)?;
+        }
+        (Some(port), None) => {
+            port.write_all(data)
+                .map_err(|e| format!("Error writing to serial port: {}", e))?;
+        }
+        (None, _) => {
+            // this should never really happen
+            return Err(String::from("Error: Serial port unbound itself between seeing if bytes are available, and reading them."));
+        }
+    }
+
+    // when the bus echo is shown, it takes the place of the local echo (it's what actually went out)
+    let is_bus_echo_shown = app.app_config.rs485.as_ref().is_some_and(|rs485_config| rs485_config.echo_mode == BusEchoMode::Mark);
+    match app.app_config.echo_mode {
+        EchoMode::On if is_bus_echo_shown => {}
+        EchoMode::On => {
+            for line in echo_lines {
+                app.add_echo_to_transfer_log(line.as_bytes().to_vec());
+            }
+        }
+        EchoMode::Off => {}
+    }
+    Ok(())
+}
+
+/// Shows our own bytes from the RS-485 bus as echo, a line at a time.
+fn app_add_bus_echo_to_transfer_log(app: &mut App, bus_echo: &[u8]) {
+    for line in bus_echo.split(|&byte| byte == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if !line.is_empty() {
+            app.add_echo_to_transfer_log(line.to_vec());
+        }
+    }
+}
+
+fn app_handle_keypresses_for_main_screen_filter_prompt(app: &mut App, key: KeyEvent) {
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) => {
+            app.main_screen_filter.is_editing = false;
+            app.main_screen_filter.clear();
+        }
+        (_, KeyCode::Enter) => {
+            app.main_screen_filter.is_editing = false;
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
+            app.main_screen_filter.is_regex = !app.main_screen_filter.is_regex;
+            app.main_screen_filter.compile_expression();
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('v')) => {
+            app.main_screen_filter.is_inverted = !app.main_screen_filter.is_inverted;
+            app.main_screen_filter.compile_expression();
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('e')) => {
+            app.main_screen_filter.hide_echo = !app.main_screen_filter.hide_echo;
+            app.main_screen_filter.compile_expression();
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('x')) => {
+            app.main_screen_filter.hide_errors = !app.main_screen_filter.hide_errors;
+            app.main_screen_filter.compile_expression();
+        }
+        (_, KeyCode::Backspace) => {
+            app.main_screen_filter.expression.pop();
+            app.main_screen_filter.compile_expression();
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            app.main_screen_filter.expression.push(c);
+            app.main_screen_filter.compile_expression();
+        }
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_main_screen_search_prompt(app: &mut App, key: KeyEvent) {
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) => {
+            app.main_screen_search.is_editing = false;
+            app.main_screen_search.clear();
+        }
+        (_, KeyCode::Enter) => {
+            app.main_screen_search.is_editing = false;
+            app.main_screen_search.update_matches(&app.main_screen_transfer_log, &app.main_screen_filter);
+            app.main_screen_search.select_first_match_from(app.main_screen_top_entry_idx);
+
+            // so that n/N work right away
+            app.main_screen_active_region = MainScreenActiveRegion::OutputScrollBars;
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
+            app.main_screen_search.is_regex = !app.main_screen_search.is_regex;
+            app.main_screen_search.compile_query();
+        }
+        (_, KeyCode::Backspace) => {
+            app.main_screen_search.query.pop();
+            app.main_screen_search.compile_query();
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            app.main_screen_search.query.push(c);
+            app.main_screen_search.compile_query();
+        }
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_send_history_search_prompt(app: &mut App, key: KeyEvent) {
+    let Some(search) = &mut app.main_input_send_history_search else { return };
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('g')) => {
+            app.main_input_send_history_search = None;
+        }
+        (_, KeyCode::Enter) => {
+            // put the match in the Send box, as if it had been reached with Up
+            if let Some(match_idx) = search.match_idx {
+                if app.main_input_send_history_index.is_none() {
+                    app.main_input_typing_in_progress_but_not_sent = Some(app.main_input.get_text().to_string());
+                }
+                app.main_input_send_history_index = Some(match_idx);
+                app.main_input.set_text(app.main_input_send_history.get(match_idx));
+            }
+            app.main_input_send_history_search = None;
+        }
+        (KeyModifiers::CONTROL, KeyCode::Char('r')) => {
+            search.select_older_match(&app.main_input_send_history);
+        }
+        (_, KeyCode::Backspace) => {
+            search.query.pop();
+            search.update_match(&app.main_input_send_history);
+        }
+        (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+            search.query.push(c);
+            search.update_match(&app.main_input_send_history);
+        }
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_snippet_picker(app: &mut App, key: KeyEvent) {
+    let Some(list_state) = &mut app.snippet_picker_list_state else { return };
+    let snippet_count = app.snippets.len();
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+            app.snippet_picker_list_state = None;
+        }
+        (_, KeyCode::Up | KeyCode::Char('k')) if snippet_count > 0 => {
+            list_state.select(Some(list_state.selected().unwrap_or(0).checked_sub(1).unwrap_or(snippet_count - 1)));
+        }
+        (_, KeyCode::Down | KeyCode::Char('j')) if snippet_count > 0 => {
+            list_state.select(Some((list_state.selected().map(|idx| idx + 1).unwrap_or(0)) % snippet_count));
+        }
+        (_, KeyCode::Enter) => {
+            if let Some(snippet_idx) = list_state.selected() {
+                app.snippet_picker_list_state = None;
+                app_send_snippet(app, snippet_idx);
+            }
+        }
+        (KeyModifiers::NONE, KeyCode::F(_)) => {
+            // function keys still send their snippets
+            app.snippet_picker_list_state = None;
+            app_handle_keypresses_for_main_screen(app, key);
+        }
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_config1_screen(app: &mut App, key: KeyEvent) -> () {
+    // typing a new highlight rule
+    if let Some(rule_input) = &mut app.config1_rule_input {
+        match key.code {
+            KeyCode::Esc => {
+                app.config1_rule_input = None;
+                app.config1_error_message = None;
+            }
+            KeyCode::Enter => {
+                match HighlightRule::from_rule_string(rule_input) {
+                    Ok(rule) => {
+                        app.app_config.highlight_rules.push(rule);
+                        app.config1_rule_list_state.select(Some(app.app_config.highlight_rules.len() - 1));
+                        app.config1_rule_input = None;
+                        app.config1_error_message = None;
+                    }
+                    Err(e) => {
+                        app.config1_error_message = Some(e);
+                    }
+                }
+            }
+            KeyCode::Backspace => {
+                rule_input.pop();
+            }
+            KeyCode::Char(c) => {
+                rule_input.push(c);
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    let rule_count = app.app_config.highlight_rules.len();
+    match key.code {
+        KeyCode::Esc | KeyCode::Enter => {
+            app.config1_error_message = None;
+            app.current_screen = CurrentScreen::Main;
+        }
+        KeyCode::Char('k') | KeyCode::Up if rule_count > 0 => {
+            let selected_idx = app.config1_rule_list_state.selected().unwrap_or(0);
+            app.config1_rule_list_state.select(Some(selected_idx.saturating_sub(1)));
+        }
+        KeyCode::Char('j') | KeyCode::Down if rule_count > 0 => {
+            let selected_idx = app.config1_rule_list_state.selected().map(|idx| idx + 1).unwrap_or(0);
+            app.config1_rule_list_state.select(Some(selected_idx.min(rule_count - 1)));
+        }
+        KeyCode::Char('a') => {
+            app.config1_rule_input = Some(String::new());
+        }
+        KeyCode::Char('f') => {
+            app_change_flow_control(app);
+        }
+        KeyCode::Char('p') => {
+            app.app_config.decoder = DecoderKind::next(app.app_config.decoder);
+        }
+        KeyCode::Char('d') | KeyCode::Delete => {
+            if let Some(selected_idx) = app.config1_rule_list_state.selected() {
+                if selected_idx < rule_count {
+                    app.app_config.highlight_rules.remove(selected_idx);
+                    app.config1_rule_list_state.select(match rule_count - 1 {
+                        0 => None,
+                        new_rule_count => Some(selected_idx.min(new_rule_count - 1)),
+                    });
+                }
+            }
+        }
+        KeyCode::Char(' ') => {
+            if let Some(selected_idx) = app.config1_rule_list_state.selected() {
+                if let Some(rule) = app.app_config.highlight_rules.get_mut(selected_idx) {
+                    rule.enabled = !rule.enabled;
+                }
+            }
+        }
+        // TODO: handle changing the rest of the config
+        _ => {}
+    }
+}
+
+fn app_handle_keypresses_for_help_screen(app: &mut App, key: KeyEvent) -> () {
+    match key.code {
+        KeyCode::Esc => {
+            app.current_screen = CurrentScreen::Main;
+        }
+        _ => {
+            app.current_screen = CurrentScreen::Main;
+        }
+    }
+}
+
+/// Handle pasted text (bracketed paste), so that a pasted multi-line block isn't sent line by line as it arrives.
+fn app_handle_paste(app: &mut App, pasted_text: &str) {
+    match (&app.current_screen, &app.main_screen_active_region) {
+        (CurrentScreen::Main, MainScreenActiveRegion::Input) if !is_main_screen_prompt_open(app) => {
+            app.main_input.insert_str(&pasted_text.replace("\r\n", "\n").replace('\r', "\n"));
+        }
+        (CurrentScreen::Main, _) if is_main_screen_prompt_open(app) => {
+            // in a prompt, add the text without the line breaks (and not as keypresses, which could be shortcuts)
+            let pasted_text: String = pasted_text.chars().filter(|c| !c.is_control()).collect();
+            app_insert_into_main_screen_prompt(app, &pasted_text);
+        }
+        _ => {}
+    }
+}
+
+/// Adds text to the end of the open prompt's input, as if it was typed.
+fn app_insert_into_main_screen_prompt(app: &mut App, text: &str) {
+    // same order as the keypresses go to the prompts
+    if app.main_screen_filter.is_editing {
+        app.main_screen_filter.expression.push_str(text);
+        app.main_screen_filter.compile_expression();
+    }
+    else if app.main_screen_search.is_editing {
+        app.main_screen_search.query.push_str(text);
+        app.main_screen_search.compile_query();
+    }
+    else if let Some(search) = &mut app.main_input_send_history_search {
+        search.query.push_str(text);
+        search.update_match(&app.main_input_send_history);
+    }
+    else if let Some(schedule_spec) = &mut app.main_input_schedule_prompt {
+        schedule_spec.push_str(text);
+    }
+    else if let Some(prompt) = &mut app.send_file_prompt {
+        prompt.path.push_str(text);
+    }
+    else if let Some(prompt) = &mut app.receive_file_prompt {
+        prompt.path.push_str(text);
+    }
+    else if let Some(modbus_prompt) = &mut app.modbus_prompt {
+        modbus_prompt.spec.push_str(text);
+    }
+}
+
+/// Whether one of the prompts in the Main screen's footer is open (search, filter, send history search, schedule,
+/// send/receive file, or the Modbus master console).
+fn is_main_screen_prompt_open(app: &App) -> bool {
+    app.main_screen_filter.is_editing || app.main_screen_search.is_editing
+        || app.main_input_send_history_search.is_some() || app.main_input_schedule_prompt.is_some()
+        || app.send_file_prompt.is_some() || app.receive_file_prompt.is_some() || app.modbus_prompt.is_some()
+}
+
+/// Handle mouse events: wheel scrolling, clicking on lists and regions, and selecting text in the transfer log.
+fn app_handle_mouse_event(app: &mut App, mouse: MouseEvent) {
+    let position = (mouse.column, mouse.row);
+    match app.current_screen {
+        CurrentScreen::PickSerialPort => {
+            match mouse.kind {
+                MouseEventKind::ScrollUp => app.pick_serial_port_list_state.previous(),
+                MouseEventKind::ScrollDown => app.pick_serial_port_list_state.next(),
+                MouseEventKind::Down(MouseButton::Left) => {
+                    let offset = app.pick_serial_port_list_state.state.offset();
+                    let item_count = app.pick_serial_port_list_state.items.len();
+                    if let Some(idx) = get_clicked_list_item_idx(app.pick_serial_port_list_rect, offset, item_count, position) {
+                        // clicking on the selected port again opens it (like a double-click)
+                        match app.pick_serial_port_list_state.state.selected() == Some(idx) {
+                            true => app_confirm_selected_serial_port(app),
+                            false => app.pick_serial_port_list_state.state.select(Some(idx)),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        CurrentScreen::PickBaudRate => {
+            let offset = app.pick_baud_rate_list_state.offset();
+            match mouse.kind {
+                MouseEventKind::ScrollUp => {
+                    app_handle_keypresses_for_pick_baud_rate_screen(app, KeyEvent::from(KeyCode::Up));
+                }
+                MouseEventKind::ScrollDown => {
+                    app_handle_keypresses_for_pick_baud_rate_screen(app, KeyEvent::from(KeyCode::Down));
+                }
+                MouseEventKind::Down(MouseButton::Left) => {
+                    if let Some(idx) = get_clicked_list_item_idx(app.pick_baud_rate_list_rect, offset, COMMON_BAUD_RATES.len(), position) {
+                        // clicking on the selected baud rate again confirms it (like a double-click)
+                        match app.pick_baud_rate_list_state.selected() == Some(idx) {
+                            true => app_confirm_baud_rate_input(app),
+                            false => app_select_common_baud_rate(app, idx),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        CurrentScreen::Main => {
+            app_handle_mouse_event_for_main_screen(app, mouse);
+        }
+
+        CurrentScreen::Config1 | CurrentScreen::Help => {}
+    }
+}
+
+fn app_handle_mouse_event_for_main_screen(app: &mut App, mouse: MouseEvent) {
+    let position = (mouse.column, mouse.row);
+    let log_area = app.main_screen_log_layout.area;
+
+    // the snippet picker (a popup) takes all mouse events while it's open
+    if let Some(list_state) = &app.snippet_picker_list_state {
+        match mouse.kind {
+            MouseEventKind::ScrollUp => app_handle_keypresses_for_snippet_picker(app, KeyEvent::from(KeyCode::Up)),
+            MouseEventKind::ScrollDown => app_handle_keypresses_for_snippet_picker(app, KeyEvent::from(KeyCode::Down)),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let offset = list_state.offset();
+                match get_clicked_list_item_idx(app.snippet_picker_list_rect, offset, app.snippets.len(), position) {
+                    Some(snippet_idx) => {
+                        app.snippet_picker_list_state = None;
+                        app_send_snippet(app, snippet_idx);
+                    }
+                    None if !is_position_in_rect(position, app.snippet_picker_list_rect) => {
+                        // clicking outside of the picker closes it
+                        app.snippet_picker_list_state = None;
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match mouse.kind {
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if mouse.modifiers == KeyModifiers::SHIFT => {
+            // Shift+wheel scrolls sideways, for mice without a horizontal wheel
+            app_scroll_transfer_log_horizontally(app, matches!(mouse.kind, MouseEventKind::ScrollDown));
+        }
+        MouseEventKind::ScrollUp => {
+            app.main_screen_vert_scroll_val = app.main_screen_vert_scroll_val.saturating_sub(MOUSE_WHEEL_SCROLL_LINES);
+            app.main_screen_vert_scroll_pos = ScrollPosition::FinitePosition;
+        }
+        MouseEventKind::ScrollDown => {
+            // scrolling past the end pins the view to the end again (see the clamping in the ui)
+            app.main_screen_vert_scroll_val = app.main_screen_vert_scroll_val.saturating_add(MOUSE_WHEEL_SCROLL_LINES);
+            app.main_screen_vert_scroll_pos = ScrollPosition::FinitePosition;
+        }
+        MouseEventKind::ScrollLeft | MouseEventKind::ScrollRight => {
+            app_scroll_transfer_log_horizontally(app, matches!(mouse.kind, MouseEventKind::ScrollRight));
+        }
+
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.main_screen_log_selection = None;
+            if is_position_in_rect(position, app.main_screen_input_rect) {
+                app.main_screen_active_region = MainScreenActiveRegion::Input;
+            }
+            else if is_position_in_rect(position, app.scheduler_panel_rect) {
+                app.main_screen_active_region = MainScreenActiveRegion::SchedulerPanel;
+                let offset = app.scheduler_panel_list_state.offset();
+                if let Some(job_idx) = get_clicked_list_item_idx(app.scheduler_panel_rect, offset, app.tx_scheduler.jobs.len(), position) {
+                    app.scheduler_panel_list_state.select(Some(job_idx));
+                }
+            }
+            else if is_position_in_rect(position, log_area) {
+                app.main_screen_active_region = MainScreenActiveRegion::OutputScrollBars;
+                app.main_screen_log_selection = Some(LogSelection { anchor: position, end: position });
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            if let Some(log_selection) = &mut app.main_screen_log_selection {
+                // keep the end inside the log, even if the mouse leaves it
+                log_selection.end = (
+                    mouse.column.clamp(log_area.left(), log_area.right().saturating_sub(1)),
+                    mouse.row.clamp(log_area.top(), log_area.bottom().saturating_sub(1)),
+                );
+            }
+        }
+        MouseEventKind::Up(MouseButton::Left) => {
+            if let Some(log_selection) = app.main_screen_log_selection.take() {
+                if !log_selection.is_empty() {
+                    let selected_text = log_selection.get_selected_text(
+                        &app.main_screen_log_layout, &app.main_screen_transfer_log, &mut app.main_screen_line_cache);
+                    app_copy_to_clipboard(app, &selected_text);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn app_scroll_transfer_log_horizontally(app: &mut App, is_right: bool) {
+    app.main_screen_horiz_scroll_val = match is_right {
+        true => app.main_screen_horiz_scroll_val.saturating_add(MOUSE_WHEEL_SCROLL_COLUMNS),
+        false => app.main_screen_horiz_scroll_val.saturating_sub(MOUSE_WHEEL_SCROLL_COLUMNS),
+    };
+}
+
+/// Copies the "current" line: the line with the current search match, or else the newest visible line.
+fn app_copy_current_log_line(app: &mut App) {
+    let transfer_log = &app.main_screen_transfer_log;
+    let log_view = &app.main_screen_log_view;
+    let entry_idx = match app.main_screen_search.get_current_match() {
+        Some(current_match) => Some(current_match.entry_idx),
+        None => log_view.get_row_count(transfer_log).checked_sub(1)
+            .and_then(|last_row_idx| log_view.get_entry_idx(transfer_log, last_row_idx)),
+    };
+    match entry_idx.and_then(|entry_idx| transfer_log.get(entry_idx)) {
+        Some(entry) => {
+            let line_text = entry.get_data_as_string();
+            app_copy_to_clipboard(app, &line_text);
+        }
+        None => {
+            app.status_message = Some(String::from("Nothing to copy"));
+        }
+    }
+}
+
+/// Copies the lines shown in the transfer log's view (whole lines, even if scrolled sideways).
+fn app_copy_visible_log_lines(app: &mut App) {
+    let mut entry_indices: Vec<usize> = app.main_screen_log_layout.rows.iter().map(|row| row.entry_idx).collect();
+    entry_indices.dedup(); // wrapped lines take up several rows
+    let page_text = entry_indices.into_iter()
+        .filter_map(|entry_idx| app.main_screen_transfer_log.get(entry_idx))
+        .map(|entry| entry.get_data_as_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+    app_copy_to_clipboard(app, &page_text);
+}
+
+/// Copies every line that passes the filter (including the scrollback that's been spilled to disk),
+/// or as many of the newest ones as fit in the clipboard.
+fn app_copy_filtered_log(app: &mut App) {
+    let transfer_log = &app.main_screen_transfer_log;
+    let log_view = &app.main_screen_log_view;
+    let row_count = log_view.get_row_count(transfer_log);
+    let mut lines: Vec<String> = Vec::new();
+    let mut text_len: usize = 0;
+    for row_idx in (0..row_count).rev() {
+        let Some(entry) = log_view.get_entry_idx(transfer_log, row_idx).and_then(|entry_idx| transfer_log.get(entry_idx)) else { continue };
+        let line = entry.get_data_as_string();
+        if text_len + line.len() + 1 > CLIPBOARD_MAX_BYTES {
+            break;
+        }
+        text_len += line.len() + 1;
+        lines.push(line);
+    }
+    lines.reverse();
+    let copied_line_count = lines.len();
+    app_copy_to_clipboard(app, &lines.join("\n"));
+    if copied_line_count < row_count {
+        if let Some(status_message) = &mut app.status_message {
+            status_message.push_str(&format!(" (only the last {} of {} lines fit in the {} KB limit)",
+                copied_line_count, row_count, CLIPBOARD_MAX_BYTES / 1024));
+        }
+    }
+}
+
+/// Copies `text` to the clipboard, cut to the first `CLIPBOARD_MAX_BYTES` bytes if it's longer.
+fn app_copy_to_clipboard(app: &mut App, text: &str) {
+    let mut copied_len = text.len().min(CLIPBOARD_MAX_BYTES);
+    while !text.is_char_boundary(copied_len) {
+        copied_len -= 1;
+    }
+    let copied_text = &text[..copied_len];
+    app.status_message = Some(match copy_to_clipboard(copied_text) {
+        Ok(()) if copied_len < text.len() => format!("Copied {} lines ({} chars) to the clipboard (cut to the {} KB limit)",
+            copied_text.lines().count(), copied_text.chars().count(), CLIPBOARD_MAX_BYTES / 1024),
+        Ok(()) => format!("Copied {} lines ({} chars) to the clipboard",
+            copied_text.lines().count(), copied_text.chars().count()),
+        Err(e) => format!("Error copying to the clipboard: {}", e),
+    });
+}
+
+/// Index of the list item at `position`, for a List drawn (with borders) in `list_rect`.
+fn get_clicked_list_item_idx(list_rect: Rect, offset: usize, item_count: usize, position: (u16, u16)) -> Option<usize> {
+    let items_rect = list_rect.inner(&Margin { vertical: 1, horizontal: 1 });
+    if !is_position_in_rect(position, items_rect) {
+        return None;
+    }
+    let idx = offset + (position.1 - items_rect.y) as usize;
+    match idx < item_count {
+        true => Some(idx),
+        false => None,
+    }
+}
+
+fn is_position_in_rect(position: (u16, u16), rect: Rect) -> bool {
+    (position.0 >= rect.left()) && (position.0 < rect.right())
+        && (position.1 >= rect.top()) && (position.1 < rect.bottom())
+}
+
+fn app_export_transfer_log(app: &mut App, format: LogExportFormat) {
+    app.status_message = match export_transfer_log(app, format) {
+        Ok(file_path) => Some(format!("Exported log to '{}'", file_path.display())),
+        Err(e) => Some(format!("Error exporting log: {}", e)),
+    };
+}
+
+/// Attempts to transition the app to the main screen by opening the serial port.
+/// If an error occurs, the app will revert back to the serial port selection screen, with an error message.
+fn app_transition_to_main(app: &mut App) -> () {
+    // attempt to open the serial port
+    match (&app.selected_serial_port, app.app_config.baud_rate) {
+        (Some(port_name), Some(baud_rate)) => {
+            match bind_serial_port(port_name, baud_rate, app.app_config.flow_control.get_driver_flow_control()) {
+                Ok(serial_port) => {
+                    app.line_error_decoder = match enable_line_error_marking(&serial_port) {
+                        Ok(()) => {
+                            let is_parity_enabled = serial_port.parity().is_ok_and(|parity| parity != serialport5::Parity::None);
+                            Some(LineErrorDecoder::new(is_parity_enabled))
+                        }
+                        Err(e) => {
+                            app.status_message = Some(e);
+                            None
+                        }
+                    };
+                    app.bound_serial_port = Some(serial_port);
+                    app.modem_lines = ModemLines::new();
+                    app.bus_echo_filter = BusEchoFilter::new();
+                    if let (Some(port), Some(rs485_config)) = (&mut app.bound_serial_port, &app.app_config.rs485) {
+                        if let Err(e) = rs485_config.set_receive_mode(port, &mut app.modem_lines) {
+                            app.status_message = Some(e);
+                        }
+                    }
+                    app.break_end_time = None;
+                    app.reset_runner = None;
+                    app.session_start_time = chrono::Local::now();
+                    app_load_send_history(app);
+                    app_load_snippets(app);
+                    if let Some(reset_on_connect) = app.app_config.reset_on_connect.clone() {
+                        app_start_reset(app, &reset_on_connect);
+                    }
+                    if let Some(send_file_path) = app.send_file_on_start.take() {
+                        app_start_file_send(app, &send_file_path, app.app_config.file_send_mode);
+                    }
+                    app.current_screen = CurrentScreen::Main;
+                }
+                Err(e) => {
+                    app.general_error_message = Some(format!("Error binding serial port: {}", e));
+                    app.current_screen = CurrentScreen::PickSerialPort;
+                }
+            }
+        }
+        (None, _) => {
+            // this should never really happen
+            app.general_error_message = Some(format!("Error: No serial port selected"));
             app.current_screen = CurrentScreen::PickSerialPort;
         }
         (_, None) => {
@@ -642,6 +2210,29 @@ fn app_transition_to_main(app: &mut App) -> () {
     }
 }
 
+/// Loads the saved send history for the bound port (or workspace), if the history is persisted.
+fn app_load_send_history(app: &mut App) {
+    if !app.app_config.send_history_persist {
+        if app.main_input_send_history.is_empty() {
+            app.main_input_send_history = SendHistory::new(app.app_config.send_history_max_entries);
+        }
+        return;
+    }
+    let Some(port_name) = &app.selected_serial_port else { return };
+    let Some(file_path) = get_send_history_file_path(port_name, app.app_config.send_history_per_device) else {
+        return;
+    };
+    match SendHistory::load(file_path, app.app_config.send_history_max_entries) {
+        Ok(send_history) => {
+            app.main_input_send_history = send_history;
+            app.main_input_send_history_index = None;
+        }
+        Err(e) => {
+            app.status_message = Some(format!("{} (send history won't be saved)", e));
+        }
+    }
+}
+
 fn is_keypress_quit_event(key: KeyEvent, is_q_quit: bool) -> bool {
     if key.kind == event::KeyEventKind::Release {
         return false;
@@ -0,0 +1,472 @@
+use std::time::{Duration, Instant};
+
+use crate::protocol_decoder::{DecodedFrame, FrameDirection, FrameValidity, ProtocolDecoder};
+use crate::send_template::get_crc16_modbus;
+use crate::hex::parse_hex_bytes;
+
+/// How long the master waits for a reply to a request from the console.
+pub const MODBUS_REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Above 19200 baud, the silence between frames is fixed (rather than 3.5 characters).
+const MIN_FRAME_SILENCE: Duration = Duration::from_micros(1750);
+
+fn get_function_name(function_code: u8) -> &'static str {
+    match function_code {
+        1 => "Read Coils",
+        2 => "Read Discrete Inputs",
+        3 => "Read Holding Registers",
+        4 => "Read Input Registers",
+        5 => "Write Single Coil",
+        6 => "Write Single Register",
+        15 => "Write Multiple Coils",
+        16 => "Write Multiple Registers",
+        _ => "Unknown Function",
+    }
+}
+
+fn get_exception_name(exception_code: u8) -> &'static str {
+    match exception_code {
+        1 => "Illegal Function",
+        2 => "Illegal Data Address",
+        3 => "Illegal Data Value",
+        4 => "Slave Device Failure",
+        5 => "Acknowledge",
+        6 => "Slave Device Busy",
+        8 => "Memory Parity Error",
+        10 => "Gateway Path Unavailable",
+        11 => "Gateway Target Device Failed to Respond",
+        _ => "Unknown Exception",
+    }
+}
+
+/// The silence that ends a frame: 3.5 characters (of 11 bits) at `baud_rate`.
+pub fn get_frame_silence(baud_rate: u32) -> Duration {
+    Duration::from_secs_f64(3.5 * 11.0 / baud_rate.max(1) as f64).max(MIN_FRAME_SILENCE)
+}
+
+/// Splits received data into frames, at each silence on the line.
+/// The silence can only be seen between reads, and a slow main loop also leaves gaps between reads (with the data
+/// waiting in the OS buffer), so a gap only ends a frame that's whole (its CRC checks out). Otherwise, the frame
+/// ends once the line has been silent and nothing is waiting to be read (see `take_finished_frame`).
+pub struct RtuFramer {
+    frame: Vec<u8>,
+    frame_timestamp: chrono::DateTime<chrono::Local>,
+    last_rxd_time: Option<Instant>,
+}
+
+impl RtuFramer {
+    pub fn new() -> Self {
+        Self {
+            frame: Vec::new(),
+            frame_timestamp: chrono::Local::now(),
+            last_rxd_time: None,
+        }
+    }
+
+    /// Adds data received at `now` to the current frame. Returns the previous frame, if it was whole and there was
+    /// a silence before `data`.
+    pub fn handle_rxd_data(&mut self, data: &[u8], timestamp: chrono::DateTime<chrono::Local>, silence: Duration, now: Instant) -> Option<(Vec<u8>, chrono::DateTime<chrono::Local>)> {
+        let finished_frame = match self.get_time_until_frame_end(silence, now) {
+            Some(time_until_frame_end) if time_until_frame_end.is_zero() && is_crc_valid(&self.frame) => {
+                Some((std::mem::take(&mut self.frame), self.frame_timestamp))
+            }
+            _ => None,
+        };
+        if self.frame.is_empty() {
+            self.frame_timestamp = timestamp;
+        }
+        self.frame.extend_from_slice(data);
+        self.last_rxd_time = Some(now);
+        finished_frame
+    }
+
+    /// Takes the current frame (and when its first byte was read), once the line has been silent for `silence`.
+    /// Only call this when nothing is waiting to be read (the silence might just be the main loop being slow).
+    pub fn take_finished_frame(&mut self, silence: Duration, now: Instant) -> Option<(Vec<u8>, chrono::DateTime<chrono::Local>)> {
+        match self.get_time_until_frame_end(silence, now) {
+            Some(time_until_frame_end) if time_until_frame_end.is_zero() => {
+                Some((std::mem::take(&mut self.frame), self.frame_timestamp))
+            }
+            _ => None,
+        }
+    }
+
+    /// How long until the current frame ends, if nothing else is received (None if there's no frame).
+    pub fn get_time_until_frame_end(&self, silence: Duration, now: Instant) -> Option<Duration> {
+        match (self.frame.is_empty(), self.last_rxd_time) {
+            (false, Some(last_rxd_time)) => Some(silence.saturating_sub(now.saturating_duration_since(last_rxd_time))),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `frame` ends with the CRC of the rest of it.
+fn is_crc_valid(frame: &[u8]) -> bool {
+    if frame.len() < 4 {
+        return false;
+    }
+    let (body, crc) = frame.split_at(frame.len() - 2);
+    get_crc16_modbus(body).to_le_bytes() == crc
+}
+
+/// A request built by the master console (Alt+M).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModbusRequest {
+    pub slave_address: u8,
+    pub function_code: u8,
+    pub start_address: u16,
+    /// Number of coils/registers to read (for reads).
+    pub count: u16,
+    /// Values to write (for writes; coils are 0 or 1).
+    pub values: Vec<u16>,
+}
+
+impl ModbusRequest {
+    /// Parses "<slave> <function> <address> <count | values...>" (e.g., "1 3 0 10", or "1 16 100 1 2 3").
+    /// Numbers can be decimal or hex ("0x64").
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let numbers: Vec<u32> = spec.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .map(|word| match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                Some(hex_word) => u32::from_str_radix(hex_word, 16),
+                None => word.parse::<u32>(),
+            }.map_err(|_| format!("'{}' isn't a number", word)))
+            .collect::<Result<_, _>>()?;
+        let [slave_address, function_code, start_address, rest @ ..] = numbers.as_slice() else {
+            return Err(String::from("Expected <slave> <function> <address> <count | values...>"));
+        };
+        let slave_address = u8::try_from(*slave_address).map_err(|_| format!("Slave address {} is over 255", slave_address))?;
+        let start_address = u16::try_from(*start_address).map_err(|_| format!("Address {} is over 65535", start_address))?;
+        let values: Vec<u16> = rest.iter()
+            .map(|value| u16::try_from(*value).map_err(|_| format!("Value {} is over 65535", value)))
+            .collect::<Result<_, _>>()?;
+        let function_code = u8::try_from(*function_code).map_err(|_| format!("Function {} is over 255", function_code))?;
+        let max_count: usize = match function_code {
+            1 | 2 => 2000,
+            3 | 4 => 125,
+            15 => 1968,
+            _ => 123,
+        };
+        if matches!(function_code, 1..=4) && values.first().is_some_and(|count| *count as usize > max_count)
+                || matches!(function_code, 15 | 16) && values.len() > max_count {
+            return Err(format!("Function {} takes at most {} coils/registers", function_code, max_count));
+        }
+        match (function_code, values.as_slice()) {
+            (1..=4, [count]) => Ok(Self { slave_address, function_code, start_address, count: *count, values: Vec::new() }),
+            (1..=4, _) => Err(String::from("A read takes one count (e.g., '1 3 0 10')")),
+            (5 | 6, [_]) => Ok(Self { slave_address, function_code, start_address, count: 1, values }),
+            (5 | 6, _) => Err(String::from("A single write takes one value (e.g., '1 6 100 1234')")),
+            (15 | 16, [_, ..]) => Ok(Self { slave_address, function_code, start_address, count: values.len() as u16, values }),
+            (15 | 16, []) => Err(String::from("A multiple write takes one or more values (e.g., '1 16 100 1 2 3')")),
+            _ => Err(format!("Function {} isn't supported (1-6, 15, or 16)", function_code)),
+        }
+    }
+
+    /// The request as an RTU frame, with its CRC.
+    pub fn build_frame(&self) -> Vec<u8> {
+        let mut frame: Vec<u8> = vec![self.slave_address, self.function_code];
+        frame.extend(self.start_address.to_be_bytes());
+        match self.function_code {
+            5 => frame.extend(match self.values[0] {
+                0 => [0x00, 0x00],
+                _ => [0xFF, 0x00],
+            }),
+            6 => frame.extend(self.values[0].to_be_bytes()),
+            15 => {
+                let coil_bytes: Vec<u8> = self.values.chunks(8)
+                    .map(|coils| coils.iter().enumerate().fold(0_u8, |byte, (bit_num, coil)| byte | (((*coil != 0) as u8) << bit_num)))
+                    .collect();
+                frame.extend(self.count.to_be_bytes());
+                frame.push(coil_bytes.len() as u8);
+                frame.extend(coil_bytes);
+            }
+            16 => {
+                frame.extend(self.count.to_be_bytes());
+                frame.push((self.values.len() * 2) as u8);
+                frame.extend(self.values.iter().flat_map(|value| value.to_be_bytes()));
+            }
+            _ => frame.extend(self.count.to_be_bytes()),
+        }
+        frame.extend(get_crc16_modbus(&frame).to_le_bytes());
+        frame
+    }
+
+    /// Parses a received frame as the reply to this request.
+    /// Returns None if it isn't one (e.g., another slave's, or the echo of the request), or the reply's text.
+    pub fn parse_reply(&self, frame: &[u8]) -> Option<Result<String, String>> {
+        if frame.len() < 5 || frame[0] != self.slave_address || (frame[1] & 0x7F) != self.function_code || frame == self.build_frame() && !matches!(self.function_code, 5 | 6) {
+            return None;
+        }
+        let (body, crc) = frame.split_at(frame.len() - 2);
+        if get_crc16_modbus(body).to_le_bytes() != crc {
+            return Some(Err(String::from("Reply with a bad CRC")));
+        }
+        if frame[1] & 0x80 != 0 {
+            return Some(Err(format!("Exception {}: {}", frame[2], get_exception_name(frame[2]))));
+        }
+        match self.function_code {
+            1 | 2 => {
+                let coils: Vec<String> = (0..self.count as usize)
+                    .filter_map(|coil_num| body.get(3 + coil_num / 8).map(|byte| (coil_num, (byte >> (coil_num % 8)) & 1)))
+                    .map(|(coil_num, coil)| format!("{}={}", self.start_address as usize + coil_num, coil))
+                    .collect();
+                Some(Ok(coils.join(" ")))
+            }
+            3 | 4 => {
+                let registers: Vec<String> = body.get(3..)?.chunks_exact(2).enumerate()
+                    .map(|(register_num, bytes)| format!("{}={}", self.start_address as usize + register_num, u16::from_be_bytes([bytes[0], bytes[1]])))
+                    .collect();
+                Some(Ok(registers.join(" ")))
+            }
+            _ => Some(Ok(format!("Wrote {} at {}", match self.count { 1 => String::from("1 value"), count => format!("{} values", count) }, self.start_address))),
+        }
+    }
+}
+
+/// State of the Modbus master console (Alt+M).
+pub struct ModbusPromptState {
+    pub spec: String,
+
+    /// The request that was sent, and when, until its reply comes (or it times out).
+    pub pending_request: Option<(ModbusRequest, Instant)>,
+    /// The last reply (or error), shown under the prompt.
+    pub last_reply: Option<Result<String, String>>,
+}
+
+/// Decodes Modbus RTU frames: each transfer log entry is a frame (split by `RtuFramer`). Sent frames are
+/// decoded from their echo, which is hex text.
+pub struct ModbusRtuDecoder;
+
+impl ProtocolDecoder for ModbusRtuDecoder {
+    fn decode(&self, data: &[u8], direction: FrameDirection) -> Option<DecodedFrame> {
+        let frame = match direction {
+            FrameDirection::Rx => data.to_vec(),
+            FrameDirection::Tx => parse_hex_bytes(std::str::from_utf8(data).ok()?).ok()?,
+        };
+        if frame.len() < 5 {
+            return None;
+        }
+        let (body, crc) = frame.split_at(frame.len() - 2);
+        let expected_crc = get_crc16_modbus(body);
+        let validity = match u16::from_le_bytes([crc[0], crc[1]]) == expected_crc {
+            true => FrameValidity::Valid,
+            false => FrameValidity::Invalid(format!("CRC {:02X} {:02X}, expected {:02X} {:02X}",
+                crc[0], crc[1], expected_crc.to_le_bytes()[0], expected_crc.to_le_bytes()[1])),
+        };
+
+        let function_code = body[1] & 0x7F;
+        let mut fields: Vec<(String, String)> = vec![(String::from("slave"), body[0].to_string())];
+        let get_u16 = |idx: usize| -> Option<u16> { Some(u16::from_be_bytes([*body.get(idx)?, *body.get(idx + 1)?])) };
+        let kind = match (body[1] & 0x80 != 0, function_code, body.len()) {
+            (true, _, _) => {
+                let exception_code = body[2];
+                fields.push((String::from("exception"), format!("{} {}", exception_code, get_exception_name(exception_code))));
+                "exception"
+            }
+            // requests and replies are told apart by their length (or by which way they went, when that's the same)
+            (false, 1..=4, len) if len == 3 + body[2] as usize && (len != 6 || direction == FrameDirection::Rx) => {
+                fields.push((String::from("bytes"), body[2].to_string()));
+                let values: Vec<String> = match function_code {
+                    1 | 2 => body[3..].iter().map(|byte| format!("{:08b}", byte.reverse_bits())).collect(),
+                    _ => body[3..].chunks_exact(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]).to_string()).collect(),
+                };
+                fields.push((String::from(if function_code <= 2 { "bits" } else { "regs" }), values.join(" ")));
+                "reply"
+            }
+            (false, 1..=4, 6) => {
+                fields.push((String::from("addr"), get_u16(2)?.to_string()));
+                fields.push((String::from("count"), get_u16(4)?.to_string()));
+                "request"
+            }
+            (false, 5 | 6, 6) => {
+                fields.push((String::from("addr"), get_u16(2)?.to_string()));
+                fields.push((String::from("value"), match function_code {
+                    5 => String::from(if get_u16(4)? == 0xFF00 { "on" } else { "off" }),
+                    _ => get_u16(4)?.to_string(),
+                }));
+                "request/reply"
+            }
+            (false, 15 | 16, len) if len > 7 && len == 7 + body[6] as usize => {
+                fields.push((String::from("addr"), get_u16(2)?.to_string()));
+                fields.push((String::from("count"), get_u16(4)?.to_string()));
+                let values: Vec<String> = match function_code {
+                    15 => body[7..].iter().map(|byte| format!("{:08b}", byte.reverse_bits())).collect(),
+                    _ => body[7..].chunks_exact(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]).to_string()).collect(),
+                };
+                fields.push((String::from(if function_code == 15 { "bits" } else { "regs" }), values.join(" ")));
+                "request"
+            }
+            (false, 15 | 16, 6) => {
+                fields.push((String::from("addr"), get_u16(2)?.to_string()));
+                fields.push((String::from("count"), get_u16(4)?.to_string()));
+                "reply"
+            }
+            _ => {
+                fields.push((String::from("data"), body[2..].iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")));
+                "frame"
+            }
+        };
+
+        Some(DecodedFrame {
+            summary: format!("{:02} {} ({})", function_code, get_function_name(function_code), kind),
+            fields,
+            validity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SILENCE: Duration = Duration::from_millis(4);
+
+    fn get_request(spec: &str) -> ModbusRequest {
+        ModbusRequest::from_spec(spec).unwrap()
+    }
+
+    fn with_crc(body: &[u8]) -> Vec<u8> {
+        let mut frame = body.to_vec();
+        frame.extend(get_crc16_modbus(body).to_le_bytes());
+        frame
+    }
+
+    /// A slave with 100 holding registers (register n holds n * 10), that answers like a real one would.
+    fn get_slave_reply(request_frame: &[u8]) -> Vec<u8> {
+        assert!(is_crc_valid(request_frame));
+        let get_u16 = |idx: usize| u16::from_be_bytes([request_frame[idx], request_frame[idx + 1]]);
+        let (slave_address, function_code, start_address) = (request_frame[0], request_frame[1], get_u16(2));
+        match function_code {
+            3 => {
+                let count = get_u16(4);
+                if start_address as usize + count as usize > 100 {
+                    return with_crc(&[slave_address, function_code | 0x80, 2]);
+                }
+                let mut body = vec![slave_address, function_code, (count * 2) as u8];
+                body.extend((start_address..start_address + count).flat_map(|register| (register * 10).to_be_bytes()));
+                with_crc(&body)
+            }
+            6 => request_frame.to_vec(),
+            _ => with_crc(&[slave_address, function_code | 0x80, 1]),
+        }
+    }
+
+    #[test]
+    fn frames_match_known_vectors() {
+        assert_eq!(get_request("1 3 0 10").build_frame(), [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]);
+        assert_eq!(get_request("1 6 1 3").build_frame(), [0x01, 0x06, 0x00, 0x01, 0x00, 0x03, 0x98, 0x0B]);
+        assert_eq!(get_request("1 5 10 1").build_frame(), [0x01, 0x05, 0x00, 0x0A, 0xFF, 0x00, 0xAC, 0x38]);
+        // the example from the Modbus spec: coils 20 to 29
+        assert_eq!(get_request("1 15 19 1 0 1 1 0 0 1 1 1 0").build_frame(),
+            [0x01, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01, 0x72, 0xCB]);
+        assert_eq!(get_request("1 0x10 0x64 1 2").build_frame(),
+            [0x01, 0x10, 0x00, 0x64, 0x00, 0x02, 0x04, 0x00, 0x01, 0x00, 0x02, 0x24, 0x75]);
+    }
+
+    #[test]
+    fn specs_parse_into_requests() {
+        assert_eq!(get_request("1 3 0 10"), ModbusRequest { slave_address: 1, function_code: 3, start_address: 0, count: 10, values: vec![] });
+        assert_eq!(get_request("247, 16, 0x100, 1, 2, 3"),
+            ModbusRequest { slave_address: 247, function_code: 16, start_address: 256, count: 3, values: vec![1, 2, 3] });
+
+        for invalid_spec in ["", "1 3", "1 3 0", "1 3 0 10 11", "1 3 0 126", "1 6 100", "1 6 100 1 2", "1 16 100", "256 3 0 1",
+                "1 3 65536 1", "1 6 0 65536", "1 7 0 1", "1 3 zero 1"] {
+            assert!(ModbusRequest::from_spec(invalid_spec).is_err(), "'{}'", invalid_spec);
+        }
+    }
+
+    #[test]
+    fn replies_are_parsed() {
+        let request = get_request("1 3 0 2");
+        assert_eq!(request.parse_reply(&with_crc(&[0x01, 0x03, 0x04, 0x00, 0x01, 0x00, 0x0A])), Some(Ok(String::from("0=1 1=10"))));
+        assert_eq!(request.parse_reply(&with_crc(&[0x01, 0x83, 0x02])), Some(Err(String::from("Exception 2: Illegal Data Address"))));
+        assert_eq!(request.parse_reply(&[0x01, 0x03, 0x04, 0x00, 0x01, 0x00, 0x0A, 0x00, 0x00]), Some(Err(String::from("Reply with a bad CRC"))));
+        // another slave's reply, and the echo of the request
+        assert_eq!(request.parse_reply(&with_crc(&[0x02, 0x03, 0x02, 0x00, 0x01])), None);
+        assert_eq!(request.parse_reply(&request.build_frame()), None);
+
+        let request = get_request("1 1 10 10");
+        assert_eq!(request.parse_reply(&with_crc(&[0x01, 0x01, 0x02, 0x05, 0x02])),
+            Some(Ok(String::from("10=1 11=0 12=1 13=0 14=0 15=0 16=0 17=0 18=0 19=1"))));
+
+        // a single write's reply is the same as the request
+        let request = get_request("1 6 100 1234");
+        assert_eq!(request.parse_reply(&request.build_frame()), Some(Ok(String::from("Wrote 1 value at 100"))));
+        let request = get_request("1 16 100 1 2");
+        assert_eq!(request.parse_reply(&with_crc(&[0x01, 0x10, 0x00, 0x64, 0x00, 0x02])), Some(Ok(String::from("Wrote 2 values at 100"))));
+    }
+
+    #[test]
+    fn framer_splits_at_silences() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let timestamp = chrono::Local::now();
+        let mut framer = RtuFramer::new();
+        let request_frame = get_request("1 3 0 10").build_frame();
+
+        assert_eq!(framer.handle_rxd_data(&request_frame[..3], timestamp, SILENCE, at(0)), None);
+        assert_eq!(framer.handle_rxd_data(&request_frame[3..], timestamp, SILENCE, at(1)), None);
+        assert_eq!(framer.get_time_until_frame_end(SILENCE, at(2)), Some(Duration::from_millis(3)));
+        assert_eq!(framer.take_finished_frame(SILENCE, at(5)), Some((request_frame.clone(), timestamp)));
+        assert_eq!(framer.get_time_until_frame_end(SILENCE, at(5)), None);
+
+        // a whole frame, then more data after a silence (the idle path didn't run in between)
+        assert_eq!(framer.handle_rxd_data(&request_frame, timestamp, SILENCE, at(10)), None);
+        assert_eq!(framer.handle_rxd_data(&[0x01], timestamp, SILENCE, at(20)), Some((request_frame, timestamp)));
+    }
+
+    #[test]
+    fn framer_doesnt_split_a_frame_between_slow_reads() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let timestamp = chrono::Local::now();
+        let mut framer = RtuFramer::new();
+        let reply_frame = get_slave_reply(&get_request("1 3 0 10").build_frame());
+
+        // each read comes well after the silence (the bytes waited in the OS buffer), but the frame isn't whole yet
+        for (read_num, chunk) in reply_frame.chunks(5).enumerate() {
+            assert_eq!(framer.handle_rxd_data(chunk, timestamp, SILENCE, at(read_num as u64 * 20)), None);
+        }
+        assert_eq!(framer.take_finished_frame(SILENCE, at(1000)), Some((reply_frame, timestamp)));
+    }
+
+    #[test]
+    fn master_and_simulated_slave() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let timestamp = chrono::Local::now();
+        let decoder = ModbusRtuDecoder;
+
+        for (spec, expected_reply, expected_summary) in [
+            ("1 3 5 3", Ok(String::from("5=50 6=60 7=70")), "03 Read Holding Registers (reply)"),
+            ("1 6 5 1234", Ok(String::from("Wrote 1 value at 5")), "06 Write Single Register (request/reply)"),
+            ("1 3 99 2", Err(String::from("Exception 2: Illegal Data Address")), "03 Read Holding Registers (exception)"),
+            ("1 4 0 1", Err(String::from("Exception 1: Illegal Function")), "04 Read Input Registers (exception)"),
+        ] {
+            let request = get_request(spec);
+            let request_frame = request.build_frame();
+            let request_decoded = decoder.decode(&request_frame, FrameDirection::Rx).unwrap();
+            assert_eq!(request_decoded.validity, FrameValidity::Valid, "{}", spec);
+
+            // the reply comes back a few bytes at a time, like it would from the port
+            let mut framer = RtuFramer::new();
+            for (read_num, chunk) in get_slave_reply(&request_frame).chunks(3).enumerate() {
+                assert_eq!(framer.handle_rxd_data(chunk, timestamp, SILENCE, at(read_num as u64)), None, "{}", spec);
+            }
+            let (reply_frame, _) = framer.take_finished_frame(SILENCE, at(100)).unwrap();
+            assert_eq!(request.parse_reply(&reply_frame), Some(expected_reply), "{}", spec);
+            let reply_decoded = decoder.decode(&reply_frame, FrameDirection::Rx).unwrap();
+            assert_eq!(reply_decoded.summary, expected_summary);
+            assert_eq!(reply_decoded.validity, FrameValidity::Valid, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn decoder_reports_a_bad_crc() {
+        let mut frame = get_request("1 3 0 10").build_frame();
+        frame[7] ^= 0xFF;
+        let decoded = ModbusRtuDecoder.decode(&frame, FrameDirection::Rx).unwrap();
+        assert_eq!(decoded.validity, FrameValidity::Invalid(String::from("CRC C5 32, expected C5 CD")));
+        assert_eq!(decoded.summary, "03 Read Holding Registers (request)");
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use serialport5::SerialPort;
+
+/// How often the modem control inputs are read.
+const MODEM_LINE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The modem control inputs, driven by the other side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModemInputLines {
+    pub cts: bool,
+    pub dsr: bool,
+    pub ri: bool,
+    pub cd: bool,
+}
+
+impl ModemInputLines {
+    pub fn read(port: &mut SerialPort) -> Result<Self, String> {
+        let map_err = |e: serialport5::Error| format!("Error reading the modem control lines: {}", e);
+        Ok(Self {
+            cts: port.read_clear_to_send().map_err(map_err)?,
+            dsr: port.read_data_set_ready().map_err(map_err)?,
+            ri: port.read_ring_indicator().map_err(map_err)?,
+            cd: port.read_carrier_detect().map_err(map_err)?,
+        })
+    }
+
+    /// Each line's name and state, in the order they're shown.
+    pub fn get_named_states(&self) -> [(&'static str, bool); 4] {
+        [("CTS", self.cts), ("DSR", self.dsr), ("RI", self.ri), ("CD", self.cd)]
+    }
+
+    /// The lines that changed since `previous`, e.g. "CTS on" or "CD off".
+    pub fn get_changes(&self, previous: &Self) -> Vec<String> {
+        self.get_named_states().iter().zip(previous.get_named_states())
+            .filter(|((_, state), (_, previous_state))| state != previous_state)
+            .map(|((name, state), _)| format!("{} {}", name, if *state { "on" } else { "off" }))
+            .collect()
+    }
+}
+
+/// The modem control outputs, driven by us.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModemOutputLine {
+    Dtr,
+    Rts,
+}
+
+impl ModemOutputLine {
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            ModemOutputLine::Dtr => "DTR",
+            ModemOutputLine::Rts => "RTS",
+        }
+    }
+}
+
+/// State of the modem control lines, shown in the status strip on the Main screen.
+pub struct ModemLines {
+    /// None until they're first read, or if the port can't read them (e.g., some USB adapters and PTYs).
+    pub inputs: Option<ModemInputLines>,
+    pub is_unsupported: bool,
+
+    /// None until they're set (the OS usually turns both on when the port is opened).
+    pub dtr: Option<bool>,
+    pub rts: Option<bool>,
+
+    last_poll_time: Option<Instant>,
+}
+
+impl ModemLines {
+    pub fn new() -> Self {
+        Self {
+            inputs: None,
+            is_unsupported: false,
+            dtr: None,
+            rts: None,
+            last_poll_time: None,
+        }
+    }
+
+    /// Whether it's time to read the inputs again; if so, the next poll is due an interval from now.
+    pub fn take_poll_due(&mut self) -> bool {
+        if self.is_unsupported || self.last_poll_time.is_some_and(|time| time.elapsed() < MODEM_LINE_POLL_INTERVAL) {
+            return false;
+        }
+        self.last_poll_time = Some(Instant::now());
+        true
+    }
+
+    pub fn get_output(&self, line: ModemOutputLine) -> Option<bool> {
+        match line {
+            ModemOutputLine::Dtr => self.dtr,
+            ModemOutputLine::Rts => self.rts,
+        }
+    }
+
+    /// Sets an output line on the port, and remembers its state.
+    pub fn set_output(&mut self, port: &mut SerialPort, line: ModemOutputLine, level: bool) -> Result<(), String> {
+        let result = match line {
+            ModemOutputLine::Dtr => port.write_data_terminal_ready(level),
+            ModemOutputLine::Rts => port.write_request_to_send(level),
+        };
+        result.map_err(|e| format!("Error setting {}: {}", line.get_display_name(), e))?;
+        match line {
+            ModemOutputLine::Dtr => self.dtr = Some(level),
+            ModemOutputLine::Rts => self.rts = Some(level),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::protocol_decoder::{DecodedFrame, FrameDirection, FrameValidity, ProtocolDecoder};
+
+/// Decodes NMEA 0183 sentences (e.g., "$GPGGA,123519,4807.038,N,...*47").
+pub struct NmeaDecoder;
+
+impl ProtocolDecoder for NmeaDecoder {
+    fn decode(&self, data: &[u8], _direction: FrameDirection) -> Option<DecodedFrame> {
+        let sentence = std::str::from_utf8(data).ok()?.trim_end_matches(['\r', '\n']);
+        let body = sentence.strip_prefix(['$', '!'])?;
+        let (body, checksum) = match body.rsplit_once('*') {
+            Some((body, checksum)) => (body, Some(checksum)),
+            None => (body, None),
+        };
+        let mut parts = body.split(',');
+        let address = parts.next().filter(|address| address.len() >= 3 && address.bytes().all(|byte| byte.is_ascii_alphanumeric()))?;
+
+        let validity = match checksum {
+            Some(checksum) => {
+                let expected_checksum = body.bytes().fold(0_u8, |checksum, byte| checksum ^ byte);
+                match u8::from_str_radix(checksum, 16) {
+                    Ok(checksum) if checksum == expected_checksum => FrameValidity::Valid,
+                    _ => FrameValidity::Invalid(format!("checksum '{}', expected {:02X}", checksum, expected_checksum)),
+                }
+            }
+            None => FrameValidity::Unchecked,
+        };
+
+        // proprietary sentences ("P" + maker) don't have a talker ID; the field names are the standard ones'
+        let sentence_type = match address.starts_with('P') {
+            true => "",
+            false => &address[2..],
+        };
+        let field_names = get_field_names(sentence_type);
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for (field_num, value) in parts.enumerate() {
+            match field_names.get(field_num) {
+                // a unit or hemisphere goes with the value before it (e.g., "lat=4807.038 N")
+                Some(&"+") => {
+                    if let Some((_, previous_value)) = fields.last_mut().filter(|_| !value.is_empty()) {
+                        previous_value.push(' ');
+                        previous_value.push_str(value);
+                    }
+                }
+                _ if value.is_empty() => {}
+                Some(name) => fields.push((name.to_string(), value.to_string())),
+                None => fields.push((format!("f{}", field_num + 1), value.to_string())),
+            }
+        }
+
+        Some(DecodedFrame {
+            summary: address.to_string(),
+            fields,
+            validity,
+        })
+    }
+}
+
+/// Names of the fields after the address, for the common sentences ("+" goes with the field before it).
+fn get_field_names(sentence_type: &str) -> &'static [&'static str] {
+    match sentence_type {
+        "GGA" => &["time", "lat", "+", "lon", "+", "fix", "sats", "hdop", "alt", "+", "geoid_sep", "+", "dgps_age", "dgps_station"],
+        "RMC" => &["time", "status", "lat", "+", "lon", "+", "speed_kn", "course", "date", "mag_var", "+", "mode"],
+        "GLL" => &["lat", "+", "lon", "+", "time", "status", "mode"],
+        "VTG" => &["course_true", "+", "course_mag", "+", "speed_kn", "+", "speed_kmh", "+", "mode"],
+        "GSA" => &["mode", "fix_type", "sv", "sv", "sv", "sv", "sv", "sv", "sv", "sv", "sv", "sv", "sv", "sv", "pdop", "hdop", "vdop"],
+        "GSV" => &["msg_count", "msg_num", "sats_in_view"],
+        "ZDA" => &["time", "day", "month", "year", "tz_hours", "tz_minutes"],
+        _ => &[],
+    }
+}
@@ -0,0 +1,134 @@
+use crate::app::{TransferLogEntry, TransferLogType};
+use crate::modbus::ModbusRtuDecoder;
+use crate::nmea::NmeaDecoder;
+
+/// Which way a frame went.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameDirection {
+    Rx,
+    Tx,
+}
+
+/// Whether a frame's checksum (or CRC) matched.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameValidity {
+    Valid,
+    /// E.g., "checksum 0x47, expected 0x48".
+    Invalid(String),
+    /// The frame doesn't have a checksum.
+    Unchecked,
+}
+
+/// A frame, decoded into named fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedFrame {
+    /// Short description (e.g., "GPGGA"), shown first.
+    pub summary: String,
+    /// Name and value of each field, in the order they're in the frame.
+    pub fields: Vec<(String, String)>,
+    pub validity: FrameValidity,
+}
+
+impl DecodedFrame {
+    /// The frame on one line, for showing it in place of the raw bytes (e.g., "GPGGA time=123519 lat=4807.038 N").
+    pub fn get_inline_text(&self) -> String {
+        let mut text = self.summary.clone();
+        for (name, value) in self.fields.iter() {
+            text.push_str(&format!(" {}={}", name, value));
+        }
+        text
+    }
+}
+
+/// Decodes the frames of a protocol. Each transfer log entry (a line, or a frame for binary protocols) is a frame.
+pub trait ProtocolDecoder {
+    /// Decodes a frame. None if `data` isn't a frame of this protocol (so it's shown as it is).
+    fn decode(&self, data: &[u8], direction: FrameDirection) -> Option<DecodedFrame>;
+}
+
+/// The protocol decoders there are, for picking one for the session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecoderKind {
+    /// NMEA 0183 sentences (GPS receivers, marine electronics).
+    Nmea,
+    /// Modbus RTU frames, split by the silence between them (rather than by line).
+    ModbusRtu,
+}
+
+static NMEA_DECODER: NmeaDecoder = NmeaDecoder;
+static MODBUS_RTU_DECODER: ModbusRtuDecoder = ModbusRtuDecoder;
+
+impl DecoderKind {
+    pub fn from_config_str(s: &str) -> Result<Self, String> {
+        match s {
+            "nmea" => Ok(DecoderKind::Nmea),
+            "modbus_rtu" => Ok(DecoderKind::ModbusRtu),
+            _ => Err(format!("Invalid decoder '{}' (expected \"nmea\" or \"modbus_rtu\")", s)),
+        }
+    }
+
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            DecoderKind::Nmea => "NMEA 0183",
+            DecoderKind::ModbusRtu => "Modbus RTU",
+        }
+    }
+
+    pub fn get_decoder(&self) -> &'static dyn ProtocolDecoder {
+        match self {
+            DecoderKind::Nmea => &NMEA_DECODER,
+            DecoderKind::ModbusRtu => &MODBUS_RTU_DECODER,
+        }
+    }
+
+    /// The next decoder to pick (None being no decoder).
+    pub fn next(decoder_kind: Option<Self>) -> Option<Self> {
+        match decoder_kind {
+            None => Some(DecoderKind::Nmea),
+            Some(DecoderKind::Nmea) => Some(DecoderKind::ModbusRtu),
+            Some(DecoderKind::ModbusRtu) => None,
+        }
+    }
+}
+
+/// Decodes a transfer log entry with `decoder_kind`: received data, and echoes of sent data (once they're complete).
+pub fn decode_log_entry(decoder_kind: Option<DecoderKind>, entry: &TransferLogEntry) -> Option<DecodedFrame> {
+    let direction = match entry.log_type {
+        TransferLogType::SerialData => FrameDirection::Rx,
+        TransferLogType::EchoData => FrameDirection::Tx,
+        _ => return None,
+    };
+    match (decoder_kind, entry.is_final) {
+        (Some(decoder_kind), true) => decoder_kind.get_decoder().decode(&entry.data_as_bytes, direction),
+        _ => None,
+    }
+}
+
+/// How decoded frames are shown on the Main screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodedView {
+    /// Just the raw data.
+    Off,
+    /// Each decoded frame in place of its raw data.
+    Inline,
+    /// The raw data, with the fields of the last decoded frame on screen in a pane beside it.
+    DetailPane,
+}
+
+impl DecodedView {
+    pub fn next(&self) -> Self {
+        match self {
+            DecodedView::Off => DecodedView::Inline,
+            DecodedView::Inline => DecodedView::DetailPane,
+            DecodedView::DetailPane => DecodedView::Off,
+        }
+    }
+
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            DecodedView::Off => "Off",
+            DecodedView::Inline => "Inline",
+            DecodedView::DetailPane => "Pane",
+        }
+    }
+}
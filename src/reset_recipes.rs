@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+use crate::modem_lines::ModemOutputLine;
+
+/// One step of a reset recipe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResetStep {
+    Set(ModemOutputLine, bool),
+    Wait(Duration),
+}
+
+impl ResetStep {
+    /// Parses a step as written in the config file: "dtr on", "rts off", or "wait 100" (milliseconds).
+    pub fn from_config_str(s: &str) -> Result<Self, String> {
+        let words: Vec<String> = s.split_whitespace().map(|word| word.to_lowercase()).collect();
+        let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+        match words.as_slice() {
+            [line @ ("dtr" | "rts"), state @ ("on" | "off")] => {
+                let line = match *line {
+                    "dtr" => ModemOutputLine::Dtr,
+                    _ => ModemOutputLine::Rts,
+                };
+                Ok(ResetStep::Set(line, *state == "on"))
+            }
+            ["wait", wait_ms] => match wait_ms.parse::<u64>() {
+                Ok(wait_ms) => Ok(ResetStep::Wait(Duration::from_millis(wait_ms))),
+                Err(_) => Err(format!("Invalid wait '{}' in reset step '{}' (expected milliseconds)", wait_ms, s)),
+            },
+            _ => Err(format!("Invalid reset step '{}' (expected \"dtr on\", \"rts off\", \"wait 100\", ...)", s)),
+        }
+    }
+}
+
+/// A named sequence of DTR/RTS changes that resets a board (or puts it in its bootloader).
+#[derive(Debug, Clone)]
+pub struct ResetRecipe {
+    pub name: String,
+    pub steps: Vec<ResetStep>,
+
+    /// Runs the recipe with Alt+<key> on the Main screen ('4' to '9').
+    pub alt_key: Option<char>,
+}
+
+impl ResetRecipe {
+    /// Parses the key as written in the config file: "alt+4" to "alt+9".
+    pub fn parse_alt_key(s: &str) -> Result<char, String> {
+        match s.to_lowercase().strip_prefix("alt+") {
+            Some(key @ ("4" | "5" | "6" | "7" | "8" | "9")) => Ok(key.chars().next().expect("Key is one character")),
+            _ => Err(format!("Invalid reset recipe key '{}' (expected \"alt+4\" to \"alt+9\")", s)),
+        }
+    }
+}
+
+/// The recipes that are always available. A recipe in the config file with the same name replaces one of these.
+pub fn get_builtin_reset_recipes() -> Vec<ResetRecipe> {
+    use ModemOutputLine::{Dtr, Rts};
+    use ResetStep::{Set, Wait};
+    vec![
+        // Arduino (ATmega bootloaders): the board resets on the edge of DTR, through a capacitor
+        ResetRecipe {
+            name: String::from("arduino"),
+            steps: vec![Set(Dtr, false), Set(Rts, false), Wait(Duration::from_millis(250)), Set(Dtr, true), Set(Rts, true), Wait(Duration::from_millis(50))],
+            alt_key: Some('4'),
+        },
+        // ESP32/ESP8266 (esptool's "hard reset"): RTS pulls EN low
+        ResetRecipe {
+            name: String::from("esp32_hard_reset"),
+            steps: vec![Set(Rts, true), Wait(Duration::from_millis(100)), Set(Rts, false)],
+            alt_key: Some('5'),
+        },
+        // ESP32/ESP8266 (esptool's classic reset into the bootloader): EN low, then IO0 (DTR) low while EN comes back up
+        ResetRecipe {
+            name: String::from("esp32_bootloader"),
+            steps: vec![
+                Set(Dtr, false), Set(Rts, true), Wait(Duration::from_millis(100)),
+                Set(Dtr, true), Set(Rts, false), Wait(Duration::from_millis(50)),
+                Set(Dtr, false),
+            ],
+            alt_key: Some('6'),
+        },
+    ]
+}
+
+/// Runs a recipe's steps at the right times. It doesn't set the lines itself: the changes that are due are
+/// returned, so that the timing doesn't depend on the port (or on there being one).
+pub struct ResetRunner {
+    pub recipe_name: String,
+    steps: Vec<ResetStep>,
+    next_step_idx: usize,
+
+    /// When the next step is due (None until the first one runs).
+    next_step_time: Option<Instant>,
+}
+
+impl ResetRunner {
+    pub fn new(recipe: &ResetRecipe) -> Self {
+        Self {
+            recipe_name: recipe.name.clone(),
+            steps: recipe.steps.clone(),
+            next_step_idx: 0,
+            next_step_time: None,
+        }
+    }
+
+    /// Runs the steps that are due at `now`, and returns the line changes to make (in order).
+    /// Waits are counted from when the previous wait ended (not from `now`), so that a late call doesn't stretch
+    /// the rest of the recipe.
+    pub fn take_due_line_changes(&mut self, now: Instant) -> Vec<(ModemOutputLine, bool)> {
+        let mut line_changes: Vec<(ModemOutputLine, bool)> = Vec::new();
+        while let Some(step) = self.steps.get(self.next_step_idx) {
+            let step_time = *self.next_step_time.get_or_insert(now);
+            if step_time > now {
+                break;
+            }
+            match step {
+                ResetStep::Set(line, level) => line_changes.push((*line, *level)),
+                ResetStep::Wait(duration) => self.next_step_time = Some(step_time + *duration),
+            }
+            self.next_step_idx += 1;
+        }
+        line_changes
+    }
+
+    /// Whether all the steps have run (including a wait at the end, e.g. for the board to start up).
+    pub fn is_done(&self, now: Instant) -> bool {
+        self.next_step_idx >= self.steps.len() && !matches!(self.next_step_time, Some(next_step_time) if next_step_time > now)
+    }
+
+    pub fn get_time_until_next_step(&self, now: Instant) -> Option<Duration> {
+        match (self.is_done(now), self.next_step_time) {
+            (true, _) => None,
+            (false, Some(next_step_time)) => Some(next_step_time.saturating_duration_since(now)),
+            (false, None) => Some(Duration::ZERO),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ModemOutputLine::{Dtr, Rts};
+
+    fn get_builtin_runner(name: &str) -> ResetRunner {
+        let recipe = get_builtin_reset_recipes().into_iter().find(|recipe| recipe.name == name).unwrap();
+        ResetRunner::new(&recipe)
+    }
+
+    #[test]
+    fn esp32_bootloader_steps() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut runner = get_builtin_runner("esp32_bootloader");
+
+        assert_eq!(runner.get_time_until_next_step(start), Some(Duration::ZERO));
+        assert_eq!(runner.take_due_line_changes(start), vec![(Dtr, false), (Rts, true)]);
+        assert_eq!(runner.get_time_until_next_step(at(40)), Some(Duration::from_millis(60)));
+        assert_eq!(runner.take_due_line_changes(at(99)), vec![]);
+        assert!(!runner.is_done(at(99)));
+        assert_eq!(runner.take_due_line_changes(at(100)), vec![(Dtr, true), (Rts, false)]);
+        assert_eq!(runner.take_due_line_changes(at(149)), vec![]);
+        assert_eq!(runner.take_due_line_changes(at(150)), vec![(Dtr, false)]);
+        assert!(runner.is_done(at(150)));
+        assert_eq!(runner.get_time_until_next_step(at(150)), None);
+    }
+
+    #[test]
+    fn arduino_steps_wait_at_the_end() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut runner = get_builtin_runner("arduino");
+
+        assert_eq!(runner.take_due_line_changes(start), vec![(Dtr, false), (Rts, false)]);
+        assert_eq!(runner.take_due_line_changes(at(250)), vec![(Dtr, true), (Rts, true)]);
+        // the last wait (for the bootloader to start) still counts
+        assert!(!runner.is_done(at(299)));
+        assert_eq!(runner.get_time_until_next_step(at(299)), Some(Duration::from_millis(1)));
+        assert_eq!(runner.take_due_line_changes(at(300)), vec![]);
+        assert!(runner.is_done(at(300)));
+    }
+
+    #[test]
+    fn late_calls_dont_stretch_the_recipe() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut runner = get_builtin_runner("esp32_bootloader");
+
+        assert_eq!(runner.take_due_line_changes(start).len(), 2);
+        // a slow main loop: both waits have passed by now
+        assert_eq!(runner.take_due_line_changes(at(500)), vec![(Dtr, true), (Rts, false), (Dtr, false)]);
+        assert!(runner.is_done(at(500)));
+    }
+
+    #[test]
+    fn steps_from_config_strings() {
+        assert_eq!(ResetStep::from_config_str("dtr on"), Ok(ResetStep::Set(Dtr, true)));
+        assert_eq!(ResetStep::from_config_str("  RTS   Off "), Ok(ResetStep::Set(Rts, false)));
+        assert_eq!(ResetStep::from_config_str("wait 100"), Ok(ResetStep::Wait(Duration::from_millis(100))));
+
+        for invalid_step in ["", "dtr", "dtr high", "cts on", "rts on now", "wait", "wait -5", "wait 1.5", "wait 100ms"] {
+            assert!(ResetStep::from_config_str(invalid_step).is_err(), "'{}'", invalid_step);
+        }
+        assert!(ResetStep::from_config_str("wait soon").unwrap_err().contains("expected milliseconds"));
+    }
+
+    #[test]
+    fn alt_keys() {
+        assert_eq!(ResetRecipe::parse_alt_key("Alt+7"), Ok('7'));
+        for invalid_key in ["alt+3", "alt+a", "ctrl+4", "4", "alt+10"] {
+            assert!(ResetRecipe::parse_alt_key(invalid_key).is_err(), "'{}'", invalid_key);
+        }
+    }
+}
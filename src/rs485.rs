@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use serialport5::SerialPort;
+
+use crate::modem_lines::{ModemLines, ModemOutputLine};
+
+/// How long after a write its echo from the bus is still expected.
+const BUS_ECHO_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Half-duplex RS-485: the transceiver's driver is enabled with a modem control line around each write.
+#[derive(Debug, Clone)]
+pub struct Rs485Config {
+    pub direction_line: ModemOutputLine,
+    /// The driver is enabled with the line off (some adapters invert RTS).
+    pub is_active_low: bool,
+
+    /// Wait between enabling the driver and writing, and between the last byte going out and disabling it.
+    pub pre_delay: Duration,
+    pub post_delay: Duration,
+
+    /// What to do with our own bytes when they come back from the bus.
+    pub echo_mode: BusEchoMode,
+}
+
+/// What to do with our own bytes coming back from the bus. Not every adapter echoes them, and a reply can start
+/// with the same bytes as the request (e.g., Modbus writes), so the received data is left alone unless asked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusEchoMode {
+    /// Show them as received data (or the adapter doesn't echo).
+    None,
+    /// Drop them.
+    Suppress,
+    /// Show them as echo (in place of the local echo).
+    Mark,
+}
+
+impl BusEchoMode {
+    pub fn from_config_str(s: &str) -> Result<Self, String> {
+        match s {
+            "none" => Ok(BusEchoMode::None),
+            "suppress" => Ok(BusEchoMode::Suppress),
+            "mark" => Ok(BusEchoMode::Mark),
+            _ => Err(format!("Invalid RS-485 echo '{}' (expected \"none\", \"suppress\", or \"mark\")", s)),
+        }
+    }
+}
+
+impl Rs485Config {
+    pub fn new() -> Self {
+        Self {
+            direction_line: ModemOutputLine::Rts,
+            is_active_low: false,
+            pre_delay: Duration::ZERO,
+            post_delay: Duration::ZERO,
+            echo_mode: BusEchoMode::None,
+        }
+    }
+
+    /// Level of the direction line while transmitting.
+    pub fn get_transmit_level(&self) -> bool {
+        !self.is_active_low
+    }
+
+    /// Puts the transceiver in receive mode (e.g., right after the port is opened, since the OS turns RTS on).
+    pub fn set_receive_mode(&self, port: &mut SerialPort, modem_lines: &mut ModemLines) -> Result<(), String> {
+        modem_lines.set_output(port, self.direction_line, !self.get_transmit_level())
+    }
+
+    /// Writes `data` with the driver enabled, and waits for it to go out before going back to receive mode.
+    /// This blocks for the time it takes to send `data` (plus the delays).
+    pub fn write_half_duplex(&self, port: &mut SerialPort, modem_lines: &mut ModemLines, data: &[u8]) -> Result<(), String> {
+        modem_lines.set_output(port, self.direction_line, self.get_transmit_level())?;
+        std::thread::sleep(self.pre_delay);
+        // flush waits for the last byte to leave the UART, not just the OS buffer
+        let write_result = port.write_all(data)
+            .and_then(|_| port.flush())
+            .map_err(|e| format!("Error writing to serial port: {}", e));
+        std::thread::sleep(self.post_delay);
+        self.set_receive_mode(port, modem_lines)?;
+        write_result
+    }
+}
+
+/// Picks our own bytes out of the received data, as they come back from the shared bus.
+pub struct BusEchoFilter {
+    expected: VecDeque<u8>,
+    expire_time: Option<Instant>,
+}
+
+impl BusEchoFilter {
+    pub fn new() -> Self {
+        Self {
+            expected: VecDeque::new(),
+            expire_time: None,
+        }
+    }
+
+    /// Expects `data` (which was just written) to come back.
+    pub fn expect(&mut self, data: &[u8]) {
+        self.expected.extend(data);
+        self.expire_time = Some(Instant::now() + BUS_ECHO_TIMEOUT);
+    }
+
+    /// Splits received data into the echo of what we wrote, and the rest (what the other devices sent).
+    /// Once a byte doesn't match (e.g., a collision, or a reply), the rest of the echo isn't expected anymore.
+    pub fn take_echo(&mut self, data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        if self.expire_time.is_some_and(|expire_time| Instant::now() >= expire_time) {
+            self.expected.clear();
+            self.expire_time = None;
+        }
+        let echo_len = data.iter().zip(self.expected.iter())
+            .take_while(|(byte, expected_byte)| byte == expected_byte)
+            .count();
+        match echo_len < data.len() {
+            true => self.expected.clear(),
+            false => { self.expected.drain(..echo_len); }
+        }
+        (data[..echo_len].to_vec(), data[echo_len..].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_modes_from_config_strings() {
+        assert_eq!(Rs485Config::new().echo_mode, BusEchoMode::None);
+        assert_eq!(BusEchoMode::from_config_str("suppress"), Ok(BusEchoMode::Suppress));
+        assert_eq!(BusEchoMode::from_config_str("mark"), Ok(BusEchoMode::Mark));
+        assert!(BusEchoMode::from_config_str("drop").is_err());
+    }
+
+    #[test]
+    fn echo_filter_takes_only_the_echo() {
+        let mut filter = BusEchoFilter::new();
+        filter.expect(&[0x01, 0x06, 0x00, 0x64]);
+        assert_eq!(filter.take_echo(&[0x01, 0x06]), (vec![0x01, 0x06], vec![]));
+        assert_eq!(filter.take_echo(&[0x00, 0x64, 0x01, 0x03]), (vec![0x00, 0x64], vec![0x01, 0x03]));
+        // nothing more is expected
+        assert_eq!(filter.take_echo(&[0x01, 0x06]), (vec![], vec![0x01, 0x06]));
+
+        filter.expect(&[0x41, 0x42, 0x43]);
+        assert_eq!(filter.take_echo(&[0x41, 0x58, 0x43]), (vec![0x41], vec![0x58, 0x43]));
+        assert_eq!(filter.take_echo(&[0x43]), (vec![], vec![0x43]));
+    }
+}
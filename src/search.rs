@@ -0,0 +1,364 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::log_filter::LogFilterState;
+use crate::transfer_log::{TransferLog, MAX_ENTRIES_SCANNED_PER_UPDATE};
+
+/// A single search match in the transfer log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchMatch {
+    pub entry_idx: usize,
+
+    /// Byte offsets of the match within the entry's `get_data_as_string()`.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// State of the search in the Main screen's transfer log.
+pub struct SearchState {
+    pub query: String,
+    pub is_regex: bool,
+
+    /// Whether the search prompt is open (i.e., keypresses go to the query).
+    pub is_editing: bool,
+
+    pub matches: Vec<SearchMatch>,
+    pub current_match_idx: Option<usize>,
+
+    /// Set when the view should scroll to the current match on the next render.
+    pub scroll_to_current_match_pending: bool,
+
+    /// Set when the query is an invalid regex.
+    pub error_message: Option<String>,
+
+    compiled_query: Option<Regex>,
+
+    /// Entries before this index have been searched, and won't change anymore.
+    /// None when the whole log needs to be searched again (e.g., the query changed).
+    scanned_final_len: Option<usize>,
+    scanned_filter_generation: u64,
+
+    /// Whether the last update stopped before the end of the log, to spread a rescan over several updates.
+    is_scan_pending: bool,
+
+    /// Set when the first match from this entry index is to be selected once it's been searched.
+    select_first_match_pending_from: Option<usize>,
+}
+
+impl SearchState {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            is_regex: false,
+            is_editing: false,
+            matches: Vec::new(),
+            current_match_idx: None,
+            scroll_to_current_match_pending: false,
+            error_message: None,
+            compiled_query: None,
+            scanned_final_len: None,
+            scanned_filter_generation: 0,
+            is_scan_pending: false,
+            select_first_match_pending_from: None,
+        }
+    }
+
+    /// Whether there's a search to show (a valid, non-empty query).
+    pub fn is_active(&self) -> bool {
+        self.compiled_query.is_some()
+    }
+
+    /// Recompiles the query. Must be called after changing `query` or `is_regex`.
+    /// Uses "smart case": the search is case-insensitive unless the query has an uppercase letter.
+    pub fn compile_query(&mut self) {
+        self.scanned_final_len = None;
+        self.select_first_match_pending_from = None;
+        self.error_message = None;
+        self.compiled_query = None;
+        if self.query.is_empty() {
+            return;
+        }
+
+        let pattern = match self.is_regex {
+            true => self.query.clone(),
+            false => regex::escape(&self.query),
+        };
+        let is_case_insensitive = !self.query.chars().any(|c| c.is_uppercase());
+        match RegexBuilder::new(&pattern).case_insensitive(is_case_insensitive).build() {
+            Ok(regex) => {
+                self.compiled_query = Some(regex);
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Invalid regex: {}", e));
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.compile_query();
+        self.matches.clear();
+        self.current_match_idx = None;
+    }
+
+    /// Finds the matches in the visible (i.e., not filtered-out) indexed entries of the transfer log.
+    /// Only the new entries are searched, unless the query or the filter changed since the last update;
+    /// then the search starts over, spread over several updates (`MAX_ENTRIES_SCANNED_PER_UPDATE` entries each).
+    /// The current match stays selected if it still exists.
+    pub fn update_matches(&mut self, transfer_log: &TransferLog, filter: &LogFilterState) {
+        let current_match = self.get_current_match();
+        let first_indexed_idx = transfer_log.first_indexed_idx();
+
+        let mut scan_from_idx = match self.scanned_final_len {
+            Some(scanned_final_len) if self.scanned_filter_generation == filter.generation => scanned_final_len,
+            _ => {
+                self.matches.clear();
+                first_indexed_idx
+            }
+        };
+        self.scanned_filter_generation = filter.generation;
+        scan_from_idx = scan_from_idx.max(first_indexed_idx);
+
+        // drop the matches in entries that fell out of the indexed entries, or that may have changed (i.e., the last line)
+        let dropped_match_count = self.matches.partition_point(|m| m.entry_idx < first_indexed_idx);
+        self.matches.drain(..dropped_match_count);
+        self.matches.truncate(self.matches.partition_point(|m| m.entry_idx < scan_from_idx));
+
+        let scan_end = match &self.compiled_query {
+            Some(_) => transfer_log.len().min(scan_from_idx + MAX_ENTRIES_SCANNED_PER_UPDATE),
+            None => transfer_log.len(),
+        };
+        if let Some(regex) = &self.compiled_query {
+            for entry_idx in scan_from_idx..scan_end {
+                let Some(entry) = transfer_log.get(entry_idx) else { continue };
+                if !filter.is_entry_visible(&entry) {
+                    continue;
+                }
+                let entry_text = entry.get_data_as_string();
+                for m in regex.find_iter(&entry_text).filter(|m| !m.is_empty()) {
+                    self.matches.push(SearchMatch { entry_idx, start: m.start(), end: m.end() });
+                }
+            }
+        }
+        // the rest of the entries are searched in the next updates
+        self.scanned_final_len = Some(transfer_log.first_mutable_idx().min(scan_end));
+        self.is_scan_pending = scan_end < transfer_log.len();
+
+        self.current_match_idx = match current_match {
+            Some(current_match) => {
+                // matches are sorted by (entry, start), so the same or next match can be found by binary search
+                let next_match_idx = self.matches.partition_point(
+                    |m| (m.entry_idx, m.start) < (current_match.entry_idx, current_match.start));
+                match next_match_idx < self.matches.len() {
+                    true => Some(next_match_idx),
+                    false => None,
+                }
+            }
+            None => None,
+        };
+        if let Some(entry_idx) = self.select_first_match_pending_from {
+            self.select_first_match_from(entry_idx);
+        }
+    }
+
+    /// Whether some entries haven't been searched yet (after the query or the filter changed).
+    pub fn is_scan_pending(&self) -> bool {
+        self.is_scan_pending
+    }
+
+    /// Selects the first match at or after `entry_idx` (wrapping around to the first match).
+    /// If there's none yet, but the search isn't done, the match is selected once it's found.
+    pub fn select_first_match_from(&mut self, entry_idx: usize) {
+        let next_match_idx = self.matches.partition_point(|m| m.entry_idx < entry_idx);
+        if next_match_idx == self.matches.len() && self.is_scan_pending {
+            self.select_first_match_pending_from = Some(entry_idx);
+            return;
+        }
+        self.select_first_match_pending_from = None;
+        self.current_match_idx = match (next_match_idx < self.matches.len(), self.matches.is_empty()) {
+            (true, _) => Some(next_match_idx),
+            (false, false) => Some(0),
+            (false, true) => None,
+        };
+        self.scroll_to_current_match_pending = self.current_match_idx.is_some();
+    }
+
+    pub fn select_next_match(&mut self) {
+        self.select_first_match_pending_from = None;
+        if self.matches.is_empty() {
+            return;
+        }
+        self.current_match_idx = Some(match self.current_match_idx {
+            Some(idx) => (idx + 1) % self.matches.len(),
+            None => 0,
+        });
+        self.scroll_to_current_match_pending = true;
+    }
+
+    pub fn select_prev_match(&mut self) {
+        self.select_first_match_pending_from = None;
+        if self.matches.is_empty() {
+            return;
+        }
+        self.current_match_idx = Some(match self.current_match_idx {
+            Some(0) | None => self.matches.len() - 1,
+            Some(idx) => idx - 1,
+        });
+        self.scroll_to_current_match_pending = true;
+    }
+
+    pub fn get_current_match(&self) -> Option<SearchMatch> {
+        self.current_match_idx.and_then(|idx| self.matches.get(idx).copied())
+    }
+
+    /// Text for the match counter (e.g., "3/17").
+    pub fn get_match_counter_string(&self) -> String {
+        match self.current_match_idx {
+            Some(idx) => format!("{}/{}", idx + 1, self.matches.len()),
+            None => format!("-/{}", self.matches.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{TransferLogEntry, TransferLogType};
+
+    fn push_line(transfer_log: &mut TransferLog, text: &str) {
+        transfer_log.push(TransferLogEntry::new(text.as_bytes().to_vec(), TransferLogType::SerialData, true, chrono::Local::now()));
+    }
+
+    fn set_query(search: &mut SearchState, query: &str) {
+        search.query = query.to_string();
+        search.compile_query();
+    }
+
+    #[test]
+    fn matches_are_ordered_by_entry_then_offset() {
+        let mut transfer_log = TransferLog::new(100, None);
+        let filter = LogFilterState::new();
+        let mut search = SearchState::new();
+        push_line(&mut transfer_log, "ok ok");
+        push_line(&mut transfer_log, "nothing here");
+        push_line(&mut transfer_log, "OK");
+
+        set_query(&mut search, "ok");
+        search.update_matches(&transfer_log, &filter);
+        let matches: Vec<(usize, usize, usize)> = search.matches.iter().map(|m| (m.entry_idx, m.start, m.end)).collect();
+        assert_eq!(matches, vec![(0, 0, 2), (0, 3, 5), (2, 0, 2)]);
+        assert_eq!(search.get_match_counter_string(), "-/3");
+
+        // smart case: an uppercase letter makes the search case-sensitive
+        set_query(&mut search, "OK");
+        search.update_matches(&transfer_log, &filter);
+        assert_eq!(search.matches.len(), 1);
+
+        search.select_first_match_from(1);
+        assert_eq!(search.get_current_match().map(|m| m.entry_idx), Some(2));
+        search.select_next_match();
+        assert_eq!(search.get_current_match().map(|m| m.entry_idx), Some(2));
+    }
+
+    #[test]
+    fn zero_width_matches_are_skipped() {
+        let mut transfer_log = TransferLog::new(100, None);
+        let filter = LogFilterState::new();
+        let mut search = SearchState::new();
+        push_line(&mut transfer_log, "abc");
+        push_line(&mut transfer_log, "xbx");
+
+        search.is_regex = true;
+        set_query(&mut search, "b*");
+        search.update_matches(&transfer_log, &filter);
+        let matches: Vec<(usize, usize, usize)> = search.matches.iter().map(|m| (m.entry_idx, m.start, m.end)).collect();
+        assert_eq!(matches, vec![(0, 1, 2), (1, 1, 2)]);
+
+        set_query(&mut search, "^");
+        search.update_matches(&transfer_log, &filter);
+        assert!(search.matches.is_empty());
+
+        set_query(&mut search, "(");
+        assert!(!search.is_active());
+        assert!(search.error_message.is_some());
+    }
+
+    #[test]
+    fn current_match_is_kept_when_new_data_arrives() {
+        let mut transfer_log = TransferLog::new(3, None);
+        let filter = LogFilterState::new();
+        let mut search = SearchState::new();
+        push_line(&mut transfer_log, "err 0");
+        push_line(&mut transfer_log, "err 1");
+        push_line(&mut transfer_log, "err 2");
+
+        set_query(&mut search, "err");
+        search.update_matches(&transfer_log, &filter);
+        search.select_first_match_from(1);
+        assert_eq!(search.get_match_counter_string(), "2/3");
+
+        // the first match falls out of the scrollback, and a partial line (that can still change) arrives
+        transfer_log.push(TransferLogEntry::new(b"er".to_vec(), TransferLogType::SerialData, false, chrono::Local::now()));
+        search.update_matches(&transfer_log, &filter);
+        assert_eq!(search.get_current_match().map(|m| m.entry_idx), Some(1));
+        assert_eq!(search.get_match_counter_string(), "1/2");
+
+        transfer_log.last_mut().unwrap().data_as_bytes.extend(b"r 3");
+        search.update_matches(&transfer_log, &filter);
+        assert_eq!(search.get_current_match().map(|m| m.entry_idx), Some(1));
+        assert_eq!(search.get_match_counter_string(), "1/3");
+
+        // once the current match's line is gone, the next one is selected
+        push_line(&mut transfer_log, "x");
+        push_line(&mut transfer_log, "y");
+        search.update_matches(&transfer_log, &filter);
+        assert_eq!(search.get_current_match().map(|m| m.entry_idx), Some(3));
+    }
+
+    #[test]
+    fn filter_change_rescans_only_visible_entries() {
+        let mut transfer_log = TransferLog::new(100, None);
+        let mut filter = LogFilterState::new();
+        let mut search = SearchState::new();
+        push_line(&mut transfer_log, "temp 21");
+        push_line(&mut transfer_log, "humidity 21");
+        push_line(&mut transfer_log, "temp 22");
+
+        set_query(&mut search, "2");
+        search.update_matches(&transfer_log, &filter);
+        assert_eq!(search.matches.len(), 4);
+
+        filter.expression = "temp".to_string();
+        filter.compile_expression();
+        search.update_matches(&transfer_log, &filter);
+        let match_entries: Vec<usize> = search.matches.iter().map(|m| m.entry_idx).collect();
+        assert_eq!(match_entries, vec![0, 2, 2]);
+
+        filter.clear();
+        search.update_matches(&transfer_log, &filter);
+        assert_eq!(search.matches.len(), 4);
+    }
+
+    #[test]
+    fn search_is_spread_over_updates() {
+        let entry_count = MAX_ENTRIES_SCANNED_PER_UPDATE + 10;
+        let mut transfer_log = TransferLog::new(entry_count, None);
+        let filter = LogFilterState::new();
+        let mut search = SearchState::new();
+        for line_num in 0..entry_count {
+            push_line(&mut transfer_log, &format!("line {}", line_num));
+        }
+
+        set_query(&mut search, "line 2000");
+        search.update_matches(&transfer_log, &filter);
+        assert!(search.is_scan_pending());
+        assert_eq!(search.matches.len(), 1); // "line 20000" to "line 20009" aren't searched yet
+
+        // the match to select may not have been found yet
+        search.select_first_match_from(20001);
+        assert_eq!(search.get_current_match(), None);
+        search.update_matches(&transfer_log, &filter);
+        assert!(!search.is_scan_pending());
+        assert_eq!(search.matches.len(), 11);
+        assert_eq!(search.get_current_match().map(|m| m.entry_idx), Some(20001));
+    }
+}
@@ -0,0 +1,250 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::config_file::{get_workspace_dir, WORKSPACE_DIR_NAME};
+
+/// Default max number of entries kept in the send history.
+pub const DEFAULT_SEND_HISTORY_MAX_ENTRIES: usize = 1000;
+
+/// History of the messages sent from the Send box, oldest first, with no duplicates.
+/// Optionally saved to a file, so that it's kept across runs.
+pub struct SendHistory {
+    entries: Vec<String>,
+    max_entries: usize,
+
+    /// File the history is saved to after every change. None keeps it in memory only.
+    file_path: Option<PathBuf>,
+}
+
+/// State of the reverse incremental search (Ctrl+R) through the send history.
+pub struct SendHistorySearchState {
+    pub query: String,
+
+    /// Index of the current match in the send history. None if nothing matches the query.
+    pub match_idx: Option<usize>,
+}
+
+impl SendHistory {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            max_entries,
+            file_path: None,
+        }
+    }
+
+    /// Loads the history from `file_path` (if it exists yet), and saves it back there after every change.
+    pub fn load(file_path: PathBuf, max_entries: usize) -> Result<Self, Box<dyn Error>> {
+        let mut send_history = SendHistory::new(max_entries);
+        if file_path.is_file() {
+            let contents = std::fs::read_to_string(&file_path)
+                .map_err(|e| format!("Error reading send history file '{}': {}", file_path.display(), e))?;
+            for line in contents.lines().filter(|line| !line.is_empty()) {
+                send_history.add_entry(unescape_entry(line));
+            }
+        }
+        send_history.file_path = Some(file_path);
+        Ok(send_history)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> &str {
+        &self.entries[idx]
+    }
+
+    /// Adds a sent message as the newest entry (moving it there, if it was already in the history),
+    /// and saves the history to its file.
+    pub fn push(&mut self, entry: String) -> Result<(), String> {
+        if entry.is_empty() {
+            return Ok(());
+        }
+        self.add_entry(entry);
+        self.save()
+    }
+
+    /// Index of the newest entry before `before_idx` that contains `query`.
+    pub fn find_prev_match(&self, query: &str, before_idx: usize) -> Option<usize> {
+        self.entries[..before_idx.min(self.entries.len())].iter()
+            .rposition(|entry| entry.contains(query))
+    }
+
+    fn add_entry(&mut self, entry: String) {
+        self.entries.retain(|existing_entry| *existing_entry != entry);
+        self.entries.push(entry);
+        if self.entries.len() > self.max_entries {
+            self.entries.drain(..(self.entries.len() - self.max_entries));
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(file_path) = &self.file_path else { return Ok(()) };
+        if let Some(parent_dir) = file_path.parent() {
+            std::fs::create_dir_all(parent_dir)
+                .map_err(|e| format!("Error creating directory '{}': {}", parent_dir.display(), e))?;
+        }
+        let contents: String = self.entries.iter()
+            .map(|entry| format!("{}\n", escape_entry(entry)))
+            .collect();
+        std::fs::write(file_path, contents)
+            .map_err(|e| format!("Error saving send history to '{}': {}", file_path.display(), e))
+    }
+}
+
+impl SendHistorySearchState {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            match_idx: None,
+        }
+    }
+
+    /// Searches again from the newest entry (after the query changed).
+    pub fn update_match(&mut self, send_history: &SendHistory) {
+        self.match_idx = send_history.find_prev_match(&self.query, send_history.len());
+    }
+
+    /// Moves to the next older match (Ctrl+R again). Stays on the current match if there are no older ones.
+    pub fn select_older_match(&mut self, send_history: &SendHistory) {
+        if let Some(match_idx) = self.match_idx {
+            if let Some(older_match_idx) = send_history.find_prev_match(&self.query, match_idx) {
+                self.match_idx = Some(older_match_idx);
+            }
+        }
+    }
+}
+
+/// Path of the send history file for `port_name`.
+///
+/// In a workspace (`.baud-boss/` in the current directory), the history belongs to the workspace,
+/// optionally split up per device. Otherwise, it's kept per device in `~/.baud-boss/`.
+pub fn get_send_history_file_path(port_name: &str, is_per_device: bool) -> Option<PathBuf> {
+    let (dir, is_per_device) = match get_workspace_dir().is_dir() {
+        true => (get_workspace_dir(), is_per_device),
+        false => (PathBuf::from(std::env::var_os("HOME")?).join(WORKSPACE_DIR_NAME), true),
+    };
+    let file_name = match is_per_device {
+        true => format!("send_history_{}.txt", get_sanitized_port_name(port_name)),
+        false => String::from("send_history.txt"),
+    };
+    Some(dir.join(file_name))
+}
+
+/// Turns a port name (e.g., "/dev/ttyUSB0" or "COM3") into something usable in a file name.
+fn get_sanitized_port_name(port_name: &str) -> String {
+    let port_name = port_name.strip_prefix("/dev/").unwrap_or(port_name);
+    port_name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Entries are stored one per line, so line breaks (from multi-line blocks) and backslashes are escaped.
+fn escape_entry(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_entry(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            _ => entry.push(c),
+        }
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_deduplicated_and_capped() {
+        let mut send_history = SendHistory::new(3);
+        assert!(send_history.is_empty());
+        for entry in ["a", "b", "a", "", "c", "d"] {
+            send_history.push(entry.to_string()).unwrap();
+        }
+        let entries: Vec<&str> = (0..send_history.len()).map(|idx| send_history.get(idx)).collect();
+        assert_eq!(entries, vec!["a", "c", "d"]);
+    }
+
+    #[test]
+    fn reverse_search_goes_to_older_matches() {
+        let mut send_history = SendHistory::new(10);
+        for entry in ["ls -l", "cat log", "ls /tmp", "reboot"] {
+            send_history.push(entry.to_string()).unwrap();
+        }
+        let mut search = SendHistorySearchState::new();
+        search.query = "ls".to_string();
+        search.update_match(&send_history);
+        assert_eq!(search.match_idx, Some(2));
+        search.select_older_match(&send_history);
+        assert_eq!(search.match_idx, Some(0));
+        // no older match, so it stays
+        search.select_older_match(&send_history);
+        assert_eq!(search.match_idx, Some(0));
+
+        search.query = "nothing".to_string();
+        search.update_match(&send_history);
+        assert_eq!(search.match_idx, None);
+    }
+
+    #[test]
+    fn escaping_round_trips() {
+        for entry in ["plain", "two\nlines", "back\\slash", "literal \\n, not a line break", "\\", "ends with \\\n"] {
+            let escaped = escape_entry(entry);
+            assert!(!escaped.contains('\n'));
+            assert_eq!(unescape_entry(&escaped), entry);
+        }
+        assert_eq!(escape_entry("a\\nb\nc"), "a\\\\nb\\nc");
+        // a stray backslash (e.g., from a hand-edited file) is kept as-is
+        assert_eq!(unescape_entry("a\\tb\\"), "a\\tb\\");
+    }
+
+    #[test]
+    fn history_is_saved_and_loaded() {
+        let file_path = std::env::temp_dir().join(format!("baud-boss-test-send-history-{}.txt", std::process::id()));
+        // removes the file even if the test fails
+        struct RemoveOnDrop<'a>(&'a std::path::Path);
+        impl Drop for RemoveOnDrop<'_> {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(self.0);
+            }
+        }
+        let _remove_on_drop = RemoveOnDrop(&file_path);
+
+        let mut send_history = SendHistory::load(file_path.clone(), 10).unwrap();
+        assert!(send_history.is_empty());
+        send_history.push("first".to_string()).unwrap();
+        send_history.push("multi\nline \\ block".to_string()).unwrap();
+
+        let loaded_send_history = SendHistory::load(file_path.clone(), 1).unwrap();
+        assert_eq!(loaded_send_history.len(), 1);
+        assert_eq!(loaded_send_history.get(0), "multi\nline \\ block");
+    }
+
+    #[test]
+    fn port_names_are_sanitized() {
+        assert_eq!(get_sanitized_port_name("/dev/ttyUSB0"), "ttyUSB0");
+        assert_eq!(get_sanitized_port_name("/dev/serial/by-id/usb-FTDI_1.0"), "serial_by-id_usb-FTDI_1.0");
+        assert_eq!(get_sanitized_port_name("COM3"), "COM3");
+    }
+}
@@ -0,0 +1,353 @@
+use chrono::format::{Item, StrftimeItems};
+
+use crate::app::decode_bytes_lossy;
+use crate::hex::parse_hex_bytes;
+
+/// Default format of `{timestamp}`.
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f";
+
+/// Max number of bytes from one `{random:N}`.
+const MAX_RANDOM_BYTES: usize = 256;
+
+/// How the text of a message is turned into bytes.
+#[derive(Clone, Copy)]
+pub struct SendTextOptions {
+    /// The text is hex bytes (e.g., "01 03 0A"), rather than text to send as UTF-8.
+    pub is_hex: bool,
+
+    /// Backslash escapes (`\r`, `\x1b`, ...) become the bytes they stand for (text only).
+    pub parse_escapes: bool,
+
+    /// Placeholders like `{counter}` and `{crc16_modbus}` are expanded.
+    pub expand_templates: bool,
+}
+
+/// State shared by every template expansion: the message counter, and the random number generator.
+pub struct TemplateContext {
+    /// Value of `{counter}`. Incremented after each send.
+    pub counter: u64,
+    rng_state: u64,
+}
+
+impl TemplateContext {
+    pub fn new() -> Self {
+        let seed = chrono::Local::now().timestamp_nanos_opt().unwrap_or(0) as u64;
+        Self {
+            counter: 0,
+            rng_state: seed | 1, // xorshift gets stuck at 0
+        }
+    }
+
+    /// Random byte (xorshift64; not for anything security-related).
+    fn get_random_byte(&mut self) -> u8 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        (self.rng_state >> 32) as u8
+    }
+}
+
+/// Turns the text of a message into the bytes to send (not including the EOL).
+///
+/// Placeholders (with `expand_templates`):
+/// * `{counter}`: the message counter, in decimal (or as a byte, in hex).
+/// * `{timestamp}` or `{timestamp:<strftime format>}`: the current local time.
+/// * `{random}` or `{random:N}`: N random bytes.
+/// * `{crc16_modbus}`, `{xor}`, `{sum8}`: checksum of the bytes before it in the message (low byte first,
+///   for the CRC). A byte range can be given as `{xor:1}` (from offset 1) or `{xor:1..5}` (offsets 1 to 4).
+/// * `{{` is a literal `{`.
+pub fn expand_send_text(text: &str, options: SendTextOptions, context: &mut TemplateContext) -> Result<Vec<u8>, String> {
+    let mut out: Vec<u8> = Vec::new();
+    let mut literal = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if options.parse_escapes && !options.is_hex => {
+                // keep the escape (even `\{`) for unescape_to_bytes
+                literal.push(c);
+                literal.extend(chars.next());
+            }
+            '{' if options.expand_templates => {
+                let rest = chars.as_str();
+                if let Some(after_brace) = rest.strip_prefix('{') {
+                    literal.push('{');
+                    chars = after_brace.chars();
+                    continue;
+                }
+                let placeholder_len = rest.find('}')
+                    .ok_or_else(|| format!("Unclosed placeholder '{{{}'", rest))?;
+                out.extend(get_literal_bytes(&literal, options)?);
+                literal.clear();
+                let placeholder_bytes = expand_placeholder(&rest[..placeholder_len], &out, options, context)?;
+                out.extend(placeholder_bytes);
+                chars = rest[placeholder_len + 1..].chars();
+            }
+            _ => literal.push(c),
+        }
+    }
+    out.extend(get_literal_bytes(&literal, options)?);
+    Ok(out)
+}
+
+/// Splits a message (from the Send box, a snippet, or a scheduled send) into its lines, at each '\n'.
+/// A line break at the end leaves an empty last line, which is sent as just the EOL.
+pub fn split_message_lines(text: &str) -> Vec<&str> {
+    text.split('\n').collect()
+}
+
+/// Expands each line of a message. Returns the bytes to send for each line (with `end_of_line` after it),
+/// and how to echo it. The counter goes up once per message.
+pub fn expand_message_lines(lines: &[&str], end_of_line: &str, options: SendTextOptions, context: &mut TemplateContext) -> Result<Vec<(Vec<u8>, String)>, String> {
+    let mut expanded_lines: Vec<(Vec<u8>, String)> = Vec::with_capacity(lines.len());
+    for line in lines {
+        let mut data = expand_send_text(line, options, context)?;
+        let echo_string = match options.is_hex {
+            true => data.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" "),
+            false => get_echo_string(&data),
+        };
+        data.extend(end_of_line.as_bytes());
+        expanded_lines.push((data, echo_string));
+    }
+    if options.expand_templates {
+        context.counter += 1;
+    }
+    Ok(expanded_lines)
+}
+
+/// Checks the hex digits of a line of hex text, skipping its placeholders (which are only expanded when it's sent).
+pub fn check_hex_text(text: &str) -> Result<(), String> {
+    let mut rest = text;
+    while let Some(placeholder_start) = rest.find('{') {
+        parse_hex_bytes(&rest[..placeholder_start])?;
+        let placeholder_len = rest[placeholder_start..].find('}')
+            .ok_or_else(|| format!("Unclosed placeholder '{}'", &rest[placeholder_start..]))?;
+        rest = &rest[placeholder_start + placeholder_len + 1..];
+    }
+    parse_hex_bytes(rest)?;
+    Ok(())
+}
+
+/// How sent bytes are shown in their echo entry: as text, with control characters as escapes.
+pub fn get_echo_string(data: &[u8]) -> String {
+    let mut echo_string = String::new();
+    for c in decode_bytes_lossy(data).chars() {
+        match c {
+            '\r' => echo_string.push_str("\\r"),
+            '\n' => echo_string.push_str("\\n"),
+            '\t' => echo_string.push_str("\\t"),
+            c if c.is_control() => echo_string.push_str(&format!("\\x{:02X}", c as u32)),
+            c => echo_string.push(c),
+        }
+    }
+    echo_string
+}
+
+fn get_literal_bytes(literal: &str, options: SendTextOptions) -> Result<Vec<u8>, String> {
+    match (options.is_hex, options.parse_escapes) {
+        (true, _) => parse_hex_bytes(literal),
+        (false, true) => unescape_to_bytes(literal),
+        (false, false) => Ok(literal.as_bytes().to_vec()),
+    }
+}
+
+/// Expands the placeholder `name[:arg]` (without the braces). `message` is the message so far (for checksums).
+fn expand_placeholder(placeholder: &str, message: &[u8], options: SendTextOptions, context: &mut TemplateContext) -> Result<Vec<u8>, String> {
+    let (name, arg) = match placeholder.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(arg)),
+        None => (placeholder.trim(), None),
+    };
+    match (name, arg) {
+        ("counter", None) => {
+            match options.is_hex {
+                // in hex, it's a byte (wrapping around)
+                true => Ok(vec![context.counter as u8]),
+                false => Ok(context.counter.to_string().into_bytes()),
+            }
+        }
+        ("timestamp", format) => {
+            let format = format.unwrap_or(DEFAULT_TIMESTAMP_FORMAT);
+            if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                return Err(format!("Invalid timestamp format '{}'", format));
+            }
+            Ok(chrono::Local::now().format(format).to_string().into_bytes())
+        }
+        ("random", count) => {
+            let count: usize = match count {
+                Some(count) => count.trim().parse()
+                    .map_err(|_| format!("Invalid byte count '{}' in {{random:N}}", count))?,
+                None => 1,
+            };
+            if count > MAX_RANDOM_BYTES {
+                return Err(format!("{{random:N}} is limited to {} bytes", MAX_RANDOM_BYTES));
+            }
+            Ok((0..count).map(|_| context.get_random_byte()).collect())
+        }
+        ("crc16_modbus" | "xor" | "sum8", range) => {
+            let range_bytes = get_checksum_range(message, range)?;
+            match name {
+                "crc16_modbus" => Ok(get_crc16_modbus(range_bytes).to_le_bytes().to_vec()),
+                "xor" => Ok(vec![range_bytes.iter().fold(0, |acc, byte| acc ^ byte)]),
+                _ => Ok(vec![range_bytes.iter().fold(0_u8, |acc, byte| acc.wrapping_add(*byte))]),
+            }
+        }
+        _ => Err(format!("Unknown placeholder '{{{}}}'", placeholder)),
+    }
+}
+
+/// The bytes of `message` in `range` ("start" or "start..end"; the whole message so far when None).
+fn get_checksum_range<'a>(message: &'a [u8], range: Option<&str>) -> Result<&'a [u8], String> {
+    let Some(range) = range else { return Ok(message) };
+    let parse_offset = |offset: &str| -> Result<usize, String> {
+        offset.trim().parse().map_err(|_| format!("Invalid checksum range '{}'", range))
+    };
+    let (start, end) = match range.split_once("..") {
+        Some((start, "")) => (parse_offset(start)?, message.len()),
+        Some((start, end)) => (parse_offset(start)?, parse_offset(end)?),
+        None => (parse_offset(range)?, message.len()),
+    };
+    match start <= end && end <= message.len() {
+        true => Ok(&message[start..end]),
+        false => Err(format!("Checksum range '{}' isn't within the {} bytes before it", range, message.len())),
+    }
+}
+
+/// CRC-16/MODBUS (polynomial 0x8005 reflected, initial value 0xFFFF).
+pub fn get_crc16_modbus(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xA001,
+                _ => crc >> 1,
+            };
+        }
+    }
+    crc
+}
+
+/// Turns backslash escapes into bytes: `\r`, `\n`, `\t`, `\0`, `\e` (ESC), `\\`, `\{`, `\}`, and `\xHH`.
+fn unescape_to_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let mut out: Vec<u8> = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8_buf = [0_u8; 4];
+            out.extend(c.encode_utf8(&mut utf8_buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => out.push(b'\r'),
+            Some('n') => out.push(b'\n'),
+            Some('t') => out.push(b'\t'),
+            Some('0') => out.push(0x00),
+            Some('e') => out.push(0x1B),
+            Some(c @ ('\\' | '{' | '}')) => out.push(c as u8),
+            Some('x') => {
+                let hex_digits: String = chars.by_ref().take(2).collect();
+                let byte = match hex_digits.len() {
+                    2 => u8::from_str_radix(&hex_digits, 16).ok(),
+                    _ => None,
+                };
+                out.push(byte.ok_or_else(|| format!("Invalid escape sequence '\\x{}' (expected \\xHH)", hex_digits))?);
+            }
+            Some(c) => return Err(format!("Unknown escape sequence '\\{}'", c)),
+            None => return Err(String::from("Escape sequence '\\' at the end of the line")),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(text: &str, is_hex: bool) -> Result<Vec<u8>, String> {
+        let options = SendTextOptions { is_hex, parse_escapes: true, expand_templates: true };
+        expand_send_text(text, options, &mut TemplateContext::new())
+    }
+
+    #[test]
+    fn message_lines() {
+        assert_eq!(split_message_lines("AT"), vec!["AT"]);
+        assert_eq!(split_message_lines("a\nb"), vec!["a", "b"]);
+        assert_eq!(split_message_lines("a\n"), vec!["a", ""]);
+        assert_eq!(split_message_lines(""), vec![""]);
+
+        let options = SendTextOptions { is_hex: false, parse_escapes: false, expand_templates: false };
+        let expanded_lines = expand_message_lines(&split_message_lines("a\n"), "\r\n", options, &mut TemplateContext::new()).unwrap();
+        let data: Vec<Vec<u8>> = expanded_lines.into_iter().map(|(data, _)| data).collect();
+        assert_eq!(data, vec![b"a\r\n".to_vec(), b"\r\n".to_vec()]);
+    }
+
+    #[test]
+    fn crc16_modbus_matches_known_vectors() {
+        assert_eq!(get_crc16_modbus(b"123456789"), 0x4B37);
+        assert_eq!(get_crc16_modbus(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]), 0xCDC5);
+        assert_eq!(get_crc16_modbus(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn checksums_are_appended_in_hex_messages() {
+        assert_eq!(expand("01 03 00 00 00 0A {crc16_modbus}", true).unwrap(),
+            vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]);
+        assert_eq!(expand("01 02 04 {xor}", true).unwrap(), vec![0x01, 0x02, 0x04, 0x07]);
+        assert_eq!(expand("FF 02 {sum8}", true).unwrap(), vec![0xFF, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn checksum_ranges() {
+        let message = [0x02, 0x10, 0x20, 0x30];
+        assert_eq!(get_checksum_range(&message, None).unwrap(), &message);
+        assert_eq!(get_checksum_range(&message, Some("1")).unwrap(), &message[1..]);
+        assert_eq!(get_checksum_range(&message, Some("1..")).unwrap(), &message[1..]);
+        assert_eq!(get_checksum_range(&message, Some("1..3")).unwrap(), &message[1..3]);
+        assert_eq!(get_checksum_range(&message, Some("0..0")).unwrap(), &[] as &[u8]);
+        assert!(get_checksum_range(&message, Some("3..1")).is_err());
+        assert!(get_checksum_range(&message, Some("0..5")).is_err());
+        assert!(get_checksum_range(&message, Some("a")).is_err());
+
+        // e.g., STX, then the payload's XOR
+        assert_eq!(expand("02 10 20 30 {xor:1}", true).unwrap(), vec![0x02, 0x10, 0x20, 0x30, 0x00]);
+        assert_eq!(expand("02 10 20 30 {sum8:1..3}", true).unwrap(), vec![0x02, 0x10, 0x20, 0x30, 0x30]);
+    }
+
+    #[test]
+    fn escapes_become_bytes() {
+        assert_eq!(unescape_to_bytes(r"AT\r\n").unwrap(), b"AT\r\n");
+        assert_eq!(unescape_to_bytes(r"\x1b[0m\0\e\t").unwrap(), b"\x1b[0m\x00\x1b\t");
+        assert_eq!(unescape_to_bytes(r"\\\{\}").unwrap(), b"\\{}");
+        assert_eq!(unescape_to_bytes("é").unwrap(), "é".as_bytes());
+        assert!(unescape_to_bytes(r"\x1").is_err());
+        assert!(unescape_to_bytes(r"\xZZ").is_err());
+        assert!(unescape_to_bytes(r"\q").is_err());
+        assert!(unescape_to_bytes("\\").is_err());
+    }
+
+    #[test]
+    fn braces_in_text_messages() {
+        assert_eq!(expand("{{json}", false).unwrap(), b"{json}");
+        assert_eq!(expand(r"\{literal\}", false).unwrap(), b"{literal}");
+        assert_eq!(expand("n={counter}", false).unwrap(), b"n=0");
+        assert!(expand("{counter", false).is_err());
+        assert!(expand("{unknown}", false).is_err());
+    }
+
+    #[test]
+    fn counter_goes_up_once_per_message() {
+        let options = SendTextOptions { is_hex: false, parse_escapes: false, expand_templates: true };
+        let mut context = TemplateContext::new();
+        let lines = expand_message_lines(&["a{counter}", "b{counter}"], "\r\n", options, &mut context).unwrap();
+        assert_eq!(lines, vec![(b"a0\r\n".to_vec(), String::from("a0")), (b"b0\r\n".to_vec(), String::from("b0"))]);
+        let lines = expand_message_lines(&["{counter}"], "", options, &mut context).unwrap();
+        assert_eq!(lines[0].0, b"1");
+    }
+
+    #[test]
+    fn hex_text_is_checked_around_placeholders() {
+        assert!(check_hex_text("01 03 00 00 00 0A {crc16_modbus}").is_ok());
+        assert!(check_hex_text("{counter} 01 {random:2} 02").is_ok());
+        assert!(check_hex_text("01 0G {crc16_modbus}").is_err());
+        assert!(check_hex_text("01 {crc16_modbus").is_err());
+    }
+}
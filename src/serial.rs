@@ -1,14 +1,15 @@
 
 
-use serialport5::SerialPort;
+use serialport5::{FlowControl, SerialPort};
 use std::{error::Error, time::Duration};
 
 use std::io::{Read, Write};
 
 /// Binds a serial port, returning access to it.
-pub fn bind_serial_port(serial_port: &str, baud_rate: u32) -> Result<Box<SerialPort>, Box<dyn Error>> {
+pub fn bind_serial_port(serial_port: &str, baud_rate: u32, flow_control: FlowControl) -> Result<Box<SerialPort>, Box<dyn Error>> {
     let mut port = SerialPort::builder()
         .baud_rate(baud_rate)
+        .flow_control(flow_control)
         .read_timeout(Some(Duration::from_millis(10)))
         .open(&serial_port)?;
     
@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config_file::get_workspace_dir;
+use crate::send_template::{check_hex_text, split_message_lines};
+
+/// Name of the snippets file within the workspace directory.
+pub const SNIPPETS_FILE_NAME: &str = "snippets.toml";
+
+/// A named message that can be sent from the snippet picker (Ctrl+P), or with a function key.
+pub struct Snippet {
+    pub name: String,
+    pub data: String,
+    pub is_hex: bool,
+
+    /// EOL sent after each line. None uses the session's EOL (for text), or nothing (for hex).
+    pub end_of_line: Option<String>,
+
+    /// How long to wait after sending this, before sending anything else.
+    pub delay_after: Duration,
+
+    /// Function key number (1 to 12) that sends this snippet.
+    pub function_key: Option<u8>,
+}
+
+/// The contents of a snippets file.
+///
+/// ```toml
+/// [[snippets]]
+/// name = "Reset"
+/// data = "AT+RST"
+/// eol = "\r\n" # optional, defaults to the session's EOL
+/// delay_ms = 500 # optional, wait this long before sending anything else
+/// key = "F1" # optional
+///
+/// [[snippets]]
+/// name = "Read holding registers"
+/// data = "01 03 00 00 00 0A {crc16_modbus}"
+/// mode = "hex" # "text" (default) or "hex"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SnippetsFile {
+    #[serde(default)]
+    snippets: Vec<SnippetConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnippetConfig {
+    name: String,
+    data: String,
+    mode: Option<String>,
+    eol: Option<String>,
+    delay_ms: Option<u64>,
+    key: Option<String>,
+}
+
+impl Snippet {
+    fn from_config(config: &SnippetConfig) -> Result<Self, Box<dyn Error>> {
+        let is_hex = match config.mode.as_deref() {
+            None | Some("text") => false,
+            Some("hex") => true,
+            Some(other) => return Err(format!("Invalid mode '{}' for snippet '{}' (expected \"text\" or \"hex\")",
+                other, config.name).into()),
+        };
+        if is_hex {
+            for line in split_message_lines(&config.data) {
+                check_hex_text(line)
+                    .map_err(|e| format!("Invalid hex data for snippet '{}': {}", config.name, e))?;
+            }
+        }
+        let function_key = match &config.key {
+            Some(key) => Some(parse_function_key(key)
+                .ok_or_else(|| format!("Invalid key '{}' for snippet '{}' (expected F1 to F12)", key, config.name))?),
+            None => None,
+        };
+        Ok(Self {
+            name: config.name.clone(),
+            data: config.data.clone(),
+            is_hex,
+            end_of_line: config.eol.clone(),
+            delay_after: Duration::from_millis(config.delay_ms.unwrap_or(0)),
+            function_key,
+        })
+    }
+
+    /// EOL sent after each line: the snippet's own, or else the session's (for text) or nothing (for hex).
+    pub fn get_end_of_line<'a>(&'a self, session_end_of_line: &'a str) -> &'a str {
+        match (&self.end_of_line, self.is_hex) {
+            (Some(end_of_line), _) => end_of_line,
+            (None, true) => "",
+            (None, false) => session_end_of_line,
+        }
+    }
+}
+
+/// Loads the snippets from `path`. A missing file just means there are no snippets.
+pub fn load_snippets(path: &Path) -> Result<Vec<Snippet>, Box<dyn Error>> {
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading snippets file '{}': {}", path.display(), e))?;
+    let snippets_file: SnippetsFile = toml::from_str(&contents)
+        .map_err(|e| format!("Error parsing snippets file '{}': {}", path.display(), e))?;
+    let mut snippets: Vec<Snippet> = Vec::new();
+    for snippet_config in snippets_file.snippets.iter() {
+        snippets.push(Snippet::from_config(snippet_config)?);
+    }
+    Ok(snippets)
+}
+
+/// Path to the snippets file in the workspace directory (whether it exists or not).
+pub fn get_workspace_snippets_file() -> PathBuf {
+    get_workspace_dir().join(SNIPPETS_FILE_NAME)
+}
+
+/// Parses "F1" to "F12" into the function key number.
+fn parse_function_key(key: &str) -> Option<u8> {
+    let num: u8 = key.strip_prefix('F').or_else(|| key.strip_prefix('f'))?.parse().ok()?;
+    match num {
+        1..=12 => Some(num),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex_snippet_config(data: &str) -> SnippetConfig {
+        SnippetConfig {
+            name: String::from("test"),
+            data: String::from(data),
+            mode: Some(String::from("hex")),
+            eol: None,
+            delay_ms: None,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn hex_snippets_are_checked_at_load() {
+        assert!(Snippet::from_config(&hex_snippet_config("01 03 00 00 00 0A {crc16_modbus}")).is_ok());
+        assert!(Snippet::from_config(&hex_snippet_config("01 03\n0G")).is_err());
+        assert!(Snippet::from_config(&hex_snippet_config("01 3")).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use chrono::TimeZone;
+
+use crate::app::{AppConfig, TransferLogEntry, TransferLogType};
+
+/// Number of entries per block in the spool file's index.
+/// The index (the offset of the first entry of each block) is itself a file, so memory use doesn't grow with the spool.
+const SPOOL_BLOCK_SIZE: usize = 256;
+
+/// Max number of spooled entries covered by the filter and the search (on top of the entries in memory),
+/// so that their indices (and a rescan) don't grow with the spool file.
+const MAX_INDEXED_SPOOLED_ENTRIES: usize = 100_000;
+
+/// Max number of entries the filter or the search scans per update (i.e., per frame). A rescan of a long
+/// scrollback is spread over several frames, so the UI stays responsive while typing a filter or query.
+pub const MAX_ENTRIES_SCANNED_PER_UPDATE: usize = 20_000;
+
+/// The transfer log (scrollback) of the Main screen.
+///
+/// Entries are addressed by their index since the start of the session, which never changes.
+/// Only the newest `max_entries_in_memory` entries are kept in memory. Older entries are either
+/// moved to the spool file (if enabled), where they can still be read, or dropped.
+pub struct TransferLog {
+    entries: VecDeque<TransferLogEntry>,
+
+    /// Index of `entries[0]`.
+    first_in_memory_idx: usize,
+
+    max_entries_in_memory: usize,
+    spool: Option<TransferLogSpool>,
+
+    /// Set by a failed spool write (in `push`) or read (in `get`, hence the RefCell).
+    spool_error_message: RefCell<Option<String>>,
+}
+
+impl TransferLog {
+    pub fn new(max_entries_in_memory: usize, spool: Option<TransferLogSpool>) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            first_in_memory_idx: 0,
+            max_entries_in_memory: max_entries_in_memory.max(1),
+            spool,
+            spool_error_message: RefCell::new(None),
+        }
+    }
+
+    /// Creates an empty log, with the scrollback limits from the config.
+    pub fn from_config(app_config: &AppConfig) -> Result<Self, Box<dyn Error>> {
+        let spool = match app_config.scrollback_spill_to_disk {
+            true => Some(TransferLogSpool::new(app_config.scrollback_spool_dir.clone())?),
+            false => None,
+        };
+        Ok(TransferLog::new(app_config.scrollback_max_lines_in_memory, spool))
+    }
+
+    /// Total number of entries added this session (i.e., one past the index of the newest entry).
+    pub fn len(&self) -> usize {
+        self.first_in_memory_idx + self.entries.len()
+    }
+
+    /// Index of the oldest entry that can still be read (from memory or the spool).
+    pub fn first_available_idx(&self) -> usize {
+        match &self.spool {
+            Some(_) => 0, // the spool has every entry that's been moved out of memory
+            None => self.first_in_memory_idx,
+        }
+    }
+
+    /// Index of the oldest entry covered by the filter and the search: every entry in memory, plus the
+    /// newest `MAX_INDEXED_SPOOLED_ENTRIES` entries in the spool.
+    pub fn first_indexed_idx(&self) -> usize {
+        self.first_available_idx().max(self.first_in_memory_idx.saturating_sub(MAX_INDEXED_SPOOLED_ENTRIES))
+    }
+
+    /// Index of the first entry that may still change (the last line, if it's still being received).
+    /// All entries before this index are final.
+    pub fn first_mutable_idx(&self) -> usize {
+        match self.entries.back() {
+            Some(last_entry) if !last_entry.is_final => self.len() - 1,
+            _ => self.len(),
+        }
+    }
+
+    /// Gets an entry, either borrowed from memory or read back from the spool file.
+    pub fn get(&self, idx: usize) -> Option<Cow<'_, TransferLogEntry>> {
+        if idx >= self.first_in_memory_idx {
+            return self.entries.get(idx - self.first_in_memory_idx).map(Cow::Borrowed);
+        }
+        match &self.spool {
+            Some(spool) => match spool.get(idx) {
+                Ok(entry) => entry.map(Cow::Owned),
+                Err(e) => {
+                    // the spool was written by us, so this is unexpected; the entry shows up as missing
+                    *self.spool_error_message.borrow_mut() = Some(format!("Error reading from scrollback spool file: {}", e));
+                    None
+                }
+            },
+            None => None,
+        }
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut TransferLogEntry> {
+        self.entries.back_mut()
+    }
+
+    /// Adds an entry, moving the oldest entries out of memory if over the limit.
+    pub fn push(&mut self, entry: TransferLogEntry) {
+        self.entries.push_back(entry);
+
+        while self.entries.len() > self.max_entries_in_memory {
+            let old_entry = self.entries.pop_front().expect("Log can't be empty here");
+            if let Some(spool) = &mut self.spool {
+                if let Err(e) = spool.append(&old_entry) {
+                    // keep going without the spool, rather than failing to show new data
+                    *self.spool_error_message.get_mut() = Some(format!(
+                        "Error writing to scrollback spool file (older lines are no longer kept): {}", e));
+                    self.spool = None;
+                }
+            }
+            self.first_in_memory_idx += 1;
+        }
+    }
+
+    /// Takes the error message from the last failed spool write or read, if any.
+    pub fn take_spool_error_message(&mut self) -> Option<String> {
+        self.spool_error_message.get_mut().take()
+    }
+}
+
+/// On-disk storage for the entries that have been moved out of memory.
+/// The file is deleted when the spool is dropped.
+pub struct TransferLogSpool {
+    file: File,
+    file_path: PathBuf,
+
+    /// File offset (u64) of the first entry of each block of `SPOOL_BLOCK_SIZE` entries.
+    index_file: File,
+    index_file_path: PathBuf,
+
+    /// Number of entries in the spool. These are the first entries of the transfer log (i.e., indices 0..entry_count).
+    entry_count: usize,
+    write_offset: u64,
+
+    /// The most recently read block, as (block number, entries).
+    read_cache: RefCell<Option<(usize, Vec<TransferLogEntry>)>>,
+}
+
+impl TransferLogSpool {
+    /// Creates a new spool file in `spool_dir` (or the system temp directory).
+    pub fn new(spool_dir: Option<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let spool_dir = spool_dir.unwrap_or_else(std::env::temp_dir);
+        let file_path = spool_dir.join(format!("baud-boss-spool-{}-{}.bin",
+            std::process::id(), chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        let file = File::options().read(true).write(true).create_new(true).open(&file_path)
+            .map_err(|e| format!("Error creating scrollback spool file '{}': {}", file_path.display(), e))?;
+        let index_file_path = file_path.with_extension("idx");
+        let index_file = match File::options().read(true).write(true).create_new(true).open(&index_file_path) {
+            Ok(index_file) => index_file,
+            Err(e) => {
+                let _ = std::fs::remove_file(&file_path);
+                return Err(format!("Error creating scrollback spool file '{}': {}", index_file_path.display(), e).into());
+            }
+        };
+
+        Ok(Self {
+            file,
+            file_path,
+            index_file,
+            index_file_path,
+            entry_count: 0,
+            write_offset: 0,
+            read_cache: RefCell::new(None),
+        })
+    }
+
+    /// Appends an entry to the end of the spool.
+    /// Record format: log type (u8), is_final (u8), timestamp (i64 microseconds), length (u32), data.
+    fn append(&mut self, entry: &TransferLogEntry) -> std::io::Result<()> {
+        let mut record: Vec<u8> = Vec::with_capacity(14 + entry.data_as_bytes.len());
+        record.push(match entry.log_type {
+            TransferLogType::SerialData => 0,
+            TransferLogType::EchoData => 1,
+            TransferLogType::ErrorData => 2,
+            TransferLogType::EventData => 3,
+            TransferLogType::BreakData => 4,
+            TransferLogType::FramingErrorData => 5,
+            TransferLogType::ParityErrorData => 6,
+        });
+        record.push(entry.is_final as u8);
+        record.extend(entry.timestamp.timestamp_micros().to_le_bytes());
+        record.extend((entry.data_as_bytes.len() as u32).to_le_bytes());
+        record.extend(&entry.data_as_bytes);
+
+        (&self.file).seek(SeekFrom::Start(self.write_offset))?;
+        (&self.file).write_all(&record)?;
+
+        if self.entry_count.is_multiple_of(SPOOL_BLOCK_SIZE) {
+            let block_num = (self.entry_count / SPOOL_BLOCK_SIZE) as u64;
+            (&self.index_file).seek(SeekFrom::Start(block_num * 8))?;
+            (&self.index_file).write_all(&self.write_offset.to_le_bytes())?;
+        }
+        // the cached block is now stale if it's the one being appended to
+        let read_cache = self.read_cache.get_mut();
+        if matches!(read_cache, Some((cached_block_num, _)) if *cached_block_num == self.entry_count / SPOOL_BLOCK_SIZE) {
+            *read_cache = None;
+        }
+        self.write_offset += record.len() as u64;
+        self.entry_count += 1;
+        Ok(())
+    }
+
+    fn get(&self, idx: usize) -> std::io::Result<Option<TransferLogEntry>> {
+        if idx >= self.entry_count {
+            return Ok(None);
+        }
+        let block_num = idx / SPOOL_BLOCK_SIZE;
+
+        let mut read_cache = self.read_cache.borrow_mut();
+        let is_cached = matches!(&*read_cache, Some((cached_block_num, _)) if *cached_block_num == block_num);
+        if !is_cached {
+            let block_entries = self.read_block(block_num)?;
+            *read_cache = Some((block_num, block_entries));
+        }
+        match &*read_cache {
+            Some((_, block_entries)) => Ok(block_entries.get(idx % SPOOL_BLOCK_SIZE).cloned()),
+            None => Ok(None),
+        }
+    }
+
+    fn read_block(&self, block_num: usize) -> std::io::Result<Vec<TransferLogEntry>> {
+        let block_entry_count = SPOOL_BLOCK_SIZE.min(self.entry_count - (block_num * SPOOL_BLOCK_SIZE));
+
+        let mut block_offset = [0_u8; 8];
+        (&self.index_file).seek(SeekFrom::Start(block_num as u64 * 8))?;
+        (&self.index_file).read_exact(&mut block_offset)?;
+
+        (&self.file).seek(SeekFrom::Start(u64::from_le_bytes(block_offset)))?;
+        let mut reader = BufReader::new(&self.file);
+        let mut block_entries: Vec<TransferLogEntry> = Vec::with_capacity(block_entry_count);
+        for _ in 0..block_entry_count {
+            let mut header = [0_u8; 14];
+            reader.read_exact(&mut header)?;
+
+            let log_type = match header[0] {
+                0 => TransferLogType::SerialData,
+                1 => TransferLogType::EchoData,
+                3 => TransferLogType::EventData,
+                4 => TransferLogType::BreakData,
+                5 => TransferLogType::FramingErrorData,
+                6 => TransferLogType::ParityErrorData,
+                _ => TransferLogType::ErrorData,
+            };
+            let is_final = header[1] != 0;
+            let timestamp_micros = i64::from_le_bytes(header[2..10].try_into().expect("Slice is 8 bytes"));
+            let data_len = u32::from_le_bytes(header[10..14].try_into().expect("Slice is 4 bytes")) as usize;
+
+            let mut data = vec![0_u8; data_len];
+            reader.read_exact(&mut data)?;
+
+            let timestamp = chrono::Local.timestamp_micros(timestamp_micros).single()
+                .unwrap_or_else(chrono::Local::now);
+            block_entries.push(TransferLogEntry::new(data, log_type, is_final, timestamp));
+        }
+        Ok(block_entries)
+    }
+}
+
+impl Drop for TransferLogSpool {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.file_path);
+        let _ = std::fs::remove_file(&self.index_file_path);
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::app::TransferLogEntry;
+
+/// Maximum number of entries kept in the cache. Well over a screenful, so scrolling back and forth
+/// near the current view doesn't decode the same entries again.
+const MAX_CACHED_LINES: usize = 4096;
+
+/// Decoded (and wrapped) text of the transfer log entries shown recently on the Main screen, so that
+/// redrawing (every frame) doesn't decode and wrap every visible entry again.
+pub struct TransferLogLineCache {
+    lines: HashMap<usize, CachedLine>,
+
+    /// Incremented for every frame, to know which lines were used recently.
+    frame_num: u64,
+}
+
+struct CachedLine {
+    /// Length of the entry's data when it was decoded. The last entry can still grow, which makes the cached line stale.
+    data_len: usize,
+    text: String,
+
+    /// Byte offset in `text` where each row starts, when wrapped to `row_starts_wrap_width` columns.
+    row_starts: Vec<usize>,
+    row_starts_wrap_width: Option<usize>,
+
+    last_used_frame_num: u64,
+}
+
+impl TransferLogLineCache {
+    pub fn new() -> Self {
+        Self {
+            lines: HashMap::new(),
+            frame_num: 0,
+        }
+    }
+
+    /// Must be called once per frame, before getting any lines.
+    /// Evicts the lines that weren't used in the last frame, if the cache is full.
+    pub fn start_frame(&mut self) {
+        if self.lines.len() > MAX_CACHED_LINES {
+            let last_frame_num = self.frame_num;
+            self.lines.retain(|_, line| line.last_used_frame_num == last_frame_num);
+        }
+        self.frame_num += 1;
+    }
+
+    /// Text of the entry at `entry_idx`, decoding it if it isn't cached (or if it's changed since).
+    pub fn get_text(&mut self, entry_idx: usize, entry: &TransferLogEntry) -> &str {
+        &self.get_line(entry_idx, entry).text
+    }
+
+    /// Text of the entry at `entry_idx`, and the byte offset where each of its rows starts when
+    /// wrapped to `wrap_width` columns. With no wrapping (None), there's a single row.
+    pub fn get_text_and_row_starts(&mut self, entry_idx: usize, entry: &TransferLogEntry, wrap_width: Option<usize>) -> (&str, &[usize]) {
+        let line = self.get_line(entry_idx, entry);
+        if line.row_starts_wrap_width != wrap_width {
+            line.row_starts = match wrap_width {
+                Some(wrap_width) => get_wrapped_row_starts(&line.text, wrap_width),
+                None => vec![0],
+            };
+            line.row_starts_wrap_width = wrap_width;
+        }
+        (&line.text, &line.row_starts)
+    }
+
+    /// Number of rows the entry takes up when wrapped to `wrap_width` columns.
+    pub fn get_row_count(&mut self, entry_idx: usize, entry: &TransferLogEntry, wrap_width: Option<usize>) -> usize {
+        match wrap_width {
+            Some(_) => self.get_text_and_row_starts(entry_idx, entry, wrap_width).1.len(),
+            None => 1,
+        }
+    }
+
+    fn get_line(&mut self, entry_idx: usize, entry: &TransferLogEntry) -> &mut CachedLine {
+        let frame_num = self.frame_num;
+        let line = self.lines.entry(entry_idx)
+            .and_modify(|line| {
+                if line.data_len != entry.data_as_bytes.len() {
+                    *line = CachedLine::new(entry, frame_num);
+                }
+            })
+            .or_insert_with(|| CachedLine::new(entry, frame_num));
+        line.last_used_frame_num = frame_num;
+        line
+    }
+}
+
+impl CachedLine {
+    fn new(entry: &TransferLogEntry, frame_num: u64) -> Self {
+        Self {
+            data_len: entry.data_as_bytes.len(),
+            text: entry.get_data_as_string(),
+            row_starts: vec![0],
+            row_starts_wrap_width: None,
+            last_used_frame_num: frame_num,
+        }
+    }
+}
+
+/// Splits `text` into rows of at most `wrap_width` columns (by display width, so wide characters take
+/// up 2 columns). Returns the byte offset where each row starts; there's always at least one row.
+pub fn get_wrapped_row_starts(text: &str, wrap_width: usize) -> Vec<usize> {
+    let mut row_starts: Vec<usize> = vec![0];
+    let mut row_width: usize = 0;
+    for (byte_idx, c) in text.char_indices() {
+        let char_width = c.width().unwrap_or(0);
+        if row_width + char_width > wrap_width && row_width > 0 {
+            row_starts.push(byte_idx);
+            row_width = 0;
+        }
+        row_width += char_width;
+    }
+    row_starts
+}
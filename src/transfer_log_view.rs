@@ -0,0 +1,422 @@
+use std::collections::VecDeque;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::TransferLogEntry;
+use crate::log_filter::LogFilterState;
+use crate::protocol_decoder::{decode_log_entry, DecoderKind};
+use crate::transfer_log::{TransferLog, MAX_ENTRIES_SCANNED_PER_UPDATE};
+use crate::transfer_log_line_cache::get_wrapped_row_starts;
+
+/// Number of entries per block of wrapped row counts.
+const WRAPPED_ROWS_BLOCK_SIZE: usize = 256;
+
+/// Maps the rows shown on the Main screen to transfer log entries (one row per visible entry).
+///
+/// The mapping is updated incrementally: each update only looks at the entries added since the
+/// last update (plus the last line, if it's still being received), so the cost doesn't grow with
+/// the size of the scrollback. Changing the filter (or how lines are wrapped) triggers a rescan, which
+/// is spread over several updates (`MAX_ENTRIES_SCANNED_PER_UPDATE` entries each). Only the indexed entries
+/// (see `TransferLog::first_indexed_idx`) are scanned, so a long spooled scrollback isn't filtered in full.
+///
+/// When wrapping, an entry can take up several rows on screen. The rows are counted per block of
+/// entries, for the scrollbar (the scroll position itself is still counted in entries).
+pub struct TransferLogView {
+    /// Whether the filter was active as of the last update.
+    is_filtered: bool,
+
+    /// Entry indices of the visible rows (of the indexed entries). Only used while the filter is active;
+    /// otherwise every available entry is a row.
+    filtered_entry_indices: VecDeque<usize>,
+
+    /// Entries before this index have been scanned, and won't change anymore.
+    scanned_final_len: usize,
+
+    /// The filter's generation as of the last scan (None before the first scan).
+    scanned_filter_generation: Option<u64>,
+
+    first_available_idx: usize,
+
+    /// `transfer_log.first_indexed_idx()` as of the last update. The filtered rows, the longest line and the
+    /// wrapped rows only cover the entries from here on.
+    first_indexed_idx: usize,
+
+    /// Whether the last update stopped before the end of the log, to spread a rescan over several updates.
+    is_scan_pending: bool,
+
+    /// (entry index, display width) of the visible lines that could still be the longest one, as lines fall out
+    /// of the scrollback: each is longer than all the lines after it, so the front one is the longest.
+    longest_line_candidates: VecDeque<(usize, usize)>,
+
+    /// Number of rows removed from the top (because they fell out of the scrollback) during the last update.
+    rows_dropped_in_last_update: usize,
+
+    /// Wrap width and inline decoder as of the last scan; with no wrapping, rows aren't counted.
+    wrap_width: Option<usize>,
+    inline_decoder: Option<DecoderKind>,
+
+    /// Wrapped rows of the final visible entries in each block of `WRAPPED_ROWS_BLOCK_SIZE` entries,
+    /// starting with block number `first_wrapped_rows_block_num`.
+    wrapped_rows_blocks: VecDeque<usize>,
+    first_wrapped_rows_block_num: usize,
+
+    /// Wrapped rows of the entries that may still change (the last line).
+    unfinished_wrapped_rows: usize,
+}
+
+impl TransferLogView {
+    pub fn new() -> Self {
+        Self {
+            is_filtered: false,
+            filtered_entry_indices: VecDeque::new(),
+            scanned_final_len: 0,
+            scanned_filter_generation: None,
+            first_available_idx: 0,
+            first_indexed_idx: 0,
+            is_scan_pending: false,
+            longest_line_candidates: VecDeque::new(),
+            rows_dropped_in_last_update: 0,
+            wrap_width: None,
+            inline_decoder: None,
+            wrapped_rows_blocks: VecDeque::new(),
+            first_wrapped_rows_block_num: 0,
+            unfinished_wrapped_rows: 0,
+        }
+    }
+
+    /// Updates the view with the new entries. `wrap_width` is the width lines are wrapped to (None for
+    /// no wrapping), and entries decoded by `inline_decoder` take up a single row.
+    pub fn update(&mut self, transfer_log: &TransferLog, filter: &LogFilterState, wrap_width: Option<usize>, inline_decoder: Option<DecoderKind>) {
+        self.rows_dropped_in_last_update = 0;
+        self.is_filtered = filter.is_active();
+        let first_available_idx = transfer_log.first_available_idx();
+        let first_indexed_idx = transfer_log.first_indexed_idx();
+
+        if (self.scanned_filter_generation != Some(filter.generation))
+                || (self.wrap_width != wrap_width) || (self.inline_decoder != inline_decoder) {
+            // the filter (or how rows are laid out) changed, so rescan the indexed entries
+            self.filtered_entry_indices.clear();
+            self.scanned_final_len = first_indexed_idx;
+            self.longest_line_candidates.clear();
+            self.scanned_filter_generation = Some(filter.generation);
+            self.wrap_width = wrap_width;
+            self.inline_decoder = inline_decoder;
+            self.wrapped_rows_blocks.clear();
+            self.first_wrapped_rows_block_num = first_indexed_idx / WRAPPED_ROWS_BLOCK_SIZE;
+        }
+        else {
+            // drop the rows for the entries that fell out of the scrollback (or out of the indexed entries)
+            match self.is_filtered {
+                true => {
+                    while self.filtered_entry_indices.front().is_some_and(|&entry_idx| entry_idx < first_indexed_idx) {
+                        self.filtered_entry_indices.pop_front();
+                        self.rows_dropped_in_last_update += 1;
+                    }
+                }
+                false => {
+                    self.rows_dropped_in_last_update = first_available_idx.saturating_sub(self.first_available_idx);
+                }
+            }
+            while self.longest_line_candidates.front().is_some_and(|&(entry_idx, _)| entry_idx < first_indexed_idx) {
+                self.longest_line_candidates.pop_front();
+            }
+            while self.first_wrapped_rows_block_num < first_indexed_idx / WRAPPED_ROWS_BLOCK_SIZE {
+                self.wrapped_rows_blocks.pop_front();
+                self.first_wrapped_rows_block_num += 1;
+            }
+            // recount the first block if only some of its entries fell out
+            if let (Some(wrap_width), Some(first_block_rows)) = (wrap_width, self.wrapped_rows_blocks.front_mut()) {
+                if first_indexed_idx > self.first_indexed_idx && !first_indexed_idx.is_multiple_of(WRAPPED_ROWS_BLOCK_SIZE) {
+                    let first_block_end = (self.first_wrapped_rows_block_num + 1) * WRAPPED_ROWS_BLOCK_SIZE;
+                    *first_block_rows = (first_indexed_idx..first_block_end.min(self.scanned_final_len))
+                        .filter_map(|entry_idx| transfer_log.get(entry_idx))
+                        .filter(|entry| !self.is_filtered || filter.is_entry_visible(entry))
+                        .map(|entry| get_entry_row_count(&entry, &entry.get_data_as_string(), wrap_width, inline_decoder))
+                        .sum();
+                }
+            }
+        }
+        self.first_available_idx = first_available_idx;
+        self.first_indexed_idx = first_indexed_idx;
+        self.scanned_final_len = self.scanned_final_len.max(first_indexed_idx);
+
+        // re-check the entries that may have changed since the last scan (i.e., the last line)
+        while self.filtered_entry_indices.back().is_some_and(|&entry_idx| entry_idx >= self.scanned_final_len) {
+            self.filtered_entry_indices.pop_back();
+        }
+        // (the last line only gets longer, so the lines it replaced as candidates stay replaced)
+        while self.longest_line_candidates.back().is_some_and(|&(entry_idx, _)| entry_idx >= self.scanned_final_len) {
+            self.longest_line_candidates.pop_back();
+        }
+        self.unfinished_wrapped_rows = 0;
+        let first_mutable_idx = transfer_log.first_mutable_idx();
+        let scan_end = transfer_log.len().min(self.scanned_final_len + MAX_ENTRIES_SCANNED_PER_UPDATE);
+        for entry_idx in self.scanned_final_len..scan_end {
+            let Some(entry) = transfer_log.get(entry_idx) else { continue };
+            if self.is_filtered {
+                if !filter.is_entry_visible(&entry) {
+                    continue;
+                }
+                self.filtered_entry_indices.push_back(entry_idx);
+            }
+            let entry_text = entry.get_data_as_string();
+            if let Some(wrap_width) = wrap_width {
+                let row_count = get_entry_row_count(&entry, &entry_text, wrap_width, inline_decoder);
+                match entry_idx < first_mutable_idx {
+                    true => {
+                        let block_num = entry_idx / WRAPPED_ROWS_BLOCK_SIZE;
+                        while self.first_wrapped_rows_block_num + self.wrapped_rows_blocks.len() <= block_num {
+                            self.wrapped_rows_blocks.push_back(0);
+                        }
+                        self.wrapped_rows_blocks[block_num - self.first_wrapped_rows_block_num] += row_count;
+                    }
+                    false => self.unfinished_wrapped_rows += row_count,
+                }
+            }
+            let line_length = entry_text.width();
+            while self.longest_line_candidates.back().is_some_and(|&(_, candidate_length)| candidate_length <= line_length) {
+                self.longest_line_candidates.pop_back();
+            }
+            self.longest_line_candidates.push_back((entry_idx, line_length));
+        }
+        // the rest of the entries are scanned in the next updates
+        self.scanned_final_len = first_mutable_idx.min(scan_end);
+        self.is_scan_pending = scan_end < transfer_log.len();
+    }
+
+    /// Whether some entries haven't been scanned yet (after the filter changed), so the rows aren't all there yet.
+    pub fn is_scan_pending(&self) -> bool {
+        self.is_scan_pending
+    }
+
+    pub fn get_row_count(&self, transfer_log: &TransferLog) -> usize {
+        match self.is_filtered {
+            true => self.filtered_entry_indices.len(),
+            false => transfer_log.len() - self.first_available_idx,
+        }
+    }
+
+    /// Index of the log entry shown in `row_idx`.
+    pub fn get_entry_idx(&self, transfer_log: &TransferLog, row_idx: usize) -> Option<usize> {
+        match self.is_filtered {
+            true => self.filtered_entry_indices.get(row_idx).copied(),
+            false => {
+                let entry_idx = self.first_available_idx + row_idx;
+                match entry_idx < transfer_log.len() {
+                    true => Some(entry_idx),
+                    false => None,
+                }
+            }
+        }
+    }
+
+    /// Index of the row showing `entry_idx`, or the next visible entry after it (if it's filtered out).
+    pub fn get_row_idx(&self, entry_idx: usize) -> usize {
+        match self.is_filtered {
+            true => self.filtered_entry_indices.partition_point(|&idx| idx < entry_idx),
+            false => entry_idx.saturating_sub(self.first_available_idx),
+        }
+    }
+
+    pub fn get_longest_line_length(&self) -> usize {
+        self.longest_line_candidates.front().map(|&(_, line_length)| line_length).unwrap_or(0)
+    }
+
+    pub fn get_rows_dropped_in_last_update(&self) -> usize {
+        self.rows_dropped_in_last_update
+    }
+
+    /// Total number of rows on screen, with wrapped entries taking up several rows (when wrapping).
+    /// Only the indexed entries are counted, so spooled entries before them take up no rows here.
+    pub fn get_wrapped_row_count(&self) -> usize {
+        self.wrapped_rows_blocks.iter().sum::<usize>() + self.unfinished_wrapped_rows
+    }
+
+    /// Number of rows on screen (when wrapping) before the block of entries `entry_idx` is in, and the index of that
+    /// block's first entry. The rows of the entries between the two are left to the caller (who may have them cached).
+    pub fn get_wrapped_rows_before_block(&self, entry_idx: usize) -> (usize, usize) {
+        let block_num = (entry_idx / WRAPPED_ROWS_BLOCK_SIZE).max(self.first_wrapped_rows_block_num);
+        let rows_before_block = self.wrapped_rows_blocks.iter()
+            .take(block_num - self.first_wrapped_rows_block_num)
+            .sum();
+        (rows_before_block, block_num * WRAPPED_ROWS_BLOCK_SIZE)
+    }
+}
+
+/// Number of rows an entry takes up on screen when wrapped to `wrap_width` columns.
+fn get_entry_row_count(entry: &TransferLogEntry, entry_text: &str, wrap_width: usize, inline_decoder: Option<DecoderKind>) -> usize {
+    match decode_log_entry(inline_decoder, entry) {
+        // a decoded frame shown inline is a single row
+        Some(_) => 1,
+        None => get_wrapped_row_starts(entry_text, wrap_width).len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{TransferLogEntry, TransferLogType};
+
+    /// A spool directory of its own for a test, removed when the test ends (even if it fails).
+    struct TempSpoolDir(std::path::PathBuf);
+
+    impl TempSpoolDir {
+        fn new(test_name: &str) -> Self {
+            let dir_path = std::env::temp_dir().join(format!("baud-boss-test-{}-{}", test_name, std::process::id()));
+            std::fs::create_dir_all(&dir_path).unwrap();
+            Self(dir_path)
+        }
+    }
+
+    impl Drop for TempSpoolDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn update_until_scanned(log_view: &mut TransferLogView, transfer_log: &TransferLog, filter: &LogFilterState) {
+        log_view.update(transfer_log, filter, None, None);
+        while log_view.is_scan_pending() {
+            log_view.update(transfer_log, filter, None, None);
+        }
+    }
+
+    fn push_line(transfer_log: &mut TransferLog, text: &str) {
+        transfer_log.push(TransferLogEntry::new(text.as_bytes().to_vec(), TransferLogType::SerialData, true, chrono::Local::now()));
+    }
+
+    #[test]
+    fn longest_line_length_shrinks_when_lines_are_evicted() {
+        let mut transfer_log = TransferLog::new(3, None);
+        let filter = LogFilterState::new();
+        let mut log_view = TransferLogView::new();
+
+        push_line(&mut transfer_log, "a long line");
+        push_line(&mut transfer_log, "short");
+        push_line(&mut transfer_log, "medium");
+        log_view.update(&transfer_log, &filter, None, None);
+        assert_eq!(log_view.get_longest_line_length(), 11);
+
+        push_line(&mut transfer_log, "x");
+        log_view.update(&transfer_log, &filter, None, None);
+        assert_eq!(log_view.get_rows_dropped_in_last_update(), 1);
+        assert_eq!(log_view.get_row_count(&transfer_log), 3);
+        assert_eq!(log_view.get_longest_line_length(), 6);
+
+        push_line(&mut transfer_log, "y");
+        push_line(&mut transfer_log, "z");
+        log_view.update(&transfer_log, &filter, None, None);
+        assert_eq!(log_view.get_longest_line_length(), 1);
+    }
+
+    #[test]
+    fn wrapped_rows_are_counted_as_lines_come_and_go() {
+        let mut transfer_log = TransferLog::new(3, None);
+        let filter = LogFilterState::new();
+        let mut log_view = TransferLogView::new();
+
+        push_line(&mut transfer_log, "abcdefghij");
+        push_line(&mut transfer_log, "ab");
+        push_line(&mut transfer_log, "abcde");
+        log_view.update(&transfer_log, &filter, Some(4), None);
+        assert_eq!(log_view.get_wrapped_row_count(), 3 + 1 + 2);
+
+        push_line(&mut transfer_log, "x");
+        log_view.update(&transfer_log, &filter, Some(4), None);
+        assert_eq!(log_view.get_wrapped_row_count(), 1 + 2 + 1);
+        assert_eq!(log_view.get_wrapped_rows_before_block(3), (0, 0));
+
+        // the last line can still grow
+        transfer_log.push(TransferLogEntry::new(b"abcd".to_vec(), TransferLogType::SerialData, false, chrono::Local::now()));
+        log_view.update(&transfer_log, &filter, Some(4), None);
+        assert_eq!(log_view.get_wrapped_row_count(), 2 + 1 + 1);
+        transfer_log.last_mut().unwrap().data_as_bytes.extend(b"efgh");
+        log_view.update(&transfer_log, &filter, Some(4), None);
+        assert_eq!(log_view.get_wrapped_row_count(), 2 + 1 + 2);
+
+        // a new wrap width recounts everything
+        log_view.update(&transfer_log, &filter, Some(8), None);
+        assert_eq!(log_view.get_wrapped_row_count(), 1 + 1 + 1);
+    }
+
+    #[test]
+    fn filter_rescan_is_spread_over_updates() {
+        let entry_count = MAX_ENTRIES_SCANNED_PER_UPDATE * 2 + 10;
+        let mut transfer_log = TransferLog::new(entry_count, None);
+        let mut filter = LogFilterState::new();
+        let mut log_view = TransferLogView::new();
+        for line_num in 0..entry_count {
+            push_line(&mut transfer_log, &format!("line {}", line_num));
+        }
+        update_until_scanned(&mut log_view, &transfer_log, &filter);
+
+        filter.expression = "line 1".to_string();
+        filter.compile_expression();
+        log_view.update(&transfer_log, &filter, None, None);
+        assert!(log_view.is_scan_pending());
+        let partial_row_count = log_view.get_row_count(&transfer_log);
+        assert_eq!(log_view.get_entry_idx(&transfer_log, partial_row_count - 1), Some(19999));
+
+        // new lines wait for the rescan to get to them
+        push_line(&mut transfer_log, "line 1 (new)");
+        log_view.update(&transfer_log, &filter, None, None);
+        log_view.update(&transfer_log, &filter, None, None);
+        assert!(!log_view.is_scan_pending());
+        let expected_rows: Vec<usize> = (transfer_log.first_available_idx()..transfer_log.len())
+            .filter(|&entry_idx| transfer_log.get(entry_idx).unwrap().get_data_as_string().contains("line 1"))
+            .collect();
+        let rows: Vec<usize> = (0..log_view.get_row_count(&transfer_log))
+            .map(|row_idx| log_view.get_entry_idx(&transfer_log, row_idx).unwrap())
+            .collect();
+        assert_eq!(rows, expected_rows);
+    }
+
+    #[test]
+    fn spooled_lines_stay_readable() {
+        let spool_dir = TempSpoolDir::new("spooled_lines_stay_readable");
+        let spool = crate::transfer_log::TransferLogSpool::new(Some(spool_dir.0.clone())).unwrap();
+        let mut transfer_log = TransferLog::new(10, Some(spool));
+        for line_num in 0..1000 {
+            push_line(&mut transfer_log, &format!("line {}", line_num));
+        }
+        assert_eq!(transfer_log.first_available_idx(), 0);
+        for idx in [0, 255, 256, 257, 600, 989, 990, 999] {
+            assert_eq!(transfer_log.get(idx).unwrap().data_as_bytes, format!("line {}", idx).into_bytes());
+        }
+        assert!(transfer_log.get(1000).is_none());
+        assert!(transfer_log.take_spool_error_message().is_none());
+    }
+
+    #[test]
+    fn filter_only_covers_the_newest_spooled_lines() {
+        let spool_dir = TempSpoolDir::new("filter_only_covers_the_newest_spooled_lines");
+        let spool = crate::transfer_log::TransferLogSpool::new(Some(spool_dir.0.clone())).unwrap();
+        let mut transfer_log = TransferLog::new(10, Some(spool));
+        let mut filter = LogFilterState::new();
+        let mut log_view = TransferLogView::new();
+        let entry_count = 100_000 + 10 + 500;
+        for line_num in 0..entry_count {
+            push_line(&mut transfer_log, &format!("line {}", line_num));
+        }
+        assert_eq!(transfer_log.first_available_idx(), 0);
+        assert_eq!(transfer_log.first_indexed_idx(), 500);
+
+        filter.expression = "0".to_string();
+        filter.compile_expression();
+        update_until_scanned(&mut log_view, &transfer_log, &filter);
+        assert_eq!(log_view.get_entry_idx(&transfer_log, 0), Some(500));
+
+        // the window moves along with new lines
+        for line_num in entry_count..(entry_count + 10) {
+            push_line(&mut transfer_log, &format!("line {}", line_num));
+        }
+        log_view.update(&transfer_log, &filter, None, None);
+        assert_eq!(log_view.get_rows_dropped_in_last_update(), 10);
+        assert_eq!(log_view.get_entry_idx(&transfer_log, 0), Some(510));
+
+        // unfiltered, every spooled line is still a row
+        filter.clear();
+        log_view.update(&transfer_log, &filter, None, None);
+        assert_eq!(log_view.get_row_count(&transfer_log), entry_count + 10);
+    }
+}
@@ -1,17 +1,25 @@
 use std::cmp::min;
 
-use crate::app::{App, CurrentScreen, MainScreenActiveRegion, TransferLogType, ScrollPosition};
+use crate::app::{App, COMMON_BAUD_RATES, CurrentScreen, MainScreenActiveRegion, TransferLogType, ScrollPosition, TimestampMode};
 use crate::tui_list_state_tracker::ListStateTracker;
+use crate::highlight::get_highlighted_rows;
+use crate::log_selection::{LogSelection, RenderedLogLayout, RenderedLogRow};
+use crate::snippets::get_workspace_snippets_file;
+use crate::file_transfer::FileTransferState;
+use crate::modem_lines::ModemLines;
+use crate::flow_control::FlowControl;
+use crate::protocol_decoder::{decode_log_entry, DecodedFrame, DecodedView, FrameValidity};
 
-use ratatui::layout::{Alignment, Constraint, Direction, Layout, Margin};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Margin, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::symbols::scrollbar;
 use ratatui::text::{Line, Masked, Text};
 use ratatui::widgets::block::{Position, Title};
-use ratatui::widgets::{List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation};
+use ratatui::widgets::{Clear, Gauge, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation};
 use ratatui::widgets::{Block, Borders, Wrap};
 use ratatui::Frame;
 use ratatui::text::Span;
+use unicode_width::UnicodeWidthStr;
 
 // traits
 use ratatui::style::Stylize;
@@ -79,6 +87,7 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
             
             frame.render_widget(title, general_chunks[0]);
             frame.render_stateful_widget(port_select_block, general_chunks[1], &mut app.pick_serial_port_list_state.state);
+            app.pick_serial_port_list_rect = general_chunks[1];
 
 
             // TODO: update keybinding coloring, update based on which screen is active
@@ -104,44 +113,52 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                 .block(Block::default().borders(Borders::ALL).title(select_baud_rate_title_text))
                 .wrap(Wrap { trim: true });
 
+            let baud_list_items: Vec<ListItem> = COMMON_BAUD_RATES.iter().map(|baud_rate| {
+                ListItem::new(Span::raw(format!("{}", baud_rate)))
+            }).collect();
+            let baud_list = List::new(baud_list_items)
+                .block(Block::default().borders(Borders::ALL).title("Common baud rates"))
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+                .highlight_symbol("> ");
+
             // TODO: update keybinding coloring, update based on which screen is active
-            let help_paragraph = Paragraph::new("Quit: Ctrl+] or Ctrl+C | Menu: Ctrl+T | Help: Ctrl+H")
+            let help_paragraph = Paragraph::new("Quit: Ctrl+] or Ctrl+C | Menu: Ctrl+T | Help: Ctrl+H | Up/Down: pick a common baud rate")
                 .block(Block::default().borders(Borders::ALL).title("Help"))
                 .wrap(Wrap { trim: true });
 
+            let baud_rate_chunks = Layout::vertical([
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ])
+            .split(general_chunks[1]);
+
             frame.render_widget(title, general_chunks[0]);
-            frame.render_widget(baud_input_paragraph, general_chunks[1]);
+            frame.render_widget(baud_input_paragraph, baud_rate_chunks[0]);
+            frame.render_stateful_widget(baud_list, baud_rate_chunks[1], &mut app.pick_baud_rate_list_state);
+            app.pick_baud_rate_list_rect = baud_rate_chunks[1];
             frame.render_widget(help_paragraph, general_chunks[2]);
         },
         CurrentScreen::Main => {
             // TODO: add option to show hex-and-ascii side-by-side
 
-            let send_input_text = match app.main_input_cursor_position {
-                Some(cursor_position) => {
-                    let mut input_text = app.main_input.clone();
-                    match app.main_screen_active_region {
-                        MainScreenActiveRegion::Input => {
-                            input_text.insert(cursor_position, get_blinking_cursor('|', ' '));
-                        }
-                        _ => {}
+            let send_input_text = match app.main_screen_active_region {
+                MainScreenActiveRegion::Input => {
+                    let mut input_text = app.main_input.get_text().to_string();
+                    match app.main_input.get_cursor() == input_text.len() {
+                        true => input_text.push(get_blinking_cursor('_', ' ')),
+                        false => input_text.insert(app.main_input.get_cursor(), get_blinking_cursor('|', ' ')),
                     }
                     input_text
-                },
-                None => {
-                    match app.main_screen_active_region {
-                        MainScreenActiveRegion::Input => {
-                            format!("{}{}", app.main_input, get_blinking_cursor('_', ' '))
-                        }
-                        _ => app.main_input.clone()
-                    }
                 }
+                _ => app.main_input.get_text().to_string(),
             };
             // TODO: wrap the EOL character in a box with borders, maybe (and/or highlight the borders when MainScreenActiveRegion::InputEolChoice)
             let eol_as_repr_string = app.app_config.end_of_line
                 .replace("\n", "\\n").replace("\r", "\\r");
-            let send_input_paragraph_lines: Vec<Line> = vec![
-                Line::from(send_input_text),
-            ];
+            let send_input_paragraph_lines: Vec<Line> = send_input_text.split('\n')
+                .map(|line| Line::from(line.to_string()))
+                .collect();
+            let send_input_line_count = send_input_paragraph_lines.len();
             let send_input_paragraph = Paragraph::new(send_input_paragraph_lines)
                 .block(
                     Block::default()
@@ -152,10 +169,24 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                             }
                             _ => Style::default()
                         }) // Set border color to red
-                        .title("Send Data") // TODO: when it overflows, somewhere make sure we're always at the bottom of the content
+                        .title(format!("Send Data{}{}",
+                            // the negative index in the send history, while browsing it (-1 is the last message sent)
+                            match app.main_input_send_history_index {
+                                Some(index) => format!(" [{}]", index as i64 - app.main_input_send_history.len() as i64),
+                                None => String::new(),
+                            },
+                            match send_input_line_count {
+                                1 => String::new(),
+                                _ => format!(" ({} lines, sent {})",
+                                    send_input_line_count,
+                                    app.app_config.multi_line_send_policy.get_display_name()),
+                            }))
                         .title(
                             Title::default()
-                                .content(format!("EOL: '{}'", eol_as_repr_string))
+                                .content(format!("{}{}EOL: '{}'",
+                                    if app.app_config.send_parse_escapes { "Escapes | " } else { "" },
+                                    if app.app_config.send_expand_templates { "Templates | " } else { "" },
+                                    eol_as_repr_string))
                                 .position(Position::Bottom)
                                 .alignment(Alignment::Right)
                                 // .style(match app.main_screen_active_region {
@@ -166,7 +197,6 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                         )
                 )
                 .wrap(Wrap { trim: false });
-            // TODO: if showing history, show the negative index of the history in the "Send Data" text
             // TODO: show if immediate or on-enter
             // TODO: show if hex or ascii
             // TODO: in immediate mode, flash the characters here for a sec after sending (maybe 500ms)
@@ -184,104 +214,365 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                 (total_screen_height as f32 * 0.6).round() as u16,
                 required_send_input_height,
             ).max(3); // 3 is the absolute minimum height for the top element
-            // TODO: deal with case where top_element_height < required_send_input_height here (scrolling indicator, etc.)
+
+            // when the box is capped, scroll it so that the cursor's row is visible
+            let cursor_row_count = Paragraph::new(
+                send_input_text[..app.main_input.get_cursor().min(send_input_text.len())].split('\n')
+                    .map(|line| Line::from(line.to_string()))
+                    .collect::<Vec<Line>>())
+                .wrap(Wrap { trim: false })
+                .line_count(input_box_width)
+                .max(1);
+            let send_input_visible_height = top_element_height.saturating_sub(2) as usize;
+            let send_input_paragraph = send_input_paragraph
+                .scroll((cursor_row_count.saturating_sub(send_input_visible_height) as u16, 0));
 
             // let debug_str: String = format!("DEBUG: required_lines_for_send_input_paragraph={}, required_send_input_height={}, top_element_height={} \n", 
             //     required_lines_for_send_input_paragraph,
             //     required_send_input_height, top_element_height);
             // app.main_incoming_serial_data.insert_str(0, debug_str.as_str());
+            // the scheduler panel is only shown while there are scheduled sends
+            let scheduler_panel_height: u16 = match app.tx_scheduler.jobs.len() {
+                0 => 0,
+                job_count => (job_count as u16).min(5) + 2,
+            };
             let main_screen_chunks = Layout::vertical([
                 Constraint::Length(top_element_height),
+                Constraint::Length(scheduler_panel_height),
                 Constraint::Min(3),
                 Constraint::Length(3),
             ])
             .split(size);
 
             frame.render_widget(send_input_paragraph, main_screen_chunks[0]);
+            app.main_screen_input_rect = main_screen_chunks[0];
+
+            if scheduler_panel_height > 0 {
+                render_scheduler_panel(frame, app, main_screen_chunks[1]);
+            }
+            app.scheduler_panel_rect = main_screen_chunks[1];
+
+            // the decoder pane (when it's on) takes the right side of the transfer log's space
+            let decoder_pane_width: u16 = match (app.app_config.decoder, app.app_config.decoded_view) {
+                (Some(_), DecodedView::DetailPane) => (main_screen_chunks[2].width / 3).clamp(24, 48),
+                _ => 0,
+            };
+            let transfer_log_chunks = Layout::horizontal([
+                Constraint::Min(20),
+                Constraint::Length(decoder_pane_width),
+            ])
+            .split(main_screen_chunks[2]);
+            let transfer_log_area = transfer_log_chunks[0];
 
             let main_title_text = format!("Port '{}' @ {} baud", app.selected_serial_port.clone().unwrap_or_default(), app.app_config.baud_rate.unwrap_or_default());
 
-            // FIXME: START HERE
-            // need to convert these to Lines, and also get the metadata for the longest line
-            let transfer_log_lines_as_strs: Vec<String> = app.main_screen_transfer_log
-                .iter()
-                .map(|transfer_log_entry| transfer_log_entry.get_data_as_string()).collect();
-            let longest_line_length = transfer_log_lines_as_strs.iter().map(|line| line.len()).max().unwrap_or(0);
-            let transfer_log_lines: Vec<Line> = app.main_screen_transfer_log
-                .iter()
-                .map(|transfer_log_entry| match transfer_log_entry.log_type {
-                    TransferLogType::SerialData => {
-                        Line::from(transfer_log_entry.get_data_as_string())
-                            .style(Style::default()) //.fg(Color::Green))
-                    },
-                    TransferLogType::EchoData => {
-                        Line::from(transfer_log_entry.get_data_as_string())
-                            .style(Style::default().fg(Color::LightBlue))
-                    },
-                    TransferLogType::ErrorData => {
-                        Line::from(transfer_log_entry.get_data_as_string())
-                            .style(Style::default().fg(Color::Red))
-                    },
-                }).collect();
+            // all gutters in a mode have the same width
+            let timestamp_gutter_width = get_timestamp_gutter_string(
+                app.app_config.timestamp_mode, app.session_start_time, None, app.session_start_time).len();
 
-            app.main_screen_vert_scroll_state = app.main_screen_vert_scroll_state
-                .content_length(transfer_log_lines.len());
-            app.main_screen_horiz_scroll_state = app.main_screen_horiz_scroll_state
-                .content_length(longest_line_length);
+            let incoming_data_viewport_height: usize = transfer_log_area.height as usize - 2; // subtract 2 for borders
+            let incoming_data_viewport_width: usize = transfer_log_area.width as usize - 2; // subtract 2 for borders
 
-            // Scrollbar Rendering Examples: https://github.com/ratatui-org/ratatui/blob/main/examples/scrollbar.rs
-            // TODO: prevent scrolling if there's no need to scroll (currently lets you scroll the content fully off the screen)
-                // https://github.com/ratatui-org/ratatui/issues/1017
-            
-            // TODO: add config option for wrapping text in the incoming_data block
-        
-            let transfer_log_paragraph = Paragraph::new(transfer_log_lines)
-                // .gray()
-                .block(Block::default().borders(Borders::ALL).title(main_title_text.bold()))
-                .scroll((
-                    app.main_screen_vert_scroll_val as u16,
-                    app.main_screen_horiz_scroll_val as u16));
-
-            // TODO: refactor this clamping into a function, and try to apply it at the keyhandler level remove the jitter/vibrate
-            // Right before rendering, perform a check on the vertical scroll position.
-            // Clamp the value so the last line is at the bottom of the block.
-            let incoming_data_paragraph_width = size.width - 2; // subtract 2 for the borders
-            let incoming_data_paragraph_lines_count = transfer_log_paragraph.line_count(incoming_data_paragraph_width);
-            let incoming_data_viewport_height: usize = main_screen_chunks[1].height as usize - 2; // subtract 2 for borders
-            let max_vertical_scroll_val = (
-                (incoming_data_paragraph_lines_count as i64)
-                    - (incoming_data_viewport_height as i64)
-                    + 1 // show an empty line at the bottom, why not
-                ).max(0) as usize;
-            if (app.main_screen_vert_scroll_val + incoming_data_viewport_height) > incoming_data_paragraph_lines_count {
+            // when wrapping, each entry takes up one or more rows, and there's no horizontal scrolling
+            // (the vertical scroll position is still counted in entries, so that only the rows near the view are wrapped)
+            let wrap_width: Option<usize> = match app.app_config.line_wrap {
+                true => Some(incoming_data_viewport_width.saturating_sub(timestamp_gutter_width).max(1)),
+                false => None,
+            };
+            if wrap_width.is_some() {
+                app.main_screen_horiz_scroll_val = 0;
+            }
+            let inline_decoder = match app.app_config.decoded_view {
+                DecodedView::Inline => app.app_config.decoder,
+                _ => None,
+            };
+
+            // the filter only hides entries; each visible entry is one row of the view
+            app.main_screen_log_view.update(&app.main_screen_transfer_log, &app.main_screen_filter, wrap_width, inline_decoder);
+            app.main_screen_search.update_matches(&app.main_screen_transfer_log, &app.main_screen_filter);
+            app.main_screen_line_cache.start_frame();
+            let transfer_log = &app.main_screen_transfer_log;
+            let log_view = &app.main_screen_log_view;
+            let row_count = log_view.get_row_count(transfer_log);
+
+            // keep the view on the same lines when old lines fall out of the scrollback
+            if app.main_screen_vert_scroll_pos == ScrollPosition::FinitePosition {
+                app.main_screen_vert_scroll_val = app.main_screen_vert_scroll_val
+                    .saturating_sub(log_view.get_rows_dropped_in_last_update());
+            }
+
+            let longest_line_length = timestamp_gutter_width + log_view.get_longest_line_length();
+
+            // when the filter changes, keep the same log entry at the top of the view
+            if app.main_screen_filter.restore_view_pending {
+                app.main_screen_filter.restore_view_pending = false;
+                if app.main_screen_vert_scroll_pos == ScrollPosition::FinitePosition {
+                    app.main_screen_vert_scroll_val = log_view.get_row_idx(app.main_screen_top_entry_idx);
+                    app.main_screen_vert_scroll_val_at_filter_restore = Some(app.main_screen_vert_scroll_val);
+                }
+            }
+
+            // jump to the current search match (centered vertically), if requested by n/N/Enter
+            if app.main_screen_search.scroll_to_current_match_pending {
+                app.main_screen_search.scroll_to_current_match_pending = false;
+                if let Some(current_match) = app.main_screen_search.get_current_match() {
+                    let match_row_idx = log_view.get_row_idx(current_match.entry_idx);
+                    app.main_screen_vert_scroll_val = match_row_idx.saturating_sub(incoming_data_viewport_height / 2);
+                    app.main_screen_vert_scroll_pos = ScrollPosition::FinitePosition;
+
+                    if let (Some(entry), None) = (transfer_log.get(current_match.entry_idx), wrap_width) {
+                        let entry_text = app.main_screen_line_cache.get_text(current_match.entry_idx, &entry);
+                        let match_col = timestamp_gutter_width + entry_text[..current_match.start].width();
+                        if (match_col < app.main_screen_horiz_scroll_val)
+                                || (match_col >= app.main_screen_horiz_scroll_val + incoming_data_viewport_width) {
+                            app.main_screen_horiz_scroll_val = match_col.saturating_sub(incoming_data_viewport_width / 4);
+                        }
+                    }
+                }
+            }
+
+            // TODO: refactor this clamping into a function, and try to apply it at the keyhandler level
+            // Clamp the vertical scroll position so the last line is at the bottom of the block.
+            let line_cache = &mut app.main_screen_line_cache;
+            let mut get_row_height = |row_idx: usize| -> usize {
+                match log_view.get_entry_idx(transfer_log, row_idx).and_then(|entry_idx| Some((entry_idx, transfer_log.get(entry_idx)?))) {
+                    // a decoded frame shown inline is a single row
+                    Some((_, entry)) if decode_log_entry(inline_decoder, &entry).is_some() => 1,
+                    Some((entry_idx, entry)) => line_cache.get_row_count(entry_idx, &entry, wrap_width),
+                    None => 1,
+                }
+            };
+            // Work backwards from the last entry, to find the first entry that's shown when scrolled to the end.
+            let mut max_vertical_scroll_val = row_count;
+            let mut rows_height_at_end: usize = 0;
+            while max_vertical_scroll_val > 0 {
+                let row_height = get_row_height(max_vertical_scroll_val - 1);
+                if rows_height_at_end + row_height > incoming_data_viewport_height.saturating_sub(1) { // show an empty line at the bottom, why not
+                    break;
+                }
+                rows_height_at_end += row_height;
+                max_vertical_scroll_val -= 1;
+            }
+            // the last entry is taller than the viewport on its own; its end is shown below
+            if max_vertical_scroll_val == row_count && row_count > 0 {
+                max_vertical_scroll_val = row_count - 1;
+            }
+            if app.main_screen_vert_scroll_val >= max_vertical_scroll_val {
                 // do the clamp to the bottom/end of the scroll region
                 app.main_screen_vert_scroll_pos = ScrollPosition::PinnedAtEnd;
-                app.main_screen_vert_scroll_val = max_vertical_scroll_val;
-                // app.main_screen_vert_scroll_state.last(); // TODO: maybe we should use this thing instead? Idk
-            } // FIXME: it vibrates when it's at the bottom and you're typing in the input box
+            }
             if app.main_screen_vert_scroll_pos == ScrollPosition::PinnedAtEnd {
                 app.main_screen_vert_scroll_val = max_vertical_scroll_val;
             }
 
-            // Right before rendering, perform a check on the horizontal scroll position.
-            // Clamp the value so the last character is at the right of the block.
-            let incoming_data_paragraph_longest_line_length = transfer_log_lines_as_strs.iter().map(|line| line.len()).max().unwrap_or(0);
-            let incoming_data_viewport_width: usize = main_screen_chunks[1].width as usize - 2; // subtract 2 for borders
-            if (app.main_screen_horiz_scroll_val + incoming_data_viewport_width) > incoming_data_paragraph_longest_line_length {
+            // the scrollbar counts rows on screen, which (when wrapping) aren't the entries the scroll position counts
+            let (scrollbar_content_length, rows_above_view) = match wrap_width {
+                Some(_) => {
+                    let rows_above_view = match log_view.get_entry_idx(transfer_log, app.main_screen_vert_scroll_val) {
+                        Some(top_entry_idx) => {
+                            let (rows_before_block, block_first_entry_idx) = log_view.get_wrapped_rows_before_block(top_entry_idx);
+                            rows_before_block + (log_view.get_row_idx(block_first_entry_idx)..app.main_screen_vert_scroll_val)
+                                .map(&mut get_row_height)
+                                .sum::<usize>()
+                        }
+                        None => log_view.get_wrapped_row_count(),
+                    };
+                    (log_view.get_wrapped_row_count(), rows_above_view)
+                }
+                None => (row_count, app.main_screen_vert_scroll_val),
+            };
+
+            // Clamp the horizontal scroll position so the last character is at the right of the block.
+            if (app.main_screen_horiz_scroll_val + incoming_data_viewport_width) > longest_line_length {
                 app.main_screen_horiz_scroll_val = (
-                    (incoming_data_paragraph_longest_line_length as i64)
+                    (longest_line_length as i64)
                         - (incoming_data_viewport_width as i64)
                         + 1 // show an empty col at the right, why not
                     ).max(0) as usize;
-            } // FIXME: it vibrates when it's at the right and you're typing in the input box
+            }
+
+            // only build the lines that fit in the viewport; the rest of the log may not even be in memory
+            let first_row_idx = app.main_screen_vert_scroll_val;
+            // delta timestamps are relative to the previous visible line, which is just above the viewport
+            let mut prev_timestamp = match first_row_idx {
+                0 => None,
+                _ => log_view.get_entry_idx(transfer_log, first_row_idx - 1)
+                    .and_then(|entry_idx| transfer_log.get(entry_idx))
+                    .map(|entry| entry.timestamp),
+            };
+            let search_matches = &app.main_screen_search.matches;
+            let current_search_match = app.main_screen_search.get_current_match();
+            let decoder = match app.app_config.decoded_view {
+                DecodedView::Off => None,
+                DecodedView::Inline | DecodedView::DetailPane => app.app_config.decoder,
+            };
+            let mut last_decoded_frame: Option<DecodedFrame> = None; // the last one on screen, for the decoder pane
+            let mut transfer_log_lines: Vec<Line> = Vec::with_capacity(incoming_data_viewport_height);
+            let mut rendered_log_rows: Vec<RenderedLogRow> = Vec::with_capacity(incoming_data_viewport_height);
+            for row_idx in first_row_idx..row_count {
+                if transfer_log_lines.len() >= incoming_data_viewport_height {
+                    break;
+                }
+                let Some(entry_idx) = log_view.get_entry_idx(transfer_log, row_idx) else { break };
+                let Some(transfer_log_entry) = transfer_log.get(entry_idx) else { break };
+
+                // search matches are sorted by entry, so find this entry's matches with a binary search
+                let first_match_idx = search_matches.partition_point(|m| m.entry_idx < entry_idx);
+                let search_overlays: Vec<(usize, usize, Style)> = search_matches[first_match_idx..]
+                    .iter()
+                    .take_while(|m| m.entry_idx == entry_idx)
+                    .map(|m| (m.start, m.end, match Some(*m) == current_search_match {
+                        true => Style::default().fg(Color::Black).bg(Color::LightRed),
+                        false => Style::default().fg(Color::Black).bg(Color::Yellow),
+                    }))
+                    .collect();
+
+                let gutter = get_timestamp_gutter_string(
+                    app.app_config.timestamp_mode,
+                    transfer_log_entry.timestamp,
+                    prev_timestamp,
+                    app.session_start_time,
+                );
+                prev_timestamp = Some(transfer_log_entry.timestamp);
+
+                let decoded_frame = decode_log_entry(decoder, &transfer_log_entry);
+                if let (DecodedView::Inline, Some(decoded_frame)) = (app.app_config.decoded_view, &decoded_frame) {
+                    // selecting the row still copies the raw data
+                    let entry_text_len = app.main_screen_line_cache.get_text(entry_idx, &transfer_log_entry).len();
+                    rendered_log_rows.push(RenderedLogRow { entry_idx, text_start: 0, text_end: entry_text_len });
+                    let mut spans = vec![Span::styled(gutter, Style::default().fg(Color::DarkGray))];
+                    spans.extend(get_decoded_frame_spans(decoded_frame));
+                    transfer_log_lines.push(Line::from(spans).style(get_transfer_log_type_style(&transfer_log_entry.log_type)));
+                    continue;
+                }
+                if decoded_frame.is_some() {
+                    last_decoded_frame = decoded_frame;
+                }
+
+                let (entry_text, row_starts) = app.main_screen_line_cache.get_text_and_row_starts(
+                    entry_idx, &transfer_log_entry, wrap_width);
+                let entry_rows = get_highlighted_rows(
+                    entry_text, &app.app_config.highlight_rules, &search_overlays, row_starts);
+                for (entry_row_num, &text_start) in row_starts.iter().enumerate() {
+                    rendered_log_rows.push(RenderedLogRow {
+                        entry_idx,
+                        text_start,
+                        text_end: row_starts.get(entry_row_num + 1).copied().unwrap_or(entry_text.len()),
+                    });
+                }
+                for (entry_row_num, row_spans) in entry_rows.into_iter().enumerate() {
+                    // wrapped rows are indented past the gutter
+                    let gutter_span = match entry_row_num {
+                        0 => Span::styled(gutter.clone(), Style::default().fg(Color::DarkGray)),
+                        _ => Span::raw(" ".repeat(timestamp_gutter_width)),
+                    };
+                    let mut spans = vec![gutter_span];
+                    spans.extend(row_spans);
+                    transfer_log_lines.push(Line::from(spans).style(get_transfer_log_type_style(&transfer_log_entry.log_type)));
+                }
+            }
+            // when pinned to the end of an entry that's taller than the viewport, show the end of the entry
+            let first_line_offset = match app.main_screen_vert_scroll_pos {
+                ScrollPosition::PinnedAtEnd => transfer_log_lines.len().saturating_sub(incoming_data_viewport_height.saturating_sub(1)),
+                ScrollPosition::FinitePosition => 0,
+            };
+            rendered_log_rows.drain(..first_line_offset.min(rendered_log_rows.len()));
+            let scrollbar_position = rows_above_view + first_line_offset;
+            app.main_screen_log_layout = RenderedLogLayout {
+                area: transfer_log_area.inner(&Margin { vertical: 1, horizontal: 1 }),
+                rows: rendered_log_rows,
+                gutter_width: timestamp_gutter_width,
+                horiz_scroll_val: app.main_screen_horiz_scroll_val,
+            };
+
+            app.main_screen_vert_scroll_state = app.main_screen_vert_scroll_state
+                .content_length(scrollbar_content_length);
+            app.main_screen_horiz_scroll_state = app.main_screen_horiz_scroll_state
+                .content_length(match wrap_width {
+                    Some(_) => 0, // nothing to scroll
+                    None => longest_line_length,
+                });
+
+            // Scrollbar Rendering Examples: https://github.com/ratatui-org/ratatui/blob/main/examples/scrollbar.rs
+        
+            let transfer_log_paragraph = Paragraph::new(transfer_log_lines)
+                // .gray()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(main_title_text.bold())
+                        .title(
+                            Title::default()
+                                .content(get_modem_lines_strip(&app.modem_lines, app.app_config.flow_control, app.app_config.rs485.is_some(), app.get_tx_pause_reason()))
+                                .alignment(Alignment::Center)
+                        )
+                        .title(
+                            Title::default()
+                                .content(match (&app.main_screen_search.error_message, app.main_screen_search.is_active()) {
+                                    (Some(error_message), _) => error_message.clone().red(),
+                                    (None, true) => format!("Search '{}': {}{}",
+                                        app.main_screen_search.query,
+                                        app.main_screen_search.get_match_counter_string(),
+                                        match app.main_screen_search.is_scan_pending() {
+                                            true => " (searching...)",
+                                            false => "",
+                                        }).yellow(),
+                                    (None, false) => "".into(),
+                                })
+                                .alignment(Alignment::Right)
+                        )
+                        .title(
+                            Title::default()
+                                .content(match (&app.main_screen_filter.error_message, app.main_screen_filter.is_active()) {
+                                    (Some(error_message), _) => error_message.clone().red(),
+                                    (None, true) => format!("{} ({}/{} lines{})",
+                                        app.main_screen_filter.get_description(),
+                                        row_count,
+                                        transfer_log.len() - transfer_log.first_indexed_idx(),
+                                        match log_view.is_scan_pending() {
+                                            true => ", filtering...",
+                                            false => "",
+                                        }).yellow(),
+                                    (None, false) => "".into(),
+                                })
+                                .position(Position::Bottom)
+                                .alignment(Alignment::Left)
+                        )
+                        .title(
+                            Title::default()
+                                .content(format!("Timestamps: {} | Wrap: {}{}",
+                                    app.app_config.timestamp_mode.get_display_name(),
+                                    if app.app_config.line_wrap { "On" } else { "Off" },
+                                    match app.app_config.decoder {
+                                        Some(decoder) => format!(" | Decoded ({}): {}", decoder.get_display_name(), app.app_config.decoded_view.get_display_name()),
+                                        None => String::new(),
+                                    }))
+                                .position(Position::Bottom)
+                                .alignment(Alignment::Right)
+                        )
+                )
+                // the lines start at the top of the viewport, so the vertical scroll is only needed for a tall last entry
+                .scroll((first_line_offset as u16, app.main_screen_horiz_scroll_val as u16));
+
+            // remember which entry is at the top of the view, so that changing the filter keeps it there
+            // (unless the view is still where the last filter change put it)
+            if app.main_screen_vert_scroll_val_at_filter_restore != Some(app.main_screen_vert_scroll_val) {
+                app.main_screen_vert_scroll_val_at_filter_restore = None;
+                if let Some(top_entry_idx) = log_view.get_entry_idx(transfer_log, app.main_screen_vert_scroll_val) {
+                    app.main_screen_top_entry_idx = top_entry_idx;
+                }
+            }
 
             // create the scrollbar state
             app.main_screen_vert_scroll_state =
-                app.main_screen_vert_scroll_state.position(app.main_screen_vert_scroll_val);
+                app.main_screen_vert_scroll_state.position(scrollbar_position);
             app.main_screen_horiz_scroll_state =
                 app.main_screen_horiz_scroll_state.position(app.main_screen_horiz_scroll_val);
 
-            frame.render_widget(transfer_log_paragraph, main_screen_chunks[1]);
+            frame.render_widget(transfer_log_paragraph, transfer_log_area);
+            if let Some(log_selection) = app.main_screen_log_selection {
+                render_log_selection(frame, &log_selection, app.main_screen_log_layout.area);
+            }
             frame.render_stateful_widget(
                 Scrollbar::new(ScrollbarOrientation::VerticalRight)
                     .thumb_style(match app.main_screen_active_region {
@@ -294,7 +585,7 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                     .thumb_symbol("░")
                     .hide_when_not_scrollable(true)
                     .include_overscroll(false), // TOOD: check veritcal thumb symbol
-                main_screen_chunks[1],
+                transfer_log_area,
                 &mut app.main_screen_vert_scroll_state,
             );
             frame.render_stateful_widget(
@@ -309,30 +600,215 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
                     .thumb_symbol("░")
                     .hide_when_not_scrollable(true)
                     .include_overscroll(false),
-                main_screen_chunks[1].inner(&Margin {
+                transfer_log_area.inner(&Margin {
                     vertical: 0,
                     horizontal: 1,
                 }),
                 &mut app.main_screen_horiz_scroll_state,
             );
 
-            // TODO: update keybinding coloring, update based on which screen is active
-            let help_paragraph = Paragraph::new("Quit: Ctrl+] or Ctrl+C | Menu: Ctrl+T | Type to prep data | Enter to send")
-                .block(Block::default().borders(Borders::ALL).title("Help"))
-                .wrap(Wrap { trim: true });
-            frame.render_widget(help_paragraph, main_screen_chunks[2]);
+            if decoder_pane_width > 0 {
+                render_decoder_pane(frame, app, last_decoded_frame.as_ref(), transfer_log_chunks[1]);
+            }
+
+            if app.snippet_picker_list_state.is_some() {
+                render_snippet_picker(frame, app, main_screen_chunks[2]);
+            }
+            if app.file_transfer.is_some() {
+                render_file_transfer_popup(frame, app, main_screen_chunks[2]);
+            }
+
+            if app.main_screen_filter.is_editing {
+                let filter = &app.main_screen_filter;
+                let filter_prompt_paragraph = Paragraph::new(format!("{}{}{}",
+                        if filter.is_inverted { "!" } else { "" },
+                        filter.expression, get_blinking_cursor('_', ' ')))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Green))
+                            .title(format!("Filter ({}) | Enter: apply | Esc: clear | Ctrl+R: regex | Ctrl+V: invert | Ctrl+E: {} echo | Ctrl+X: {} errors",
+                                if filter.is_regex { "regex" } else { "plain text" },
+                                if filter.hide_echo { "show" } else { "hide" },
+                                if filter.hide_errors { "show" } else { "hide" }))
+                    );
+                frame.render_widget(filter_prompt_paragraph, main_screen_chunks[3]);
+            }
+            else if app.main_screen_search.is_editing {
+                let search_prompt_paragraph = Paragraph::new(format!("/{}{}",
+                        app.main_screen_search.query, get_blinking_cursor('_', ' ')))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Green))
+                            .title(format!("Search ({}) | Enter: find | Esc: cancel | Ctrl+R: toggle regex",
+                                if app.main_screen_search.is_regex { "regex" } else { "plain text" }))
+                    );
+                frame.render_widget(search_prompt_paragraph, main_screen_chunks[3]);
+            }
+            else if let Some(schedule_spec) = &app.main_input_schedule_prompt {
+                let schedule_prompt_paragraph = Paragraph::new(format!("{}{}", schedule_spec, get_blinking_cursor('_', ' ')))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Green))
+                            .title("Repeat the Send box's text: <interval ms> [x<count>] [hex] | Enter: start | Esc: cancel")
+                    );
+                frame.render_widget(schedule_prompt_paragraph, main_screen_chunks[3]);
+            }
+            else if let Some(send_file_prompt) = &app.send_file_prompt {
+                let send_file_prompt_paragraph = Paragraph::new(format!("{}{}", send_file_prompt.path, get_blinking_cursor('_', ' ')))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Green))
+                            .title(format!("{}Send File ({}) | Enter: send | Tab: complete path | Ctrl+N: change mode | Esc: cancel",
+                                if send_file_prompt.is_auto_detected { "The other side is waiting for a file (rz) | " } else { "" },
+                                send_file_prompt.method.get_display_name()))
+                    );
+                frame.render_widget(send_file_prompt_paragraph, main_screen_chunks[3]);
+            }
+            else if let Some(receive_file_prompt) = &app.receive_file_prompt {
+                let receive_file_prompt_paragraph = Paragraph::new(format!("{}{}", receive_file_prompt.path, get_blinking_cursor('_', ' ')))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Green))
+                            .title(format!("{}Receive File ({}) into {} | Enter: start | Tab: complete path | Ctrl+N: change protocol | Esc: cancel",
+                                if receive_file_prompt.is_auto_detected { "The other side is sending a file (sz) | " } else { "" },
+                                receive_file_prompt.protocol.get_display_name(),
+                                if receive_file_prompt.protocol.has_file_names() { "directory [.]" } else { "file" }))
+                    );
+                frame.render_widget(receive_file_prompt_paragraph, main_screen_chunks[3]);
+            }
+            else if let Some(modbus_prompt) = &app.modbus_prompt {
+                let modbus_prompt_paragraph = Paragraph::new(format!("{}{}", modbus_prompt.spec, get_blinking_cursor('_', ' ')))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Green))
+                            .title("Modbus RTU master: <slave> <function> <address> <count | values...> (e.g., '1 3 0 10', '1 6 100 1234', '1 16 100 1 2 3') | Enter: send | Esc: close")
+                            .title(
+                                Title::default()
+                                    .content(match (&modbus_prompt.pending_request, &modbus_prompt.last_reply) {
+                                        (Some(_), _) => "Waiting for the reply...".yellow(),
+                                        (None, Some(Ok(reply))) => format!("Reply: {}", reply).green(),
+                                        (None, Some(Err(e))) => e.clone().red(),
+                                        (None, None) => "".into(),
+                                    })
+                                    .position(Position::Bottom)
+                                    .alignment(Alignment::Left)
+                            )
+                    );
+                frame.render_widget(modbus_prompt_paragraph, main_screen_chunks[3]);
+            }
+            else if let Some(file_sender) = &app.file_sender {
+                let file_send_gauge = Gauge::default()
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(format!("Sending '{}' ({}) | Ctrl+X: cancel",
+                                file_sender.file_path.display(), file_sender.options.mode.get_display_name()))
+                    )
+                    .gauge_style(Style::default().fg(Color::Green))
+                    .ratio(file_sender.get_progress().clamp(0.0, 1.0))
+                    .label(format!("{} / {} bytes ({:.0}%){}",
+                        file_sender.sent_byte_count, file_sender.total_byte_count, file_sender.get_progress() * 100.0,
+                        if file_sender.is_waiting_for_prompt() { ", waiting for the prompt" } else { "" }));
+                frame.render_widget(file_send_gauge, main_screen_chunks[3]);
+            }
+            else if let Some(history_search) = &app.main_input_send_history_search {
+                let history_match = history_search.match_idx
+                    .map(|match_idx| app.main_input_send_history.get(match_idx).replace('\n', " \u{23CE} "))
+                    .unwrap_or_default();
+                let history_search_prompt_paragraph = Paragraph::new(format!("({}reverse-i-search)'{}{}': {}",
+                        if history_search.match_idx.is_none() { "failed " } else { "" },
+                        history_search.query, get_blinking_cursor('_', ' '), history_match))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(Color::Green))
+                            .title("Send History Search | Enter: use | Ctrl+R: older match | Esc: cancel")
+                    );
+                frame.render_widget(history_search_prompt_paragraph, main_screen_chunks[3]);
+            }
+            else {
+                // TODO: update keybinding coloring, update based on which screen is active
+                let help_paragraph = Paragraph::new("Quit: Ctrl+] or Ctrl+C | Menu: Ctrl+T | Type to prep data | Enter to send | Alt+Enter: new line | History: Up, Ctrl+R | Escapes/templates: Alt+E/Alt+T | Repeat: Alt+R | Send/receive file: Ctrl+O/Alt+O | Modbus master: Alt+M | DTR/RTS: Alt+1/Alt+2 | BREAK: Alt+3 | Reset: Alt+4-9 | Snippets: Ctrl+P, F1-F12 | Search: Ctrl+F | Filter: Ctrl+G | Tab, then T: timestamps, W: wrap, D: decoded view, N/Shift+N: next/prev match, E/Shift+E: export HTML/ANSI, Y/Shift+Y/A: copy line/page/all")
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Help")
+                            .title(
+                                Title::default()
+                                    .content(app.status_message.clone().unwrap_or_default())
+                                    .alignment(Alignment::Right)
+                            )
+                    )
+                    .wrap(Wrap { trim: true });
+                frame.render_widget(help_paragraph, main_screen_chunks[3]);
+            }
         },
         CurrentScreen::Config1 => {
-            let paragraph = Paragraph::new(Text::raw("Config1 Screen (NOT IMPLEMENTED):"))
-                .block(Block::default().borders(Borders::ALL).title("Config 1"))
-                .wrap(Wrap { trim: true });
+            let rule_list_items: Vec<ListItem> = app.app_config.highlight_rules.iter().map(|rule| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("[{}] ", if rule.enabled { "x" } else { " " })),
+                    Span::raw(format!("{:<24} ", rule.style_spec)),
+                    Span::styled(rule.pattern.clone(), rule.style),
+                ]))
+            }).collect();
+            let rule_list = List::new(rule_list_items)
+                .block(Block::default().borders(Borders::ALL).title("Highlight Rules (applied in order; later rules win)"))
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+                .highlight_symbol("> ");
+
+            let flow_control_paragraph = Paragraph::new(format!("Flow control: {} | Decoder: {}",
+                    app.app_config.flow_control.get_display_name(),
+                    app.app_config.decoder.map(|decoder| decoder.get_display_name()).unwrap_or("None")))
+                .block(Block::default().borders(Borders::ALL).title("Session"));
+
+            let config1_chunks = Layout::vertical([
+                Constraint::Length(3),
+                Constraint::Min(3),
+                Constraint::Length(match (&app.config1_rule_input, &app.config1_error_message) {
+                    (None, None) => 0,
+                    _ => 3,
+                }),
+            ])
+            .split(general_chunks[1]);
 
             frame.render_widget(title, general_chunks[0]);
-            frame.render_widget(paragraph, general_chunks[1]);
+            frame.render_widget(flow_control_paragraph, config1_chunks[0]);
+            frame.render_stateful_widget(rule_list, config1_chunks[1], &mut app.config1_rule_list_state);
 
+            match &app.config1_rule_input {
+                Some(rule_input) => {
+                    let rule_input_paragraph = Paragraph::new(format!("{}{}", rule_input, get_blinking_cursor('_', ' ')))
+                        .block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .border_style(Style::default().fg(Color::Green))
+                                .title("New rule: '<style> <regex>' (e.g., 'red,bold ERROR', 'black,on_yellow WARN', 'dim 0x[0-9a-fA-F]+')")
+                                .title(
+                                    Title::default()
+                                        .content(app.config1_error_message.clone().unwrap_or_default())
+                                        .position(Position::Bottom)
+                                        .alignment(Alignment::Right)
+                                )
+                        );
+                    frame.render_widget(rule_input_paragraph, config1_chunks[2]);
+                }
+                None => {
+                    if let Some(error_message) = &app.config1_error_message {
+                        let error_paragraph = Paragraph::new(error_message.clone().red())
+                            .block(Block::default().borders(Borders::ALL).title("Error"));
+                        frame.render_widget(error_paragraph, config1_chunks[2]);
+                    }
+                }
+            }
 
             // TODO: update keybinding coloring, update based on which screen is active
-            let help_paragraph = Paragraph::new("Quit: Ctrl+] or Ctrl+C | Menu: Ctrl+T | Help: Ctrl+H")
+            let help_paragraph = Paragraph::new("Back: Esc | A: add rule | D: delete rule | Space: enable/disable rule | F: change flow control | P: change decoder | Quit: Ctrl+] or Ctrl+C")
                 .block(Block::default().borders(Borders::ALL).title("Help"))
                 .wrap(Wrap { trim: true });
             frame.render_widget(help_paragraph, general_chunks[2]);
@@ -354,6 +830,275 @@ pub fn ui(frame: &mut Frame, app: &mut App) {
     }
 }
 
+/// Draws the list of scheduled sends.
+fn render_scheduler_panel(frame: &mut Frame, app: &mut App, area: Rect) {
+    let job_list_items: Vec<ListItem> = app.tx_scheduler.jobs.iter().map(|job| {
+        ListItem::new(Line::from(vec![
+            Span::raw(format!("#{:<3} every {} ms, ", job.id, job.interval.as_millis())),
+            Span::raw(match job.count {
+                Some(count) => format!("{}/{} sent ", job.sent_count, count),
+                None => format!("{} sent ", job.sent_count),
+            }),
+            match job.is_paused {
+                true => Span::styled("[paused] ", Style::default().fg(Color::Yellow)),
+                false => Span::raw(""),
+            },
+            Span::styled(
+                format!("{}{}", if job.is_hex { "[hex] " } else { "" }, job.text.replace('\n', " \u{23CE} ")),
+                Style::default().fg(Color::DarkGray)),
+        ]))
+    }).collect();
+    let is_active = matches!(app.main_screen_active_region, MainScreenActiveRegion::SchedulerPanel);
+    let job_list = List::new(job_list_items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(match is_active {
+                    true => Style::default().fg(Color::Green),
+                    false => Style::default(),
+                })
+                .title("Scheduled Sends | Space: pause/resume | X: cancel | Shift+X: cancel all")
+        )
+        .highlight_style(match is_active {
+            true => Style::default().add_modifier(Modifier::REVERSED),
+            false => Style::default(),
+        });
+
+    // keep the selection on a job, as jobs finish or are cancelled
+    let selected_job_idx = app.scheduler_panel_list_state.selected().unwrap_or(0);
+    app.scheduler_panel_list_state.select(Some(selected_job_idx.min(app.tx_scheduler.jobs.len() - 1)));
+    frame.render_stateful_widget(job_list, area, &mut app.scheduler_panel_list_state);
+}
+
+/// Draws the snippet picker as a popup, centered over `area` (the transfer log).
+fn render_snippet_picker(frame: &mut Frame, app: &mut App, area: Rect) {
+    let popup_width = (area.width * 3 / 4).max(area.width.min(40));
+    let popup_height = (app.snippets.len().max(1) as u16 + 2).min(area.height);
+    let popup_area = Rect {
+        x: area.x + (area.width - popup_width) / 2,
+        y: area.y + (area.height - popup_height) / 2,
+        width: popup_width,
+        height: popup_height,
+    };
+
+    let name_width = app.snippets.iter().map(|snippet| snippet.name.width()).max().unwrap_or(0);
+    let snippet_list_items: Vec<ListItem> = match app.snippets.is_empty() {
+        true => vec![ListItem::new(format!("No snippets yet. Add them to {}.", get_workspace_snippets_file().display()))],
+        false => app.snippets.iter().map(|snippet| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<4}", snippet.function_key.map(|num| format!("F{}", num)).unwrap_or_default()),
+                    Style::default().fg(Color::Yellow)),
+                Span::raw(format!("{:<width$}  ", snippet.name, width = name_width)),
+                Span::styled(
+                    format!("{}{}", if snippet.is_hex { "[hex] " } else { "" }, snippet.data.replace('\n', " \u{23CE} ")),
+                    Style::default().fg(Color::DarkGray)),
+            ]))
+        }).collect(),
+    };
+    let snippet_list = List::new(snippet_list_items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Green))
+                .title("Snippets | Enter: send | Esc: close")
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    frame.render_widget(Clear, popup_area);
+    let list_state = app.snippet_picker_list_state.as_mut().expect("only called while the picker is open");
+    frame.render_stateful_widget(snippet_list, popup_area, list_state);
+    app.snippet_picker_list_rect = popup_area;
+}
+
+/// The file transfer's progress, retries, and latest events, over the (paused) transfer log.
+fn render_file_transfer_popup(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(file_transfer) = &app.file_transfer else { return };
+    let status = file_transfer.get_status();
+    let popup_width = (area.width * 3 / 4).max(area.width.min(50));
+    let popup_height = (area.height * 3 / 4).max(area.height.min(10));
+    let popup_area = Rect {
+        x: area.x + (area.width - popup_width) / 2,
+        y: area.y + (area.height - popup_height) / 2,
+        width: popup_width,
+        height: popup_height,
+    };
+
+    let (border_color, keys_help) = match &status.state {
+        FileTransferState::InProgress => (Color::Green, "Ctrl+X: cancel"),
+        FileTransferState::Done => (Color::Green, "Enter/Esc: close"),
+        FileTransferState::Failed(_) => (Color::Red, "Enter/Esc: close"),
+    };
+    let popup_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(border_color))
+        .title(format!("{} | {}", status.title, keys_help));
+    let inner_area = popup_block.inner(popup_area);
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(popup_block, popup_area);
+
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // progress
+            Constraint::Length(1), // byte and retry counts
+            Constraint::Min(0), // events
+        ])
+        .split(inner_area);
+
+    let progress_gauge = Gauge::default()
+        .gauge_style(Style::default().fg(border_color))
+        .ratio(status.get_progress().clamp(0.0, 1.0))
+        .label(format!("{:.0}%", status.get_progress() * 100.0));
+    frame.render_widget(progress_gauge, popup_chunks[0]);
+
+    let counts_paragraph = Paragraph::new(format!("{} / {} bytes | Retries: {}{}",
+        status.transferred_byte_count,
+        status.total_byte_count.map(|count| count.to_string()).unwrap_or_else(|| String::from("?")),
+        status.retry_count,
+        match &status.state {
+            FileTransferState::InProgress => String::new(),
+            FileTransferState::Done => String::from(" | Done"),
+            FileTransferState::Failed(e) => format!(" | Failed: {}", e),
+        }));
+    frame.render_widget(counts_paragraph, popup_chunks[1]);
+
+    // the latest events that fit
+    let event_count = (popup_chunks[2].height as usize).min(status.events.len());
+    let event_list_items: Vec<ListItem> = status.events[status.events.len() - event_count..].iter()
+        .map(|event| ListItem::new(event.as_str()).style(Style::default().fg(Color::DarkGray)))
+        .collect();
+    frame.render_widget(List::new(event_list_items), popup_chunks[2]);
+}
+
+/// Shows the mouse selection in the transfer log, by reversing the colors of the selected cells.
+fn render_log_selection(frame: &mut Frame, log_selection: &LogSelection, log_area: Rect) {
+    let ((start_x, start_y), (end_x, end_y)) = log_selection.get_ordered_ends();
+    let buffer = frame.buffer_mut();
+    for y in start_y.max(log_area.top())..=end_y.min(log_area.bottom().saturating_sub(1)) {
+        let first_x = if y == start_y { start_x } else { log_area.left() };
+        let last_x = if y == end_y { end_x } else { log_area.right().saturating_sub(1) };
+        for x in first_x.max(log_area.left())..=last_x.min(log_area.right().saturating_sub(1)) {
+            buffer.get_mut(x, y).modifier.toggle(Modifier::REVERSED);
+        }
+    }
+}
+
+/// The modem control lines, for the status strip: DTR and RTS (ours), then CTS, DSR, RI, and CD (the other side's).
+/// Lines that are on are highlighted; "?" is a line whose state isn't known. The flow control mode follows (when
+/// there is one), RS-485 mode, and why sending is paused.
+fn get_modem_lines_strip(modem_lines: &ModemLines, flow_control: FlowControl, is_rs485: bool, tx_pause_reason: Option<&str>) -> Line<'static> {
+    let get_line_span = |name: &str, state: Option<bool>| -> Span<'static> {
+        match state {
+            Some(true) => Span::styled(format!(" {} ", name), Style::default().fg(Color::Black).bg(Color::Green)),
+            Some(false) => Span::styled(format!(" {} ", name), Style::default().fg(Color::DarkGray)),
+            None => Span::styled(format!(" {}? ", name), Style::default().fg(Color::DarkGray)),
+        }
+    };
+    let mut spans = vec![
+        get_line_span("DTR", modem_lines.dtr),
+        get_line_span("RTS", modem_lines.rts),
+        Span::raw("|"),
+    ];
+    match (&modem_lines.inputs, modem_lines.is_unsupported) {
+        (_, true) => spans.push(Span::styled(" CTS/DSR/RI/CD n/a ", Style::default().fg(Color::DarkGray))),
+        (Some(inputs), false) => {
+            spans.extend(inputs.get_named_states().iter().map(|(name, state)| get_line_span(name, Some(*state))));
+        }
+        (None, false) => {
+            spans.extend(["CTS", "DSR", "RI", "CD"].iter().map(|name| get_line_span(name, None)));
+        }
+    }
+    if flow_control != FlowControl::None {
+        spans.push(Span::raw(format!("| {} ", flow_control.get_display_name())));
+    }
+    if is_rs485 {
+        spans.push(Span::raw("| RS-485 "));
+    }
+    if let Some(tx_pause_reason) = tx_pause_reason {
+        spans.push(Span::styled(format!(" {} ", tx_pause_reason), Style::default().fg(Color::Black).bg(Color::Yellow)));
+    }
+    Line::from(spans)
+}
+
+/// A decoded frame on one line: a mark for whether its checksum matched, then its fields.
+fn get_decoded_frame_spans(decoded_frame: &DecodedFrame) -> Vec<Span<'static>> {
+    let mut spans = vec![match &decoded_frame.validity {
+        FrameValidity::Valid => Span::styled("✓ ", Style::default().fg(Color::Green)),
+        FrameValidity::Invalid(_) => Span::styled("✗ ", Style::default().fg(Color::Red)),
+        FrameValidity::Unchecked => Span::raw("  "),
+    }];
+    spans.push(Span::raw(decoded_frame.get_inline_text()));
+    if let FrameValidity::Invalid(reason) = &decoded_frame.validity {
+        spans.push(Span::styled(format!(" ({})", reason), Style::default().fg(Color::Red)));
+    }
+    spans
+}
+
+/// The fields of the last decoded frame on screen, one per line.
+fn render_decoder_pane(frame: &mut Frame, app: &App, decoded_frame: Option<&DecodedFrame>, area: Rect) {
+    let decoder_name = app.app_config.decoder.map(|decoder| decoder.get_display_name()).unwrap_or_default();
+    let lines: Vec<Line> = match decoded_frame {
+        Some(decoded_frame) => {
+            let mut lines = vec![Line::from(get_decoded_frame_spans(&DecodedFrame {
+                summary: decoded_frame.summary.clone(),
+                fields: Vec::new(),
+                validity: decoded_frame.validity.clone(),
+            })).bold()];
+            let name_width = decoded_frame.fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            lines.extend(decoded_frame.fields.iter().map(|(name, value)| Line::from(vec![
+                Span::styled(format!("{:<width$} ", name, width = name_width), Style::default().fg(Color::DarkGray)),
+                Span::raw(value.clone()),
+            ])));
+            lines
+        }
+        None => vec![Line::from("No decoded frames on screen".dark_gray())],
+    };
+    let decoder_pane_paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(format!("Decoded ({})", decoder_name)))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(decoder_pane_paragraph, area);
+}
+
+/// Base style of a transfer log line, based on its type.
+pub fn get_transfer_log_type_style(log_type: &TransferLogType) -> Style {
+    match log_type {
+        TransferLogType::SerialData => Style::default(), //.fg(Color::Green))
+        TransferLogType::EchoData => Style::default().fg(Color::LightBlue),
+        TransferLogType::ErrorData => Style::default().fg(Color::Red),
+        TransferLogType::EventData => Style::default().fg(Color::Yellow),
+        TransferLogType::BreakData => Style::default().fg(Color::Magenta),
+        TransferLogType::FramingErrorData | TransferLogType::ParityErrorData => Style::default().fg(Color::LightRed),
+    }
+}
+
+/// Formats the timestamp gutter shown at the start of a transfer log line.
+/// Returns an empty string when timestamps are turned off.
+pub fn get_timestamp_gutter_string(
+    timestamp_mode: TimestampMode,
+    timestamp: chrono::DateTime<chrono::Local>,
+    prev_timestamp: Option<chrono::DateTime<chrono::Local>>,
+    session_start_time: chrono::DateTime<chrono::Local>,
+) -> String {
+    match timestamp_mode {
+        TimestampMode::Off => String::new(),
+        TimestampMode::Absolute => {
+            format!("{} ", timestamp.format("%H:%M:%S%.3f"))
+        }
+        TimestampMode::Relative => {
+            let since_start = timestamp.signed_duration_since(session_start_time);
+            format!("+{:>10.3} ", since_start.num_milliseconds() as f64 / 1000.0)
+        }
+        TimestampMode::Delta => {
+            let since_prev = match prev_timestamp {
+                Some(prev_timestamp) => timestamp.signed_duration_since(prev_timestamp),
+                None => chrono::Duration::zero(),
+            };
+            format!("+{:>8.3} ", since_prev.num_milliseconds() as f64 / 1000.0)
+        }
+    }
+}
+
 fn get_blinking_cursor(on_symbol: char, off_symbol: char) -> char {
     let now = std::time::SystemTime::now();
     let since_the_epoch = now.duration_since(std::time::UNIX_EPOCH).expect("Time went backwards");
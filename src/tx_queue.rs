@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Data waiting to be written to the serial port.
+pub struct TxQueueItem {
+    pub data: Vec<u8>,
+
+    /// Lines shown in the transfer log as the echo of `data`.
+    pub echo_lines: Vec<String>,
+
+    /// How long to wait after writing this, before writing the next item.
+    pub delay_after: Duration,
+}
+
+/// Queue of data to write to the serial port, one item at a time, with a delay after each
+/// (e.g., the lines of a multi-line block sent line by line, or snippets).
+pub struct TxQueue {
+    items: VecDeque<TxQueueItem>,
+    next_send_time: Instant,
+}
+
+impl TxQueue {
+    pub fn new() -> Self {
+        Self {
+            items: VecDeque::new(),
+            next_send_time: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, item: TxQueueItem) {
+        self.items.push_back(item);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Drops everything still waiting to be sent (e.g., after a write error).
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Takes the next item, if the delay after the previous item has passed.
+    pub fn pop_ready(&mut self) -> Option<TxQueueItem> {
+        if Instant::now() < self.next_send_time {
+            return None;
+        }
+        let item = self.items.pop_front()?;
+        self.next_send_time = Instant::now() + item.delay_after;
+        Some(item)
+    }
+}
@@ -0,0 +1,170 @@
+use std::time::{Duration, Instant};
+
+/// A message that's sent repeatedly: every `interval`, either `count` times or until it's cancelled.
+pub struct ScheduledJob {
+    pub id: u32,
+    pub text: String,
+    pub is_hex: bool,
+    pub interval: Duration,
+
+    /// Number of times to send it. None sends it until it's cancelled.
+    pub count: Option<u64>,
+    pub sent_count: u64,
+    pub is_paused: bool,
+
+    next_send_time: Instant,
+}
+
+/// A job's message that's due to be sent.
+pub struct ScheduledSend {
+    pub job_id: u32,
+    pub text: String,
+    pub is_hex: bool,
+}
+
+/// The periodic/repeated sends (e.g., keep-alive pings), shown in the scheduler panel on the Main screen.
+pub struct TxScheduler {
+    pub jobs: Vec<ScheduledJob>,
+    next_job_id: u32,
+}
+
+impl TxScheduler {
+    pub fn new() -> Self {
+        Self {
+            jobs: Vec::new(),
+            next_job_id: 1,
+        }
+    }
+
+    /// Adds a job, which sends its first message right away.
+    pub fn add_job(&mut self, text: String, is_hex: bool, interval: Duration, count: Option<u64>) -> u32 {
+        let id = self.next_job_id;
+        self.next_job_id += 1;
+        self.jobs.push(ScheduledJob {
+            id,
+            text,
+            is_hex,
+            interval,
+            count,
+            sent_count: 0,
+            is_paused: false,
+            next_send_time: Instant::now(),
+        });
+        id
+    }
+
+    /// Takes the messages that are due now, and removes the jobs that have sent all of theirs.
+    pub fn take_due_sends(&mut self) -> Vec<ScheduledSend> {
+        let now = Instant::now();
+        let mut due_sends: Vec<ScheduledSend> = Vec::new();
+        for job in self.jobs.iter_mut().filter(|job| !job.is_paused && now >= job.next_send_time) {
+            due_sends.push(ScheduledSend { job_id: job.id, text: job.text.clone(), is_hex: job.is_hex });
+            job.sent_count += 1;
+
+            // keep a steady rate, unless it's fallen a whole interval behind (e.g., a short interval)
+            job.next_send_time += job.interval;
+            if job.next_send_time < now {
+                job.next_send_time = now + job.interval;
+            }
+        }
+        self.jobs.retain(|job| Some(job.sent_count) != job.count);
+        due_sends
+    }
+
+    /// How long until the next job is due (for how long to wait for events). None if nothing's scheduled.
+    pub fn get_time_until_next_send(&self) -> Option<Duration> {
+        self.jobs.iter()
+            .filter(|job| !job.is_paused)
+            .map(|job| job.next_send_time.saturating_duration_since(Instant::now()))
+            .min()
+    }
+
+    pub fn toggle_pause(&mut self, job_idx: usize) {
+        if let Some(job) = self.jobs.get_mut(job_idx) {
+            job.is_paused = !job.is_paused;
+            // resuming sends the next message right away
+            job.next_send_time = Instant::now();
+        }
+    }
+
+    pub fn pause_job(&mut self, job_id: u32) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == job_id) {
+            job.is_paused = true;
+        }
+    }
+
+    /// Pauses every job (e.g., after a write error, so that it isn't repeated every interval).
+    pub fn pause_all(&mut self) {
+        for job in self.jobs.iter_mut() {
+            job.is_paused = true;
+        }
+    }
+
+    pub fn cancel(&mut self, job_idx: usize) {
+        if job_idx < self.jobs.len() {
+            self.jobs.remove(job_idx);
+        }
+    }
+}
+
+/// Parses how to repeat a message: "<interval ms> [x<count>] [hex]".
+/// For example, "1000" (every second, until cancelled), or "50 x200 hex" (200 times, 50 ms apart, as hex).
+pub fn parse_schedule_spec(spec: &str) -> Result<(Duration, Option<u64>, bool), String> {
+    let mut interval: Option<Duration> = None;
+    let mut count: Option<u64> = None;
+    let mut is_hex = false;
+    for word in spec.split_whitespace() {
+        match (word.strip_prefix('x'), word.trim_end_matches("ms").parse::<u64>()) {
+            (_, Ok(interval_ms)) if interval.is_none() => {
+                if interval_ms == 0 {
+                    return Err(String::from("The interval must be at least 1 ms"));
+                }
+                interval = Some(Duration::from_millis(interval_ms));
+            }
+            (Some(count_str), _) => {
+                count = Some(count_str.parse().ok().filter(|count| *count > 0)
+                    .ok_or_else(|| format!("Invalid count '{}'", word))?);
+            }
+            _ if word == "hex" => is_hex = true,
+            _ => return Err(format!("Unexpected '{}' (expected: <interval ms> [x<count>] [hex])", word)),
+        }
+    }
+    match interval {
+        Some(interval) => Ok((interval, count, is_hex)),
+        None => Err(String::from("Missing the interval (expected: <interval ms> [x<count>] [hex])")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_schedule_specs() {
+        assert_eq!(parse_schedule_spec("1000"), Ok((Duration::from_millis(1000), None, false)));
+        assert_eq!(parse_schedule_spec("50 x200 hex"), Ok((Duration::from_millis(50), Some(200), true)));
+        assert_eq!(parse_schedule_spec("  250ms   x1 "), Ok((Duration::from_millis(250), Some(1), false)));
+        // in any order
+        assert_eq!(parse_schedule_spec("hex x3 100"), Ok((Duration::from_millis(100), Some(3), true)));
+    }
+
+    #[test]
+    fn zero_and_negative_values_are_rejected() {
+        assert_eq!(parse_schedule_spec("0"), Err(String::from("The interval must be at least 1 ms")));
+        assert_eq!(parse_schedule_spec("0ms x5"), Err(String::from("The interval must be at least 1 ms")));
+        assert!(parse_schedule_spec("-100").unwrap_err().starts_with("Unexpected '-100'"));
+        assert_eq!(parse_schedule_spec("100 x0"), Err(String::from("Invalid count 'x0'")));
+        assert_eq!(parse_schedule_spec("100 x-1"), Err(String::from("Invalid count 'x-1'")));
+    }
+
+    #[test]
+    fn malformed_schedule_specs() {
+        assert!(parse_schedule_spec("").unwrap_err().starts_with("Missing the interval"));
+        assert!(parse_schedule_spec("x5 hex").unwrap_err().starts_with("Missing the interval"));
+        assert!(parse_schedule_spec("100 200").unwrap_err().starts_with("Unexpected '200'"));
+        assert!(parse_schedule_spec("1.5").unwrap_err().starts_with("Unexpected '1.5'"));
+        assert!(parse_schedule_spec("100 text").unwrap_err().starts_with("Unexpected 'text'"));
+        assert_eq!(parse_schedule_spec("100 xten"), Err(String::from("Invalid count 'xten'")));
+        assert!(parse_schedule_spec("99999999999999999999").is_err());
+    }
+}
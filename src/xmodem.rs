@@ -0,0 +1,791 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::file_transfer::{get_safe_file_name, save_received_file};
+use crate::file_transfer::{FileTransfer, FileTransferProtocol, FileTransferState, FileTransferStatus};
+
+const SOH: u8 = 0x01; // 128-byte block
+const STX: u8 = 0x02; // 1024-byte block
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1A; // padding at the end of the last block
+const CRC_REQUEST: u8 = b'C';
+
+/// How long the sender waits for the receiver to ask for the first block.
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long to wait for a reply (or the next block) before trying again.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the receiver waits between asking the sender to start.
+const START_REQUEST_INTERVAL: Duration = Duration::from_secs(3);
+/// How long a partly received block can stall before it's thrown away.
+const BLOCK_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_RETRIES: u32 = 10;
+/// Number of start requests with 'C' (CRC) before falling back to NAK (checksum), in plain XMODEM.
+const CRC_START_REQUEST_COUNT: u32 = 3;
+
+/// Sent to cancel a transfer (the other side needs at least two CANs in a row).
+const CANCEL_SEQUENCE: [u8; 5] = [CAN, CAN, CAN, CAN, CAN];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SenderStage {
+    WaitingForStart,
+    /// YMODEM: the header (block 0, with the file name and size) was sent.
+    WaitingForHeaderAck,
+    /// YMODEM: the receiver asks again (with 'C') for the data blocks.
+    WaitingForDataStart,
+    WaitingForBlockAck,
+    WaitingForEotAck,
+    /// YMODEM: the receiver asks (with 'C') for the next file's header; an empty header ends the batch.
+    WaitingForBatchEndStart,
+    WaitingForBatchEndAck,
+    Finished,
+}
+
+/// Sends a file with XMODEM (128 or 1K) or YMODEM.
+pub struct XmodemSender {
+    protocol: FileTransferProtocol,
+    file_name: String,
+    data: Vec<u8>,
+
+    stage: SenderStage,
+    use_crc: bool,
+
+    /// Offset in `data` of the block that was last sent, and its length.
+    block_offset: usize,
+    block_len: usize,
+    block_num: u8,
+
+    /// The last packet, to send again after a NAK or a timeout.
+    last_packet: Vec<u8>,
+    last_send_time: Instant,
+    packet_retry_count: u32,
+    start_time: Instant,
+    received_can_count: u32,
+
+    status: FileTransferStatus,
+}
+
+impl XmodemSender {
+    pub fn new(protocol: FileTransferProtocol, file_name: String, data: Vec<u8>) -> Self {
+        let mut status = FileTransferStatus::new(format!("{} send '{}'", protocol.get_display_name(), file_name));
+        status.total_byte_count = Some(data.len() as u64);
+        status.add_event(String::from("Waiting for the receiver to start..."));
+        Self {
+            protocol,
+            file_name,
+            data,
+            stage: SenderStage::WaitingForStart,
+            use_crc: true,
+            block_offset: 0,
+            block_len: 0,
+            block_num: 1,
+            last_packet: Vec::new(),
+            last_send_time: Instant::now(),
+            packet_retry_count: 0,
+            start_time: Instant::now(),
+            received_can_count: 0,
+            status,
+        }
+    }
+
+    fn handle_byte(&mut self, byte: u8) -> Vec<u8> {
+        // two CANs in a row cancel the transfer
+        match byte {
+            CAN => self.received_can_count += 1,
+            _ => self.received_can_count = 0,
+        }
+        if self.received_can_count >= 2 {
+            self.status.fail(String::from("Cancelled by the receiver"));
+            self.stage = SenderStage::Finished;
+            return Vec::new();
+        }
+
+        match (self.stage, byte) {
+            (SenderStage::WaitingForStart, CRC_REQUEST | NAK) => {
+                self.use_crc = byte == CRC_REQUEST;
+                self.status.add_event(format!("Receiver started ({})", if self.use_crc { "CRC" } else { "checksum" }));
+                match self.protocol {
+                    FileTransferProtocol::Ymodem => {
+                        let header = get_ymodem_header(&self.file_name, self.data.len());
+                        self.stage = SenderStage::WaitingForHeaderAck;
+                        self.send_packet(build_block(0, &header, 128.max(header.len()), self.use_crc, 0x00))
+                    }
+                    _ => self.send_next_block(),
+                }
+            }
+            (SenderStage::WaitingForHeaderAck, ACK) => {
+                self.stage = SenderStage::WaitingForDataStart;
+                self.last_send_time = Instant::now();
+                Vec::new()
+            }
+            (SenderStage::WaitingForDataStart, NAK) => {
+                // the header wasn't received after all
+                self.resend_packet("NAK")
+            }
+            (SenderStage::WaitingForDataStart, CRC_REQUEST) => {
+                self.send_next_block()
+            }
+            (SenderStage::WaitingForBlockAck, ACK) => {
+                self.block_offset += self.block_len;
+                self.block_num = self.block_num.wrapping_add(1);
+                self.status.transferred_byte_count = self.block_offset as u64;
+                self.send_next_block()
+            }
+            (SenderStage::WaitingForEotAck, ACK) => {
+                match self.protocol {
+                    FileTransferProtocol::Ymodem => {
+                        self.stage = SenderStage::WaitingForBatchEndStart;
+                        self.last_send_time = Instant::now();
+                        Vec::new()
+                    }
+                    _ => {
+                        self.finish();
+                        Vec::new()
+                    }
+                }
+            }
+            (SenderStage::WaitingForBatchEndStart, CRC_REQUEST) => {
+                // an empty header means there are no more files
+                self.stage = SenderStage::WaitingForBatchEndAck;
+                self.send_packet(build_block(0, &[], 128, self.use_crc, 0x00))
+            }
+            (SenderStage::WaitingForBatchEndAck, ACK) => {
+                self.finish();
+                Vec::new()
+            }
+            (SenderStage::WaitingForEotAck, NAK) => {
+                // receivers often NAK the first EOT, to make sure it wasn't line noise
+                self.resend_packet("NAK")
+            }
+            (SenderStage::WaitingForHeaderAck | SenderStage::WaitingForBlockAck | SenderStage::WaitingForBatchEndAck, NAK) => {
+                self.resend_packet("NAK")
+            }
+            _ => Vec::new(), // line noise, or a repeated request
+        }
+    }
+
+    /// Sends the block at `block_offset`, or EOT if the whole file has been sent.
+    fn send_next_block(&mut self) -> Vec<u8> {
+        if self.block_offset >= self.data.len() {
+            self.stage = SenderStage::WaitingForEotAck;
+            return self.send_packet(vec![EOT]);
+        }
+        let remaining_len = self.data.len() - self.block_offset;
+        // 1K blocks need a CRC; the last block is a short one, if that's less padding
+        let block_size = match (self.protocol, self.use_crc, remaining_len) {
+            (FileTransferProtocol::Xmodem, _, _) | (_, false, _) | (_, _, 0..=128) => 128,
+            _ => 1024,
+        };
+        self.block_len = remaining_len.min(block_size);
+        self.stage = SenderStage::WaitingForBlockAck;
+        let payload = self.data[self.block_offset..self.block_offset + self.block_len].to_vec();
+        self.send_packet(build_block(self.block_num, &payload, block_size, self.use_crc, SUB))
+    }
+
+    fn send_packet(&mut self, packet: Vec<u8>) -> Vec<u8> {
+        self.last_packet = packet.clone();
+        self.last_send_time = Instant::now();
+        self.packet_retry_count = 0;
+        packet
+    }
+
+    fn resend_packet(&mut self, reason: &str) -> Vec<u8> {
+        self.packet_retry_count += 1;
+        self.status.retry_count += 1;
+        if self.packet_retry_count > MAX_RETRIES {
+            self.status.fail(format!("Too many retries ({})", reason));
+            self.stage = SenderStage::Finished;
+            return CANCEL_SEQUENCE.to_vec();
+        }
+        self.status.add_event(format!("{} for block {}, sending it again (retry {})", reason, self.block_num, self.packet_retry_count));
+        self.last_send_time = Instant::now();
+        self.last_packet.clone()
+    }
+
+    fn finish(&mut self) {
+        self.stage = SenderStage::Finished;
+        self.status.transferred_byte_count = self.data.len() as u64;
+        self.status.add_event(format!("Sent '{}' ({} bytes)", self.file_name, self.data.len()));
+        self.status.state = FileTransferState::Done;
+    }
+}
+
+impl FileTransfer for XmodemSender {
+    fn handle_rxd_data(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut reply: Vec<u8> = Vec::new();
+        for byte in data {
+            if self.stage == SenderStage::Finished {
+                break;
+            }
+            reply.extend(self.handle_byte(*byte));
+        }
+        reply
+    }
+
+    fn tick(&mut self) -> Vec<u8> {
+        match self.stage {
+            SenderStage::Finished => Vec::new(),
+            SenderStage::WaitingForStart => {
+                if self.start_time.elapsed() > START_TIMEOUT {
+                    self.status.fail(String::from("The receiver didn't start"));
+                    self.stage = SenderStage::Finished;
+                }
+                Vec::new()
+            }
+            SenderStage::WaitingForDataStart | SenderStage::WaitingForBatchEndStart => {
+                if self.last_send_time.elapsed() > REPLY_TIMEOUT {
+                    self.status.fail(String::from("The receiver stopped responding"));
+                    self.stage = SenderStage::Finished;
+                }
+                Vec::new()
+            }
+            _ => match self.last_send_time.elapsed() > REPLY_TIMEOUT {
+                true => self.resend_packet("Timeout"),
+                false => Vec::new(),
+            },
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<u8> {
+        if self.stage == SenderStage::Finished {
+            return Vec::new();
+        }
+        self.stage = SenderStage::Finished;
+        self.status.fail(String::from("Cancelled"));
+        CANCEL_SEQUENCE.to_vec()
+    }
+
+    fn get_status(&self) -> &FileTransferStatus {
+        &self.status
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReceiverStage {
+    /// Asking the sender to start (with 'C' or NAK), until the first block comes in.
+    Starting,
+    /// YMODEM: waiting for a file header (block 0). An empty one ends the batch.
+    WaitingForHeader,
+    ReceivingBlocks,
+    /// YMODEM: the first EOT was NAK'd, to make sure it wasn't line noise.
+    WaitingForSecondEot,
+    Finished,
+}
+
+/// Receives files with XMODEM (128 or 1K) or YMODEM.
+pub struct XmodemReceiver {
+    protocol: FileTransferProtocol,
+
+    /// The file to save to (XMODEM), or the directory to save into (YMODEM).
+    save_path: PathBuf,
+
+    stage: ReceiverStage,
+    use_crc: bool,
+    start_request_count: u32,
+    expected_block_num: u8,
+
+    /// Received bytes that aren't a whole block yet.
+    rx_buffer: Vec<u8>,
+    last_rx_time: Instant,
+    last_request_time: Instant,
+    error_count: u32,
+
+    /// The file being received: its name and size (YMODEM only), and its data so far.
+    file_name: Option<String>,
+    file_size: Option<u64>,
+    file_data: Vec<u8>,
+
+    status: FileTransferStatus,
+}
+
+impl XmodemReceiver {
+    pub fn new(protocol: FileTransferProtocol, save_path: PathBuf) -> Self {
+        let status = FileTransferStatus::new(format!("{} receive into '{}'", protocol.get_display_name(), save_path.display()));
+        Self {
+            protocol,
+            save_path,
+            stage: ReceiverStage::Starting,
+            use_crc: true,
+            start_request_count: 0,
+            expected_block_num: 1,
+            rx_buffer: Vec::new(),
+            last_rx_time: Instant::now(),
+            // so that the first tick asks the sender to start right away
+            last_request_time: Instant::now() - START_REQUEST_INTERVAL,
+            error_count: 0,
+            file_name: None,
+            file_size: None,
+            file_data: Vec::new(),
+            status,
+        }
+    }
+
+    /// Parses whole packets out of the received bytes. Returns the replies.
+    fn process_rx_buffer(&mut self) -> Vec<u8> {
+        let mut reply: Vec<u8> = Vec::new();
+        while let Some(&first_byte) = self.rx_buffer.first() {
+            if self.stage == ReceiverStage::Finished {
+                self.rx_buffer.clear();
+                break;
+            }
+            match first_byte {
+                SOH | STX => {
+                    let block_size = if first_byte == SOH { 128 } else { 1024 };
+                    let packet_len = 3 + block_size + if self.use_crc { 2 } else { 1 };
+                    if self.rx_buffer.len() < packet_len {
+                        break;
+                    }
+                    let packet: Vec<u8> = self.rx_buffer.drain(..packet_len).collect();
+                    reply.extend(self.handle_block(&packet, block_size));
+                }
+                EOT => {
+                    self.rx_buffer.remove(0);
+                    reply.extend(self.handle_eot());
+                }
+                CAN => {
+                    match self.rx_buffer.get(1) {
+                        Some(&CAN) => {
+                            self.status.fail(String::from("Cancelled by the sender"));
+                            self.stage = ReceiverStage::Finished;
+                        }
+                        Some(_) => {
+                            self.rx_buffer.remove(0);
+                        }
+                        None => break,
+                    }
+                }
+                _ => {
+                    // line noise between blocks
+                    self.rx_buffer.remove(0);
+                }
+            }
+        }
+        reply
+    }
+
+    fn handle_block(&mut self, packet: &[u8], block_size: usize) -> Vec<u8> {
+        let block_num = packet[1];
+        let payload = &packet[3..3 + block_size];
+        let is_check_ok = match self.use_crc {
+            true => packet[3 + block_size..] == get_crc16_xmodem(payload).to_be_bytes(),
+            false => packet[3 + block_size] == get_checksum(payload),
+        };
+        if packet[2] != !block_num || !is_check_ok {
+            return self.reject(format!("Bad block {} ({})", block_num,
+                if is_check_ok { "block number mismatch" } else { "CRC/checksum error" }));
+        }
+
+        if self.stage == ReceiverStage::Starting {
+            self.status.add_event(format!("Sender started ({})", if self.use_crc { "CRC" } else { "checksum" }));
+            self.stage = match self.protocol {
+                FileTransferProtocol::Ymodem => ReceiverStage::WaitingForHeader,
+                _ => ReceiverStage::ReceivingBlocks,
+            };
+        }
+
+        match (self.stage, block_num) {
+            (ReceiverStage::WaitingForHeader, 0) => self.handle_ymodem_header(payload),
+            (ReceiverStage::ReceivingBlocks, block_num) if block_num == self.expected_block_num => {
+                self.file_data.extend(payload);
+                self.expected_block_num = self.expected_block_num.wrapping_add(1);
+                self.error_count = 0;
+                self.status.transferred_byte_count = self.file_data.len() as u64;
+                vec![ACK]
+            }
+            (ReceiverStage::ReceivingBlocks, block_num) if block_num == self.expected_block_num.wrapping_sub(1) => {
+                // our ACK was lost, so the sender sent the same block again
+                self.status.add_event(format!("Duplicate block {}", block_num));
+                vec![ACK]
+            }
+            _ => {
+                self.status.fail(format!("Block {} is out of sequence (expected {})", block_num, self.expected_block_num));
+                self.stage = ReceiverStage::Finished;
+                CANCEL_SEQUENCE.to_vec()
+            }
+        }
+    }
+
+    /// Handles a YMODEM header: "name\0size ..." for the next file, or empty at the end of the batch.
+    fn handle_ymodem_header(&mut self, payload: &[u8]) -> Vec<u8> {
+        if payload[0] == 0 {
+            self.stage = ReceiverStage::Finished;
+            self.status.state = FileTransferState::Done;
+            self.status.add_event(String::from("Done"));
+            return vec![ACK];
+        }
+        let name_end = payload.iter().position(|byte| *byte == 0).unwrap_or(payload.len());
+        let file_name = get_safe_file_name(&String::from_utf8_lossy(&payload[..name_end]));
+        let file_info = String::from_utf8_lossy(&payload[(name_end + 1).min(payload.len())..]).to_string();
+        self.file_size = file_info.trim_start().split([' ', '\0']).next().and_then(|size| size.parse().ok());
+        self.status.add_event(format!("Receiving '{}'{}", file_name,
+            self.file_size.map(|size| format!(" ({} bytes)", size)).unwrap_or_default()));
+        self.status.title = format!("{} receive '{}'", self.protocol.get_display_name(), file_name);
+        self.status.total_byte_count = self.file_size;
+        self.status.transferred_byte_count = 0;
+        self.file_name = Some(file_name);
+        self.file_data.clear();
+        self.expected_block_num = 1;
+        self.stage = ReceiverStage::ReceivingBlocks;
+        vec![ACK, CRC_REQUEST]
+    }
+
+    fn handle_eot(&mut self) -> Vec<u8> {
+        match (self.stage, self.protocol) {
+            (ReceiverStage::ReceivingBlocks, FileTransferProtocol::Ymodem) => {
+                self.stage = ReceiverStage::WaitingForSecondEot;
+                vec![NAK]
+            }
+            (ReceiverStage::Starting | ReceiverStage::ReceivingBlocks, FileTransferProtocol::Xmodem | FileTransferProtocol::Xmodem1k) => {
+                // XMODEM doesn't send the size, so drop the padding at the end of the last block
+                while self.file_data.last() == Some(&SUB) {
+                    self.file_data.pop();
+                }
+                self.save_file();
+                if self.stage != ReceiverStage::Finished {
+                    self.stage = ReceiverStage::Finished;
+                    self.status.state = FileTransferState::Done;
+                }
+                vec![ACK]
+            }
+            (ReceiverStage::WaitingForSecondEot, _) => {
+                if let Some(file_size) = self.file_size {
+                    self.file_data.truncate(file_size as usize);
+                }
+                self.save_file();
+                if self.stage == ReceiverStage::Finished {
+                    return CANCEL_SEQUENCE.to_vec();
+                }
+                // ask for the next file's header
+                self.stage = ReceiverStage::WaitingForHeader;
+                self.last_request_time = Instant::now();
+                vec![ACK, CRC_REQUEST]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn save_file(&mut self) {
+        let save_path = match &self.file_name {
+            Some(file_name) => self.save_path.join(file_name),
+            None => self.save_path.clone(),
+        };
+        match save_received_file(&save_path, &self.file_data) {
+            Ok(saved_path) => {
+                self.status.add_event(format!("Saved '{}' ({} bytes)", saved_path.display(), self.file_data.len()));
+            }
+            Err(e) => {
+                self.status.fail(e);
+                self.stage = ReceiverStage::Finished;
+            }
+        }
+    }
+
+    /// Throws away a bad block, and asks for it again.
+    fn reject(&mut self, reason: String) -> Vec<u8> {
+        self.rx_buffer.clear();
+        self.error_count += 1;
+        self.status.retry_count += 1;
+        if self.error_count > MAX_RETRIES {
+            self.status.fail(format!("Too many errors ({})", reason));
+            self.stage = ReceiverStage::Finished;
+            return CANCEL_SEQUENCE.to_vec();
+        }
+        self.status.add_event(reason);
+        self.last_request_time = Instant::now();
+        vec![NAK]
+    }
+}
+
+impl FileTransfer for XmodemReceiver {
+    fn handle_rxd_data(&mut self, data: &[u8]) -> Vec<u8> {
+        if self.stage == ReceiverStage::Finished {
+            return Vec::new();
+        }
+        self.last_rx_time = Instant::now();
+        self.rx_buffer.extend(data);
+        self.process_rx_buffer()
+    }
+
+    fn tick(&mut self) -> Vec<u8> {
+        match self.stage {
+            ReceiverStage::Finished => Vec::new(),
+            ReceiverStage::Starting => {
+                if self.last_request_time.elapsed() < START_REQUEST_INTERVAL {
+                    return Vec::new();
+                }
+                self.start_request_count += 1;
+                if self.start_request_count > MAX_RETRIES {
+                    self.status.fail(String::from("The sender didn't start"));
+                    self.stage = ReceiverStage::Finished;
+                    return CANCEL_SEQUENCE.to_vec();
+                }
+                // plain XMODEM senders may only do checksums, so fall back to asking for those
+                if self.protocol == FileTransferProtocol::Xmodem && self.start_request_count > CRC_START_REQUEST_COUNT {
+                    self.use_crc = false;
+                }
+                self.last_request_time = Instant::now();
+                self.status.add_event(format!("Asking the sender to start ({})", if self.use_crc { "CRC" } else { "checksum" }));
+                vec![if self.use_crc { CRC_REQUEST } else { NAK }]
+            }
+            _ => {
+                if !self.rx_buffer.is_empty() && self.last_rx_time.elapsed() > BLOCK_STALL_TIMEOUT {
+                    return self.reject(String::from("Incomplete block"));
+                }
+                if self.last_rx_time.elapsed() > REPLY_TIMEOUT && self.last_request_time.elapsed() > REPLY_TIMEOUT {
+                    let reply = self.reject(String::from("Timeout"));
+                    // a lost header request is repeated as 'C'
+                    return match (self.stage, reply.as_slice()) {
+                        (ReceiverStage::WaitingForHeader, [NAK]) => vec![CRC_REQUEST],
+                        _ => reply,
+                    };
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<u8> {
+        if self.stage == ReceiverStage::Finished {
+            return Vec::new();
+        }
+        self.stage = ReceiverStage::Finished;
+        self.status.fail(String::from("Cancelled"));
+        CANCEL_SEQUENCE.to_vec()
+    }
+
+    fn get_status(&self) -> &FileTransferStatus {
+        &self.status
+    }
+}
+
+/// Builds a block: header, `payload` padded to `block_size` with `padding`, and the CRC (or checksum).
+fn build_block(block_num: u8, payload: &[u8], block_size: usize, use_crc: bool, padding: u8) -> Vec<u8> {
+    let mut block_data = payload.to_vec();
+    block_data.resize(block_size, padding);
+    let mut packet = Vec::with_capacity(block_size + 5);
+    packet.push(if block_size == 1024 { STX } else { SOH });
+    packet.push(block_num);
+    packet.push(!block_num);
+    packet.extend(&block_data);
+    match use_crc {
+        true => packet.extend(get_crc16_xmodem(&block_data).to_be_bytes()),
+        false => packet.push(get_checksum(&block_data)),
+    }
+    packet
+}
+
+/// The YMODEM header's payload: the file name, a NUL, and the size in decimal.
+/// Padded to 1024 bytes if it doesn't fit in 128.
+fn get_ymodem_header(file_name: &str, file_size: usize) -> Vec<u8> {
+    let mut header = format!("{}\0{}", file_name, file_size).into_bytes();
+    header.push(0);
+    match header.len() {
+        0..=128 => header,
+        _ => {
+            header.truncate(1024);
+            header.resize(1024, 0);
+            header
+        }
+    }
+}
+
+/// CRC-16/XMODEM (polynomial 0x1021, initial value 0).
+pub fn get_crc16_xmodem(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021,
+            };
+        }
+    }
+    crc
+}
+
+fn get_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0_u8, |acc, byte| acc.wrapping_add(*byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for each test's received files.
+    fn get_test_dir(test_name: &str) -> PathBuf {
+        let test_dir = std::env::temp_dir().join(format!("baud-boss-xmodem-test-{}-{}", std::process::id(), test_name));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&test_dir).unwrap();
+        test_dir
+    }
+
+    fn get_test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|idx| (idx * 7 + idx / 256) as u8).collect()
+    }
+
+    /// Passes bytes back and forth (starting with the receiver's first start request) until neither side has
+    /// anything more to send. `to_receiver` and `to_sender` can change what's passed at each step (e.g., to
+    /// corrupt or drop it), like a noisy line.
+    fn pump(
+        sender: &mut dyn FileTransfer,
+        receiver: &mut dyn FileTransfer,
+        mut to_receiver: impl FnMut(usize, Vec<u8>) -> Vec<u8>,
+        mut to_sender: impl FnMut(usize, Vec<u8>) -> Vec<u8>,
+    ) {
+        let mut sender_rx = receiver.tick();
+        for step in 0..10_000 {
+            let receiver_rx = to_receiver(step, sender.handle_rxd_data(&sender_rx));
+            sender_rx = to_sender(step, receiver.handle_rxd_data(&receiver_rx));
+            if sender_rx.is_empty() && receiver_rx.is_empty() {
+                return;
+            }
+        }
+        panic!("The transfer didn't finish");
+    }
+
+    fn pass(_step: usize, data: Vec<u8>) -> Vec<u8> {
+        data
+    }
+
+    #[test]
+    fn crc16_xmodem_matches_known_vector() {
+        assert_eq!(get_crc16_xmodem(b"123456789"), 0x31C3);
+        assert_eq!(get_crc16_xmodem(&[]), 0x0000);
+    }
+
+    #[test]
+    fn xmodem_crc_transfer() {
+        let test_dir = get_test_dir("xmodem_crc");
+        let data = get_test_data(1000); // not a multiple of 128, so the last block is padded
+        let mut sender = XmodemSender::new(FileTransferProtocol::Xmodem, String::from("data.bin"), data.clone());
+        let mut receiver = XmodemReceiver::new(FileTransferProtocol::Xmodem, test_dir.join("data.bin"));
+        pump(&mut sender, &mut receiver, pass, pass);
+
+        assert_eq!(sender.get_status().state, FileTransferState::Done);
+        assert_eq!(receiver.get_status().state, FileTransferState::Done);
+        assert_eq!(sender.get_status().retry_count, 0);
+        assert_eq!(std::fs::read(test_dir.join("data.bin")).unwrap(), data);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn xmodem_1k_transfer() {
+        let test_dir = get_test_dir("xmodem_1k");
+        let data = get_test_data(3000);
+        let mut sender = XmodemSender::new(FileTransferProtocol::Xmodem1k, String::from("data.bin"), data.clone());
+        let mut receiver = XmodemReceiver::new(FileTransferProtocol::Xmodem1k, test_dir.join("data.bin"));
+        let mut block_sizes: Vec<usize> = Vec::new();
+        pump(&mut sender, &mut receiver, |_, data| {
+            if data.len() > 128 + 5 {
+                block_sizes.push(data.len() - 5);
+            }
+            data
+        }, pass);
+
+        assert_eq!(receiver.get_status().state, FileTransferState::Done);
+        assert_eq!(block_sizes, vec![1024, 1024, 1024]);
+        assert_eq!(std::fs::read(test_dir.join("data.bin")).unwrap(), data);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn ymodem_transfer_keeps_the_file_name_and_size() {
+        let test_dir = get_test_dir("ymodem");
+        // ends with SUB bytes, which only the size in the header can tell apart from padding
+        let mut data = get_test_data(2000);
+        data.extend([SUB, SUB]);
+        let mut sender = XmodemSender::new(FileTransferProtocol::Ymodem, String::from("firmware.bin"), data.clone());
+        let mut receiver = XmodemReceiver::new(FileTransferProtocol::Ymodem, test_dir.clone());
+        pump(&mut sender, &mut receiver, pass, pass);
+
+        assert_eq!(sender.get_status().state, FileTransferState::Done);
+        assert_eq!(receiver.get_status().state, FileTransferState::Done);
+        assert_eq!(receiver.get_status().total_byte_count, Some(data.len() as u64));
+        assert_eq!(std::fs::read(test_dir.join("firmware.bin")).unwrap(), data);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn corrupted_block_is_nakd_and_sent_again() {
+        let test_dir = get_test_dir("corrupted_block");
+        let data = get_test_data(500);
+        let mut sender = XmodemSender::new(FileTransferProtocol::Xmodem, String::from("data.bin"), data.clone());
+        let mut receiver = XmodemReceiver::new(FileTransferProtocol::Xmodem, test_dir.join("data.bin"));
+        let mut naks: Vec<usize> = Vec::new();
+        pump(&mut sender, &mut receiver, |step, mut data| {
+            if step == 1 {
+                data[10] ^= 0xFF; // in block 2's payload
+            }
+            data
+        }, |step, data| {
+            if data == [NAK] {
+                naks.push(step);
+            }
+            data
+        });
+
+        assert_eq!(naks, vec![1]);
+        assert_eq!(sender.get_status().retry_count, 1);
+        assert_eq!(receiver.get_status().retry_count, 1);
+        assert_eq!(receiver.get_status().state, FileTransferState::Done);
+        assert_eq!(std::fs::read(test_dir.join("data.bin")).unwrap(), data);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn dropped_ack_leads_to_a_duplicate_block() {
+        let test_dir = get_test_dir("dropped_ack");
+        let data = get_test_data(500);
+        let mut sender = XmodemSender::new(FileTransferProtocol::Xmodem, String::from("data.bin"), data.clone());
+        let mut receiver = XmodemReceiver::new(FileTransferProtocol::Xmodem, test_dir.join("data.bin"));
+        pump(&mut sender, &mut receiver, |step, data| {
+            // the ACK for block 2 is lost, so the sender times out and sends block 2 again
+            match step {
+                1 => data.repeat(2),
+                _ => data,
+            }
+        }, |step, data| {
+            match step {
+                1 => {
+                    assert_eq!(data, vec![ACK, ACK]);
+                    vec![ACK]
+                }
+                _ => data,
+            }
+        });
+
+        assert!(receiver.get_status().events.iter().any(|event| event == "Duplicate block 2"));
+        assert_eq!(receiver.get_status().state, FileTransferState::Done);
+        assert_eq!(std::fs::read(test_dir.join("data.bin")).unwrap(), data);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn can_can_cancels_either_side() {
+        let test_dir = get_test_dir("can_can");
+        let data = get_test_data(1000);
+
+        let mut sender = XmodemSender::new(FileTransferProtocol::Xmodem, String::from("data.bin"), data.clone());
+        let mut receiver = XmodemReceiver::new(FileTransferProtocol::Xmodem, test_dir.join("data.bin"));
+        let block_1 = sender.handle_rxd_data(&receiver.tick());
+        assert_eq!(receiver.handle_rxd_data(&block_1), vec![ACK]);
+        assert_eq!(receiver.handle_rxd_data(&sender.cancel()), Vec::<u8>::new());
+        assert_eq!(receiver.get_status().state, FileTransferState::Failed(String::from("Cancelled by the sender")));
+
+        let mut sender = XmodemSender::new(FileTransferProtocol::Xmodem, String::from("data.bin"), data.clone());
+        let mut receiver = XmodemReceiver::new(FileTransferProtocol::Xmodem, test_dir.join("data.bin"));
+        sender.handle_rxd_data(&receiver.tick());
+        assert_eq!(sender.handle_rxd_data(&receiver.cancel()), Vec::<u8>::new());
+        assert_eq!(sender.get_status().state, FileTransferState::Failed(String::from("Cancelled by the receiver")));
+
+        // a single CAN is line noise
+        let mut sender = XmodemSender::new(FileTransferProtocol::Xmodem, String::from("data.bin"), data);
+        sender.handle_rxd_data(&[CRC_REQUEST]);
+        sender.handle_rxd_data(&[CAN]);
+        assert_eq!(sender.get_status().state, FileTransferState::InProgress);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
@@ -0,0 +1,1075 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::file_transfer::{get_safe_file_name, save_received_file};
+use crate::file_transfer::{FileTransfer, FileTransferState, FileTransferStatus};
+use crate::xmodem::get_crc16_xmodem;
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = 0x18; // also CAN; five in a row cancel the transfer
+const ZBIN: u8 = b'A'; // binary header, 16-bit CRC
+const ZHEX: u8 = b'B'; // hex header, 16-bit CRC
+const ZBIN32: u8 = b'C'; // binary header, 32-bit CRC
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+const BACKSPACE: u8 = 0x08;
+
+// frame types
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZSINIT: u8 = 2;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+const ZCAN: u8 = 16;
+const ZCOMMAND: u8 = 18;
+
+// data subpacket ends (after a ZDLE)
+const ZCRCE: u8 = b'h'; // end of frame, a header follows
+const ZCRCG: u8 = b'i'; // more data follows
+const ZCRCQ: u8 = b'j'; // more data follows, ZACK expected
+const ZCRCW: u8 = b'k'; // end of frame, ZACK expected
+const ZRUB0: u8 = b'l'; // escaped 0x7F
+const ZRUB1: u8 = b'm'; // escaped 0xFF
+
+// ZRINIT flags (in ZF0)
+const CANFDX: u8 = 0x01; // full duplex
+const CANOVIO: u8 = 0x02; // can receive while writing to disk
+const CANFC32: u8 = 0x20; // 32-bit CRCs
+
+/// Data bytes per subpacket, and subpackets per frame (the sender waits for a ZACK after each frame, so a bad
+/// subpacket costs at most a frame, and the UI isn't blocked for long writing it at low baud rates).
+const SUBPACKET_LEN: usize = 1024;
+const SUBPACKETS_PER_FRAME: usize = 4;
+/// Longer subpackets are treated as garbage (e.g., a lost subpacket end).
+const MAX_SUBPACKET_LEN: usize = 8192;
+
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+const START_REQUEST_INTERVAL: Duration = Duration::from_secs(5);
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the receiver waits for the sender's "OO" (over and out), after the last ZFIN.
+const OVER_AND_OUT_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_RETRIES: u32 = 10;
+
+/// Sent to cancel a transfer: CANs, then backspaces to erase them (in case a shell gets them).
+const CANCEL_SEQUENCE: [u8; 16] = [
+    ZDLE, ZDLE, ZDLE, ZDLE, ZDLE, ZDLE, ZDLE, ZDLE,
+    BACKSPACE, BACKSPACE, BACKSPACE, BACKSPACE, BACKSPACE, BACKSPACE, BACKSPACE, BACKSPACE,
+];
+
+/// How long the detector ignores repeated requests (e.g., `rz` sends ZRINIT every few seconds).
+const DETECTION_QUIET_TIME: Duration = Duration::from_secs(30);
+
+/// What the other side asked for, found in the received data by `ZmodemDetector`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZmodemRequest {
+    /// `sz` is running (ZRQINIT): it wants to send us files.
+    Receive,
+    /// `rz` is running (ZRINIT): it's waiting for us to send files.
+    Send,
+}
+
+/// Watches the received data for the start of a ZMODEM transfer (the hex ZRQINIT or ZRINIT header).
+pub struct ZmodemDetector {
+    /// The end of the last received data, in case a header is split across two reads.
+    tail: Vec<u8>,
+    last_detection_time: Option<Instant>,
+}
+
+impl ZmodemDetector {
+    pub fn new() -> Self {
+        Self {
+            tail: Vec::new(),
+            last_detection_time: None,
+        }
+    }
+
+    pub fn handle_rxd_data(&mut self, data: &[u8]) -> Option<ZmodemRequest> {
+        const HEX_HEADER_START: [u8; 4] = [ZPAD, ZPAD, ZDLE, ZHEX];
+        self.tail.extend(data);
+        let request = self.tail.windows(HEX_HEADER_START.len() + 2)
+            .filter(|window| window.starts_with(&HEX_HEADER_START))
+            .find_map(|window| match &window[HEX_HEADER_START.len()..] {
+                b"00" => Some(ZmodemRequest::Receive),
+                b"01" => Some(ZmodemRequest::Send),
+                _ => None,
+            });
+        let keep_len = self.tail.len().min(HEX_HEADER_START.len() + 1);
+        self.tail.drain(..self.tail.len() - keep_len);
+
+        let request = request?;
+        if self.last_detection_time.is_some_and(|time| time.elapsed() < DETECTION_QUIET_TIME) {
+            return None;
+        }
+        self.last_detection_time = Some(Instant::now());
+        Some(request)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ZmodemHeader {
+    frame_type: u8,
+    /// ZP0..ZP3 (a position, low byte first), or ZF3..ZF0 (flags).
+    args: [u8; 4],
+}
+
+impl ZmodemHeader {
+    fn with_position(frame_type: u8, position: u64) -> Self {
+        Self { frame_type, args: (position as u32).to_le_bytes() }
+    }
+
+    fn get_position(&self) -> u64 {
+        u32::from_le_bytes(self.args) as u64
+    }
+
+    /// ZF0, the main flags byte.
+    fn get_flags(&self) -> u8 {
+        self.args[3]
+    }
+}
+
+enum ZmodemItem {
+    Header(ZmodemHeader),
+    /// A data subpacket, and how it ended (ZCRCE, ZCRCG, ZCRCQ, or ZCRCW).
+    Data(Vec<u8>, u8),
+    Cancelled,
+}
+
+/// Splits the received bytes into headers and data subpackets.
+struct ZmodemParser {
+    buffer: Vec<u8>,
+
+    /// Some while data subpackets are expected (after ZDATA, ZFILE, ...): whether they have a 32-bit CRC.
+    data_crc32: Option<bool>,
+
+    consecutive_can_count: u32,
+    is_cancelled: bool,
+}
+
+impl ZmodemParser {
+    fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            data_crc32: None,
+            consecutive_can_count: 0,
+            is_cancelled: false,
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        for byte in data {
+            match *byte {
+                ZDLE => self.consecutive_can_count += 1,
+                _ => self.consecutive_can_count = 0,
+            }
+            if self.consecutive_can_count >= 5 {
+                self.is_cancelled = true;
+            }
+        }
+        self.buffer.extend(data);
+    }
+
+    /// The next whole header or data subpacket (None until there's enough data). Errors are bad CRCs and such.
+    fn next_item(&mut self) -> Option<Result<ZmodemItem, String>> {
+        if self.is_cancelled {
+            self.is_cancelled = false;
+            self.buffer.clear();
+            return Some(Ok(ZmodemItem::Cancelled));
+        }
+        match self.data_crc32 {
+            Some(is_crc32) => self.parse_data_subpacket(is_crc32),
+            None => self.parse_header(),
+        }
+    }
+
+    fn parse_header(&mut self) -> Option<Result<ZmodemItem, String>> {
+        loop {
+            // anything before the ZPAD is garbage (e.g., the "rz\r" that `sz` sends first, or the CR/LF after a hex header)
+            let Some(pad_idx) = self.buffer.iter().position(|byte| *byte == ZPAD) else {
+                self.buffer.clear();
+                return None;
+            };
+            self.buffer.drain(..pad_idx);
+            let zdle_idx = self.buffer.iter().position(|byte| *byte != ZPAD).unwrap_or(self.buffer.len());
+            if zdle_idx + 2 > self.buffer.len() {
+                return None;
+            }
+            if self.buffer[zdle_idx] != ZDLE {
+                self.buffer.drain(..zdle_idx);
+                continue;
+            }
+            let (encoding, body_idx) = (self.buffer[zdle_idx + 1], zdle_idx + 2);
+            let header_bytes = match encoding {
+                ZHEX => {
+                    // type and args, then the CRC, as lowercase hex
+                    if self.buffer.len() < body_idx + 14 {
+                        return None;
+                    }
+                    let hex_bytes: Option<Vec<u8>> = self.buffer[body_idx..body_idx + 14].chunks(2)
+                        .map(|hex_digits| std::str::from_utf8(hex_digits).ok().and_then(|s| u8::from_str_radix(s, 16).ok()))
+                        .collect();
+                    self.buffer.drain(..body_idx + 14);
+                    let Some(hex_bytes) = hex_bytes else {
+                        return Some(Err(String::from("Bad hex header")));
+                    };
+                    if get_crc16_xmodem(&hex_bytes[..5]).to_be_bytes() != hex_bytes[5..] {
+                        return Some(Err(String::from("CRC error in a header")));
+                    }
+                    hex_bytes[..5].to_vec()
+                }
+                ZBIN | ZBIN32 => {
+                    let crc_len = if encoding == ZBIN32 { 4 } else { 2 };
+                    let (decoded_bytes, decoded_len) = match decode_escaped(&self.buffer[body_idx..], 5 + crc_len)? {
+                        Ok(decoded) => decoded,
+                        Err(e) => {
+                            self.buffer.drain(..body_idx);
+                            return Some(Err(e));
+                        }
+                    };
+                    self.buffer.drain(..body_idx + decoded_len);
+                    let is_crc_ok = match encoding {
+                        ZBIN32 => get_crc32(&decoded_bytes[..5]).to_le_bytes() == decoded_bytes[5..],
+                        _ => get_crc16_xmodem(&decoded_bytes[..5]).to_be_bytes() == decoded_bytes[5..],
+                    };
+                    if !is_crc_ok {
+                        return Some(Err(String::from("CRC error in a header")));
+                    }
+                    decoded_bytes[..5].to_vec()
+                }
+                _ => {
+                    self.buffer.drain(..zdle_idx);
+                    continue;
+                }
+            };
+            let header = ZmodemHeader {
+                frame_type: header_bytes[0],
+                args: [header_bytes[1], header_bytes[2], header_bytes[3], header_bytes[4]],
+            };
+            if matches!(header.frame_type, ZDATA | ZFILE | ZSINIT | ZCOMMAND) {
+                self.data_crc32 = Some(encoding == ZBIN32);
+            }
+            return Some(Ok(ZmodemItem::Header(header)));
+        }
+    }
+
+    fn parse_data_subpacket(&mut self, is_crc32: bool) -> Option<Result<ZmodemItem, String>> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut idx = 0;
+        let frame_end = loop {
+            if data.len() > MAX_SUBPACKET_LEN {
+                return Some(Err(self.reset_to_headers(idx, "Data subpacket is too long")));
+            }
+            let byte = *self.buffer.get(idx)?;
+            idx += 1;
+            if matches!(byte, XON | XOFF) || matches!(byte ^ 0x80, XON | XOFF) {
+                continue; // flow control, not data
+            }
+            if byte != ZDLE {
+                data.push(byte);
+                continue;
+            }
+            let escaped_byte = *self.buffer.get(idx)?;
+            idx += 1;
+            match escaped_byte {
+                ZCRCE | ZCRCG | ZCRCQ | ZCRCW => break escaped_byte,
+                ZRUB0 => data.push(0x7F),
+                ZRUB1 => data.push(0xFF),
+                _ if escaped_byte & 0x60 == 0x40 => data.push(escaped_byte ^ 0x40),
+                _ => return Some(Err(self.reset_to_headers(idx, "Bad escape sequence in data"))),
+            }
+        };
+
+        let crc_len = if is_crc32 { 4 } else { 2 };
+        let (crc_bytes, crc_encoded_len) = match decode_escaped(&self.buffer[idx..], crc_len)? {
+            Ok(decoded) => decoded,
+            Err(e) => return Some(Err(self.reset_to_headers(idx, &e))),
+        };
+        idx += crc_encoded_len;
+        let mut crc_data = data.clone();
+        crc_data.push(frame_end);
+        let is_crc_ok = match is_crc32 {
+            true => get_crc32(&crc_data).to_le_bytes().as_slice() == crc_bytes.as_slice(),
+            false => get_crc16_xmodem(&crc_data).to_be_bytes().as_slice() == crc_bytes.as_slice(),
+        };
+        if !is_crc_ok {
+            return Some(Err(self.reset_to_headers(idx, "CRC error in data")));
+        }
+        self.buffer.drain(..idx);
+        if matches!(frame_end, ZCRCE | ZCRCW) {
+            self.data_crc32 = None;
+        }
+        Some(Ok(ZmodemItem::Data(data, frame_end)))
+    }
+
+    /// Drops a bad subpacket, and looks for the next header. Returns the error message.
+    fn reset_to_headers(&mut self, bad_len: usize, error_message: &str) -> String {
+        self.buffer.drain(..bad_len);
+        self.data_crc32 = None;
+        error_message.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SenderStage {
+    WaitingForReceiver,
+    WaitingForFilePosition,
+    /// A frame of data was sent, and its ZACK (or a ZRPOS, to resend from somewhere else) is expected.
+    SendingData,
+    /// ZEOF was sent, and the receiver's ZRINIT is expected.
+    WaitingForEofAck,
+    WaitingForFin,
+    Finished,
+}
+
+/// Sends a file with ZMODEM (to `rz` on the other side).
+pub struct ZmodemSender {
+    file_name: String,
+    data: Vec<u8>,
+
+    parser: ZmodemParser,
+    stage: SenderStage,
+    use_crc32: bool,
+
+    /// Where the next frame starts.
+    position: u64,
+
+    /// The last frame (or header), to send again after a timeout or a ZNAK.
+    last_packet: Vec<u8>,
+    last_send_time: Instant,
+    packet_retry_count: u32,
+    start_time: Instant,
+    start_request_count: u32,
+
+    status: FileTransferStatus,
+}
+
+impl ZmodemSender {
+    pub fn new(file_name: String, data: Vec<u8>) -> Self {
+        let mut status = FileTransferStatus::new(format!("ZMODEM send '{}'", file_name));
+        status.total_byte_count = Some(data.len() as u64);
+        Self {
+            file_name,
+            data,
+            parser: ZmodemParser::new(),
+            stage: SenderStage::WaitingForReceiver,
+            use_crc32: false,
+            position: 0,
+            last_packet: Vec::new(),
+            // so that the first tick starts right away
+            last_send_time: Instant::now() - START_REQUEST_INTERVAL,
+            packet_retry_count: 0,
+            start_time: Instant::now(),
+            start_request_count: 0,
+            status,
+        }
+    }
+
+    fn handle_item(&mut self, item: ZmodemItem) -> Vec<u8> {
+        let header = match item {
+            ZmodemItem::Header(header) => header,
+            ZmodemItem::Data(..) => return Vec::new(),
+            ZmodemItem::Cancelled => {
+                self.status.fail(String::from("Cancelled by the receiver"));
+                self.stage = SenderStage::Finished;
+                return Vec::new();
+            }
+        };
+        match (self.stage, header.frame_type) {
+            (SenderStage::WaitingForReceiver, ZRINIT) => {
+                self.use_crc32 = header.get_flags() & CANFC32 != 0;
+                self.status.add_event(format!("Receiver ready ({}-bit CRC)", if self.use_crc32 { 32 } else { 16 }));
+                let mut packet = build_bin_header(ZmodemHeader { frame_type: ZFILE, args: [0; 4] }, self.use_crc32);
+                let file_info = format!("{}\0{}\0", self.file_name, self.data.len());
+                packet.extend(build_data_subpacket(file_info.as_bytes(), ZCRCW, self.use_crc32));
+                self.stage = SenderStage::WaitingForFilePosition;
+                self.send_packet(packet)
+            }
+            (SenderStage::WaitingForFilePosition | SenderStage::SendingData | SenderStage::WaitingForEofAck, ZRPOS) => {
+                let position = header.get_position().min(self.data.len() as u64);
+                if self.stage != SenderStage::WaitingForFilePosition {
+                    self.status.retry_count += 1;
+                    self.status.add_event(format!("Receiver asked to resend from byte {}", position));
+                }
+                else if position > 0 {
+                    self.status.add_event(format!("Resuming from byte {}", position));
+                }
+                self.position = position;
+                self.status.transferred_byte_count = position;
+                self.send_next_frame()
+            }
+            (SenderStage::WaitingForFilePosition, ZSKIP) => {
+                self.status.fail(String::from("The receiver skipped the file (it may already have it)"));
+                self.stage = SenderStage::Finished;
+                build_hex_header(ZmodemHeader::with_position(ZFIN, 0))
+            }
+            (SenderStage::WaitingForFilePosition, ZRINIT) => {
+                // the ZFILE didn't get through
+                self.resend_packet("ZRINIT")
+            }
+            (SenderStage::SendingData, ZACK) => {
+                self.status.transferred_byte_count = self.position;
+                self.send_next_frame()
+            }
+            (SenderStage::WaitingForEofAck, ZRINIT) => {
+                self.status.transferred_byte_count = self.data.len() as u64;
+                self.stage = SenderStage::WaitingForFin;
+                self.send_packet(build_hex_header(ZmodemHeader::with_position(ZFIN, 0)))
+            }
+            (SenderStage::WaitingForFin, ZFIN) => {
+                self.stage = SenderStage::Finished;
+                self.status.add_event(format!("Sent '{}' ({} bytes)", self.file_name, self.data.len()));
+                self.status.state = FileTransferState::Done;
+                b"OO".to_vec() // over and out
+            }
+            (_, ZNAK) => self.resend_packet("ZNAK"),
+            (_, ZABORT | ZFERR | ZCAN) => {
+                self.status.fail(String::from("Aborted by the receiver"));
+                self.stage = SenderStage::Finished;
+                CANCEL_SEQUENCE.to_vec()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sends a frame of data from `position` (ending with ZCRCW), or the rest of the file and ZEOF.
+    fn send_next_frame(&mut self) -> Vec<u8> {
+        let file_len = self.data.len() as u64;
+        let mut packet = Vec::new();
+        if self.position < file_len {
+            packet.extend(build_bin_header(ZmodemHeader::with_position(ZDATA, self.position), self.use_crc32));
+            for subpacket_num in 1..=SUBPACKETS_PER_FRAME {
+                let start = self.position as usize;
+                let end = (start + SUBPACKET_LEN).min(self.data.len());
+                self.position = end as u64;
+                let frame_end = match (self.position >= file_len, subpacket_num == SUBPACKETS_PER_FRAME) {
+                    (true, _) => ZCRCE,
+                    (false, true) => ZCRCW,
+                    (false, false) => ZCRCG,
+                };
+                packet.extend(build_data_subpacket(&self.data[start..end], frame_end, self.use_crc32));
+                if frame_end != ZCRCG {
+                    break;
+                }
+            }
+        }
+        self.stage = match self.position >= file_len {
+            true => {
+                packet.extend(build_bin_header(ZmodemHeader::with_position(ZEOF, file_len), self.use_crc32));
+                SenderStage::WaitingForEofAck
+            }
+            false => SenderStage::SendingData,
+        };
+        self.send_packet(packet)
+    }
+
+    fn send_packet(&mut self, packet: Vec<u8>) -> Vec<u8> {
+        self.last_packet = packet.clone();
+        self.last_send_time = Instant::now();
+        self.packet_retry_count = 0;
+        packet
+    }
+
+    fn resend_packet(&mut self, reason: &str) -> Vec<u8> {
+        self.packet_retry_count += 1;
+        self.status.retry_count += 1;
+        if self.packet_retry_count > MAX_RETRIES {
+            self.status.fail(format!("Too many retries ({})", reason));
+            self.stage = SenderStage::Finished;
+            return CANCEL_SEQUENCE.to_vec();
+        }
+        self.status.add_event(format!("{}, sending the last frame again (retry {})", reason, self.packet_retry_count));
+        self.last_send_time = Instant::now();
+        self.last_packet.clone()
+    }
+}
+
+impl FileTransfer for ZmodemSender {
+    fn handle_rxd_data(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut reply: Vec<u8> = Vec::new();
+        self.parser.push(data);
+        while self.stage != SenderStage::Finished {
+            match self.parser.next_item() {
+                Some(Ok(item)) => reply.extend(self.handle_item(item)),
+                Some(Err(_)) => {} // a garbled reply; the receiver repeats it, or the timeout resends
+                None => break,
+            }
+        }
+        reply
+    }
+
+    fn tick(&mut self) -> Vec<u8> {
+        match self.stage {
+            SenderStage::Finished => Vec::new(),
+            SenderStage::WaitingForReceiver => {
+                if self.start_time.elapsed() > START_TIMEOUT {
+                    self.status.fail(String::from("The receiver didn't start"));
+                    self.stage = SenderStage::Finished;
+                    return CANCEL_SEQUENCE.to_vec();
+                }
+                if self.last_send_time.elapsed() < START_REQUEST_INTERVAL {
+                    return Vec::new();
+                }
+                self.last_send_time = Instant::now();
+                self.start_request_count += 1;
+                // "rz\r" starts the receiver, if the other side is a shell
+                let mut packet = match self.start_request_count {
+                    1 => b"rz\r".to_vec(),
+                    _ => Vec::new(),
+                };
+                self.status.add_event(String::from("Waiting for the receiver (run `rz` on the other side)..."));
+                packet.extend(build_hex_header(ZmodemHeader::with_position(ZRQINIT, 0)));
+                packet
+            }
+            _ => match self.last_send_time.elapsed() > REPLY_TIMEOUT {
+                true => self.resend_packet("Timeout"),
+                false => Vec::new(),
+            },
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<u8> {
+        if self.stage == SenderStage::Finished {
+            return Vec::new();
+        }
+        self.stage = SenderStage::Finished;
+        self.status.fail(String::from("Cancelled"));
+        CANCEL_SEQUENCE.to_vec()
+    }
+
+    fn get_status(&self) -> &FileTransferStatus {
+        &self.status
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReceiverStage {
+    /// ZRINIT was sent; a ZFILE (or ZFIN, at the end) is expected.
+    WaitingForFile,
+    /// The ZFILE header came in; its data subpacket (the file name and size) is expected.
+    WaitingForFileInfo,
+    ReceivingData,
+    /// ZFIN was sent back; the sender's "OO" is expected (so it doesn't end up in the transfer log).
+    WaitingForOverAndOut,
+    Finished,
+}
+
+/// Receives files with ZMODEM (from `sz` on the other side) into a directory.
+pub struct ZmodemReceiver {
+    save_dir: PathBuf,
+
+    parser: ZmodemParser,
+    stage: ReceiverStage,
+
+    /// Set after a bad subpacket (or a frame from the wrong position), until a ZDATA from the right position.
+    is_discarding_data: bool,
+
+    file_name: String,
+    file_data: Vec<u8>,
+    received_file_count: u32,
+
+    last_rx_time: Instant,
+    last_request_time: Instant,
+    start_request_count: u32,
+    error_count: u32,
+    over_and_out_count: u32,
+
+    status: FileTransferStatus,
+}
+
+impl ZmodemReceiver {
+    pub fn new(save_dir: PathBuf) -> Self {
+        let status = FileTransferStatus::new(format!("ZMODEM receive into '{}'", save_dir.display()));
+        Self {
+            save_dir,
+            parser: ZmodemParser::new(),
+            stage: ReceiverStage::WaitingForFile,
+            is_discarding_data: false,
+            file_name: String::new(),
+            file_data: Vec::new(),
+            received_file_count: 0,
+            last_rx_time: Instant::now(),
+            // so that the first tick sends ZRINIT right away
+            last_request_time: Instant::now() - START_REQUEST_INTERVAL,
+            start_request_count: 0,
+            error_count: 0,
+            over_and_out_count: 0,
+            status,
+        }
+    }
+
+    fn handle_item(&mut self, item: ZmodemItem) -> Vec<u8> {
+        match (self.stage, item) {
+            (_, ZmodemItem::Cancelled) => {
+                self.status.fail(String::from("Cancelled by the sender"));
+                self.stage = ReceiverStage::Finished;
+                Vec::new()
+            }
+            (_, ZmodemItem::Header(header)) => self.handle_header(header),
+            (ReceiverStage::WaitingForFileInfo, ZmodemItem::Data(data, _)) => {
+                self.start_file(&data);
+                self.send_position()
+            }
+            (ReceiverStage::ReceivingData, ZmodemItem::Data(data, frame_end)) if !self.is_discarding_data => {
+                self.file_data.extend(data);
+                self.error_count = 0;
+                self.status.transferred_byte_count = self.file_data.len() as u64;
+                match frame_end {
+                    ZCRCQ | ZCRCW => build_hex_header(ZmodemHeader::with_position(ZACK, self.file_data.len() as u64)),
+                    _ => Vec::new(),
+                }
+            }
+            (_, ZmodemItem::Data(_, frame_end)) => {
+                // e.g., ZSINIT's attention string (not used)
+                match (self.is_discarding_data, frame_end) {
+                    (false, ZCRCW) => build_hex_header(ZmodemHeader::with_position(ZACK, 0)),
+                    _ => Vec::new(),
+                }
+            }
+        }
+    }
+
+    fn handle_header(&mut self, header: ZmodemHeader) -> Vec<u8> {
+        match (self.stage, header.frame_type) {
+            (ReceiverStage::WaitingForFile, ZRQINIT) => self.send_ready(),
+            (ReceiverStage::WaitingForFile, ZSINIT) => {
+                self.is_discarding_data = false;
+                Vec::new() // ZACK'd once its data subpacket is in
+            }
+            (ReceiverStage::WaitingForFile | ReceiverStage::WaitingForFileInfo, ZFILE) => {
+                self.stage = ReceiverStage::WaitingForFileInfo;
+                Vec::new()
+            }
+            (ReceiverStage::ReceivingData, ZFILE) => {
+                // our ZRPOS was lost, so the sender sent the ZFILE again
+                self.stage = ReceiverStage::WaitingForFileInfo;
+                Vec::new()
+            }
+            (ReceiverStage::ReceivingData, ZDATA) => {
+                match header.get_position() == self.file_data.len() as u64 {
+                    true => {
+                        self.is_discarding_data = false;
+                        Vec::new()
+                    }
+                    false => {
+                        self.is_discarding_data = true;
+                        self.reject(format!("Data from byte {} (expected {})", header.get_position(), self.file_data.len()))
+                    }
+                }
+            }
+            (ReceiverStage::ReceivingData, ZEOF) if header.get_position() == self.file_data.len() as u64 => {
+                self.save_file();
+                if self.stage == ReceiverStage::Finished {
+                    return CANCEL_SEQUENCE.to_vec();
+                }
+                self.stage = ReceiverStage::WaitingForFile;
+                self.send_ready()
+            }
+            (ReceiverStage::WaitingForFile, ZFIN) => {
+                self.stage = ReceiverStage::WaitingForOverAndOut;
+                self.last_rx_time = Instant::now();
+                build_hex_header(ZmodemHeader::with_position(ZFIN, 0))
+            }
+            (_, ZABORT | ZFERR | ZCAN) => {
+                self.status.fail(String::from("Aborted by the sender"));
+                self.stage = ReceiverStage::Finished;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn finish(&mut self) {
+        self.stage = ReceiverStage::Finished;
+        self.status.state = FileTransferState::Done;
+        self.status.add_event(format!("Done ({} file{})", self.received_file_count,
+            if self.received_file_count == 1 { "" } else { "s" }));
+    }
+
+    /// ZRINIT: ready for a file.
+    fn send_ready(&mut self) -> Vec<u8> {
+        self.last_request_time = Instant::now();
+        build_hex_header(ZmodemHeader { frame_type: ZRINIT, args: [0, 0, 0, CANFDX | CANOVIO | CANFC32] })
+    }
+
+    /// ZRPOS: where to send from (what's been received so far).
+    fn send_position(&mut self) -> Vec<u8> {
+        self.last_request_time = Instant::now();
+        build_hex_header(ZmodemHeader::with_position(ZRPOS, self.file_data.len() as u64))
+    }
+
+    /// Handles the ZFILE's data subpacket: "name\0size mtime mode ...\0".
+    fn start_file(&mut self, file_info: &[u8]) {
+        let name_end = file_info.iter().position(|byte| *byte == 0).unwrap_or(file_info.len());
+        self.file_name = get_safe_file_name(&String::from_utf8_lossy(&file_info[..name_end]));
+        let file_size: Option<u64> = String::from_utf8_lossy(&file_info[(name_end + 1).min(file_info.len())..])
+            .split([' ', '\0']).next().and_then(|size| size.parse().ok());
+        self.status.title = format!("ZMODEM receive '{}'", self.file_name);
+        self.status.total_byte_count = file_size;
+        self.status.transferred_byte_count = 0;
+        self.status.add_event(format!("Receiving '{}'{}", self.file_name,
+            file_size.map(|size| format!(" ({} bytes)", size)).unwrap_or_default()));
+        self.file_data.clear();
+        self.is_discarding_data = false;
+        self.stage = ReceiverStage::ReceivingData;
+    }
+
+    fn save_file(&mut self) {
+        match save_received_file(&self.save_dir.join(&self.file_name), &self.file_data) {
+            Ok(saved_path) => {
+                self.received_file_count += 1;
+                self.status.add_event(format!("Saved '{}' ({} bytes)", saved_path.display(), self.file_data.len()));
+            }
+            Err(e) => {
+                self.status.fail(e);
+                self.stage = ReceiverStage::Finished;
+            }
+        }
+    }
+
+    /// Asks the sender to resend from what's been received so far.
+    fn reject(&mut self, reason: String) -> Vec<u8> {
+        self.error_count += 1;
+        self.status.retry_count += 1;
+        if self.error_count > MAX_RETRIES {
+            self.status.fail(format!("Too many errors ({})", reason));
+            self.stage = ReceiverStage::Finished;
+            return CANCEL_SEQUENCE.to_vec();
+        }
+        self.status.add_event(format!("{}, asking to resend from byte {}", reason, self.file_data.len()));
+        self.send_position()
+    }
+}
+
+impl FileTransfer for ZmodemReceiver {
+    fn handle_rxd_data(&mut self, data: &[u8]) -> Vec<u8> {
+        self.last_rx_time = Instant::now();
+        if self.stage == ReceiverStage::WaitingForOverAndOut {
+            self.over_and_out_count += data.iter().filter(|byte| **byte == b'O').count() as u32;
+            if self.over_and_out_count >= 2 {
+                self.finish();
+            }
+            return Vec::new();
+        }
+
+        let mut reply: Vec<u8> = Vec::new();
+        self.parser.push(data);
+        while !matches!(self.stage, ReceiverStage::Finished | ReceiverStage::WaitingForOverAndOut) {
+            match self.parser.next_item() {
+                Some(Ok(item)) => reply.extend(self.handle_item(item)),
+                Some(Err(e)) => {
+                    match self.stage {
+                        ReceiverStage::ReceivingData if !self.is_discarding_data => {
+                            self.is_discarding_data = true;
+                            reply.extend(self.reject(e));
+                        }
+                        _ => self.status.add_event(e),
+                    }
+                }
+                None => break,
+            }
+        }
+        reply
+    }
+
+    fn tick(&mut self) -> Vec<u8> {
+        match self.stage {
+            ReceiverStage::Finished => Vec::new(),
+            ReceiverStage::WaitingForOverAndOut => {
+                if self.last_rx_time.elapsed() > OVER_AND_OUT_TIMEOUT {
+                    self.finish();
+                }
+                Vec::new()
+            }
+            ReceiverStage::WaitingForFile if self.received_file_count == 0 => {
+                // keep telling the sender we're ready, until it starts
+                if self.last_request_time.elapsed() < START_REQUEST_INTERVAL {
+                    return Vec::new();
+                }
+                self.start_request_count += 1;
+                if self.start_request_count > MAX_RETRIES {
+                    self.status.fail(String::from("The sender didn't start"));
+                    self.stage = ReceiverStage::Finished;
+                    return CANCEL_SEQUENCE.to_vec();
+                }
+                self.status.add_event(String::from("Ready to receive (run `sz <file>` on the other side)..."));
+                self.send_ready()
+            }
+            _ => {
+                if self.last_rx_time.elapsed() < REPLY_TIMEOUT || self.last_request_time.elapsed() < REPLY_TIMEOUT {
+                    return Vec::new();
+                }
+                match self.stage {
+                    ReceiverStage::ReceivingData => self.reject(String::from("Timeout")),
+                    _ => {
+                        self.error_count += 1;
+                        if self.error_count > MAX_RETRIES {
+                            self.status.fail(String::from("The sender stopped responding"));
+                            self.stage = ReceiverStage::Finished;
+                            return CANCEL_SEQUENCE.to_vec();
+                        }
+                        self.send_ready()
+                    }
+                }
+            }
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<u8> {
+        match self.stage {
+            ReceiverStage::Finished => return Vec::new(),
+            ReceiverStage::WaitingForOverAndOut => {
+                // everything's been received already
+                self.finish();
+                return Vec::new();
+            }
+            _ => {}
+        }
+        self.stage = ReceiverStage::Finished;
+        self.status.fail(String::from("Cancelled"));
+        CANCEL_SEQUENCE.to_vec()
+    }
+
+    fn get_status(&self) -> &FileTransferStatus {
+        &self.status
+    }
+}
+
+/// Decodes `count` bytes of ZDLE-escaped data. Returns them and how many bytes they took (None if there aren't enough yet).
+fn decode_escaped(data: &[u8], count: usize) -> Option<Result<(Vec<u8>, usize), String>> {
+    let mut decoded: Vec<u8> = Vec::with_capacity(count);
+    let mut idx = 0;
+    while decoded.len() < count {
+        let byte = *data.get(idx)?;
+        idx += 1;
+        if matches!(byte, XON | XOFF) || matches!(byte ^ 0x80, XON | XOFF) {
+            continue;
+        }
+        if byte != ZDLE {
+            decoded.push(byte);
+            continue;
+        }
+        let escaped_byte = *data.get(idx)?;
+        idx += 1;
+        match escaped_byte {
+            ZRUB0 => decoded.push(0x7F),
+            ZRUB1 => decoded.push(0xFF),
+            _ if escaped_byte & 0x60 == 0x40 => decoded.push(escaped_byte ^ 0x40),
+            _ => return Some(Err(String::from("Bad escape sequence in a header"))),
+        }
+    }
+    Some(Ok((decoded, idx)))
+}
+
+/// Escapes the bytes that can't be sent as-is: ZDLE, and the flow control characters (and their high-bit versions).
+fn push_escaped(out: &mut Vec<u8>, data: &[u8]) {
+    for byte in data {
+        match *byte {
+            ZDLE | 0x10 | XON | XOFF | 0x90 | 0x91 | 0x93 | 0x98 => {
+                out.push(ZDLE);
+                out.push(byte ^ 0x40);
+            }
+            _ => out.push(*byte),
+        }
+    }
+}
+
+fn build_hex_header(header: ZmodemHeader) -> Vec<u8> {
+    let mut header_bytes = vec![header.frame_type];
+    header_bytes.extend(header.args);
+    header_bytes.extend(get_crc16_xmodem(&header_bytes).to_be_bytes());
+    let mut packet = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+    packet.extend(header_bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>().into_bytes());
+    packet.extend([b'\r', b'\n' | 0x80]);
+    if !matches!(header.frame_type, ZACK | ZFIN) {
+        packet.push(XON);
+    }
+    packet
+}
+
+fn build_bin_header(header: ZmodemHeader, use_crc32: bool) -> Vec<u8> {
+    let mut header_bytes = vec![header.frame_type];
+    header_bytes.extend(header.args);
+    match use_crc32 {
+        true => header_bytes.extend(get_crc32(&header_bytes).to_le_bytes()),
+        false => header_bytes.extend(get_crc16_xmodem(&header_bytes).to_be_bytes()),
+    }
+    let mut packet = vec![ZPAD, ZDLE, if use_crc32 { ZBIN32 } else { ZBIN }];
+    push_escaped(&mut packet, &header_bytes);
+    packet
+}
+
+/// A data subpacket: the escaped data, ZDLE and `frame_end`, and the CRC (of the data and `frame_end`).
+fn build_data_subpacket(data: &[u8], frame_end: u8, use_crc32: bool) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + data.len() / 16 + 8);
+    push_escaped(&mut packet, data);
+    packet.extend([ZDLE, frame_end]);
+    let mut crc_data = data.to_vec();
+    crc_data.push(frame_end);
+    match use_crc32 {
+        true => push_escaped(&mut packet, &get_crc32(&crc_data).to_le_bytes()),
+        false => push_escaped(&mut packet, &get_crc16_xmodem(&crc_data).to_be_bytes()),
+    }
+    packet
+}
+
+/// CRC-32 (the one used by Ethernet, zip, etc.).
+fn get_crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for each test's received files.
+    fn get_test_dir(test_name: &str) -> PathBuf {
+        let test_dir = std::env::temp_dir().join(format!("baud-boss-zmodem-test-{}-{}", std::process::id(), test_name));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&test_dir).unwrap();
+        test_dir
+    }
+
+    /// Includes every byte value, so that escaping (ZDLE, XON, XOFF, ...) is exercised.
+    fn get_test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|idx| (idx * 7 + idx / 256) as u8).collect()
+    }
+
+    /// Passes bytes back and forth (starting with the receiver's ZRINIT) until neither side has anything more
+    /// to send. `to_receiver` can change what's passed at each step (e.g., to corrupt it), like a noisy line.
+    fn pump(sender: &mut ZmodemSender, receiver: &mut ZmodemReceiver, mut to_receiver: impl FnMut(Vec<u8>) -> Vec<u8>) {
+        let mut sender_rx = receiver.tick();
+        for _ in 0..10_000 {
+            let receiver_rx = to_receiver(sender.handle_rxd_data(&sender_rx));
+            sender_rx = receiver.handle_rxd_data(&receiver_rx);
+            if sender_rx.is_empty() && receiver_rx.is_empty() {
+                return;
+            }
+        }
+        panic!("The transfer didn't finish");
+    }
+
+    #[test]
+    fn crc32_matches_known_vector() {
+        assert_eq!(get_crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(get_crc32(&[]), 0);
+    }
+
+    #[test]
+    fn hex_headers_match_known_vectors() {
+        // what `sz` and `rz` (lrzsz) send first
+        assert_eq!(build_hex_header(ZmodemHeader::with_position(ZRQINIT, 0)), b"**\x18B00000000000000\r\x8a\x11");
+        assert_eq!(
+            build_hex_header(ZmodemHeader { frame_type: ZRINIT, args: [0, 0, 0, CANFDX | CANOVIO | CANFC32] }),
+            b"**\x18B0100000023be50\r\x8a\x11",
+        );
+        // no XON after ZACK and ZFIN
+        assert_eq!(build_hex_header(ZmodemHeader::with_position(ZFIN, 0)), b"**\x18B0800000000022d\r\x8a");
+    }
+
+    #[test]
+    fn parser_reads_back_built_packets() {
+        for use_crc32 in [false, true] {
+            let data: Vec<u8> = (0..=255).collect();
+            let mut parser = ZmodemParser::new();
+            parser.push(&build_bin_header(ZmodemHeader::with_position(ZDATA, 0x1234_5678), use_crc32));
+            parser.push(&build_data_subpacket(&data, ZCRCE, use_crc32));
+            parser.push(&build_hex_header(ZmodemHeader::with_position(ZEOF, 256)));
+
+            let Some(Ok(ZmodemItem::Header(header))) = parser.next_item() else { panic!("Expected the ZDATA header") };
+            assert_eq!((header.frame_type, header.get_position()), (ZDATA, 0x1234_5678));
+            let Some(Ok(ZmodemItem::Data(parsed_data, frame_end))) = parser.next_item() else { panic!("Expected a subpacket") };
+            assert_eq!((parsed_data, frame_end), (data, ZCRCE));
+            let Some(Ok(ZmodemItem::Header(header))) = parser.next_item() else { panic!("Expected the ZEOF header") };
+            assert_eq!((header.frame_type, header.get_position()), (ZEOF, 256));
+            assert!(parser.next_item().is_none());
+        }
+    }
+
+    #[test]
+    fn zmodem_transfer() {
+        let test_dir = get_test_dir("transfer");
+        let data = get_test_data(10_000); // a few frames, and a short last subpacket
+        let mut sender = ZmodemSender::new(String::from("data.bin"), data.clone());
+        let mut receiver = ZmodemReceiver::new(test_dir.clone());
+        pump(&mut sender, &mut receiver, |data| data);
+
+        assert_eq!(sender.get_status().state, FileTransferState::Done);
+        assert_eq!(receiver.get_status().state, FileTransferState::Done);
+        assert_eq!(sender.get_status().retry_count, 0);
+        assert_eq!(receiver.get_status().total_byte_count, Some(data.len() as u64));
+        assert_eq!(std::fs::read(test_dir.join("data.bin")).unwrap(), data);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn zmodem_resumes_from_zrpos_after_a_corrupted_subpacket() {
+        let test_dir = get_test_dir("corrupted");
+        let data = get_test_data(10_000);
+        let mut sender = ZmodemSender::new(String::from("data.bin"), data.clone());
+        let mut receiver = ZmodemReceiver::new(test_dir.clone());
+        let mut frame_count = 0;
+        pump(&mut sender, &mut receiver, |mut data| {
+            if data.len() > SUBPACKET_LEN {
+                frame_count += 1;
+                if frame_count == 2 {
+                    // in the second subpacket of the second frame (the first one gets through)
+                    let idx = SUBPACKET_LEN + SUBPACKET_LEN / 2;
+                    data[idx] = if data[idx] == b'A' { b'B' } else { b'A' };
+                }
+            }
+            data
+        });
+
+        assert_eq!(sender.get_status().state, FileTransferState::Done);
+        assert_eq!(receiver.get_status().state, FileTransferState::Done);
+        assert_eq!(sender.get_status().retry_count, 1);
+        assert_eq!(receiver.get_status().retry_count, 1);
+        assert!(sender.get_status().events.iter()
+            .any(|event| event.contains(&format!("resend from byte {}", (SUBPACKETS_PER_FRAME + 1) * SUBPACKET_LEN))));
+        assert_eq!(std::fs::read(test_dir.join("data.bin")).unwrap(), data);
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn detector_finds_a_header_split_across_reads() {
+        let zrinit = build_hex_header(ZmodemHeader { frame_type: ZRINIT, args: [0, 0, 0, CANFDX | CANOVIO | CANFC32] });
+        for split_idx in 1..zrinit.len() {
+            let mut detector = ZmodemDetector::new();
+            let mut first_read = b"$ rz\r\n".to_vec();
+            first_read.extend(&zrinit[..split_idx]);
+            let requests = [detector.handle_rxd_data(&first_read), detector.handle_rxd_data(&zrinit[split_idx..])];
+            assert_eq!(requests.iter().flatten().collect::<Vec<_>>(), vec![&ZmodemRequest::Send], "split at {}", split_idx);
+        }
+
+        let mut detector = ZmodemDetector::new();
+        assert_eq!(detector.handle_rxd_data(b"**\x18B0"), None);
+        assert_eq!(detector.handle_rxd_data(b"0000000000000\r\x8a\x11"), Some(ZmodemRequest::Receive));
+    }
+
+    #[test]
+    fn detector_ignores_repeated_requests_and_other_data() {
+        let zrinit = build_hex_header(ZmodemHeader { frame_type: ZRINIT, args: [0, 0, 0, CANFDX | CANOVIO | CANFC32] });
+        let mut detector = ZmodemDetector::new();
+        assert_eq!(detector.handle_rxd_data(b"**B01 is not a header, and neither is \x18B01"), None);
+        assert_eq!(detector.handle_rxd_data(&zrinit), Some(ZmodemRequest::Send));
+        // `rz` repeats its ZRINIT every few seconds while waiting
+        assert_eq!(detector.handle_rxd_data(&zrinit), None);
+    }
+}